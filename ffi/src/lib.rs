@@ -0,0 +1,7 @@
+//! C library of `ecs-logger`
+//!
+//! This crate builds the C API of the [`ffi`](ecs_logger::ffi) module into `libecs_logger_ffi.so` (`.dylib` on macOS, `ecs_logger_ffi.dll` on Windows)
+//! and `libecs_logger_ffi.a` (`ecs_logger_ffi.lib` on Windows), declared in `include/ecs_logger.h` at the root of the repository.
+//! See the README of the repository for how to link against them.
+
+pub use ecs_logger::ffi::*;
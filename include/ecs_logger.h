@@ -0,0 +1,34 @@
+/* C API of ecs-logger. Build the library with `cargo build --release -p ecs-logger-ffi` and link against libecs_logger_ffi. */
+
+#ifndef ECS_LOGGER_H
+#define ECS_LOGGER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ECS_LOGGER_OK 0
+#define ECS_LOGGER_ERR_INVALID_ARGUMENT (-1)
+#define ECS_LOGGER_ERR_ALREADY_INITIALIZED (-2)
+#define ECS_LOGGER_ERR_PANIC (-3)
+
+#define ECS_LOGGER_LEVEL_ERROR 1
+#define ECS_LOGGER_LEVEL_WARN 2
+#define ECS_LOGGER_LEVEL_INFO 3
+#define ECS_LOGGER_LEVEL_DEBUG 4
+#define ECS_LOGGER_LEVEL_TRACE 5
+
+/* Initializes the global logger. `config` uses the RUST_LOG syntax; pass NULL to read RUST_LOG. */
+int ecs_logger_init(const char *config);
+
+/* Logs `message`. `fields_json` is NULL or a JSON object merged into the event. */
+int ecs_logger_log(int level, const char *target, const char *message, const char *fields_json);
+
+/* Flushes buffered log records. Call this before the process exits. Errors, including panics, are ignored. */
+void ecs_logger_flush(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ECS_LOGGER_H */
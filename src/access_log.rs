@@ -0,0 +1,219 @@
+//! HTTP access log events
+
+use crate::extra_fields::with_scoped_fields;
+use serde_json::{Map, Value};
+use std::net::IpAddr;
+use std::panic::Location;
+use std::time::Duration;
+
+/// Summary of an HTTP request and its response, logged as an ECS access log event.
+///
+/// The event has the `http.*`, `url.*`, `client.*`, and `user_agent.*` fields,
+/// and is categorized with `event.category: ["web"]` and `event.type: ["access"]`.
+/// `event.outcome` is `failure` for 4xx and 5xx status codes, and `success` otherwise.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::AccessLog;
+/// use std::time::Duration;
+///
+/// ecs_logger::init();
+///
+/// AccessLog::new("GET", "/api/users")
+///     .query("page=2")
+///     .status(200)
+///     .duration(Duration::from_millis(12))
+///     .response_bytes(5120)
+///     .client_ip("192.0.2.1".parse().unwrap())
+///     .user_agent("curl/8.0.1")
+///     .log();
+/// // {"log.level":"INFO","message":"GET /api/users 200","http.request.method":"GET","url.path":"/api/users",...}
+/// ```
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+    method: String,
+    path: String,
+    query: Option<String>,
+    status: Option<u16>,
+    duration: Option<Duration>,
+    request_bytes: Option<u64>,
+    response_bytes: Option<u64>,
+    client_ip: Option<IpAddr>,
+    user_agent: Option<String>,
+}
+
+impl AccessLog {
+    /// Creates a summary of a request with `method` (e.g. `GET`) and `path` (e.g. `/api/users`).
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        AccessLog {
+            method: method.into(),
+            path: path.into(),
+            query: None,
+            status: None,
+            duration: None,
+            request_bytes: None,
+            response_bytes: None,
+            client_ip: None,
+            user_agent: None,
+        }
+    }
+
+    /// Sets the query string without the leading `?`, logged as `url.query`.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Sets the response status code, logged as `http.response.status_code`.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Sets the time taken to respond, logged as `event.duration` in nanoseconds.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets the size of the request body, logged as `http.request.body.bytes`.
+    pub fn request_bytes(mut self, bytes: u64) -> Self {
+        self.request_bytes = Some(bytes);
+        self
+    }
+
+    /// Sets the size of the response body, logged as `http.response.body.bytes`.
+    pub fn response_bytes(mut self, bytes: u64) -> Self {
+        self.response_bytes = Some(bytes);
+        self
+    }
+
+    /// Sets the IP address of the client, logged as `client.ip`.
+    pub fn client_ip(mut self, ip: IpAddr) -> Self {
+        self.client_ip = Some(ip);
+        self
+    }
+
+    /// Sets the `User-Agent` header of the request, logged as `user_agent.original`.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Returns the ECS fields of the event with dotted keys.
+    pub fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let mut insert = |key: &str, value: Value| {
+            fields.insert(key.to_string(), value);
+        };
+
+        insert("event.kind", "event".into());
+        insert("event.category", vec!["web"].into());
+        insert("event.type", vec!["access"].into());
+        insert("http.request.method", self.method.clone().into());
+        insert("url.path", self.path.clone().into());
+        match &self.query {
+            Some(query) => {
+                insert("url.query", query.clone().into());
+                insert("url.original", format!("{}?{}", self.path, query).into());
+            }
+            None => insert("url.original", self.path.clone().into()),
+        }
+        if let Some(status) = self.status {
+            insert("http.response.status_code", status.into());
+            let outcome = if status >= 400 { "failure" } else { "success" };
+            insert("event.outcome", outcome.into());
+        }
+        if let Some(duration) = self.duration {
+            let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+            insert("event.duration", nanos.into());
+        }
+        if let Some(bytes) = self.request_bytes {
+            insert("http.request.body.bytes", bytes.into());
+        }
+        if let Some(bytes) = self.response_bytes {
+            insert("http.response.body.bytes", bytes.into());
+        }
+        if let Some(ip) = self.client_ip {
+            insert("client.ip", ip.to_string().into());
+            insert("client.address", ip.to_string().into());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            insert("user_agent.original", user_agent.clone().into());
+        }
+
+        fields
+    }
+
+    /// Logs the event at the `INFO` level.
+    ///
+    /// Like [`ResultExt`](crate::ResultExt), the event has the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn log(self) {
+        let location = Location::caller();
+        let message = match self.status {
+            Some(status) => format!("{} {} {}", self.method, self.path, status),
+            None => format!("{} {}", self.method, self.path),
+        };
+
+        with_scoped_fields(self.fields(), || {
+            crate::log_at(log::Level::Info, format_args!("{}", message), location)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields() {
+        let fields = AccessLog::new("POST", "/login")
+            .query("next=%2F")
+            .status(401)
+            .duration(Duration::from_micros(1500))
+            .request_bytes(64)
+            .response_bytes(12)
+            .client_ip("2001:db8::1".parse().unwrap())
+            .user_agent("Mozilla/5.0")
+            .fields();
+
+        assert_eq!(
+            Value::Object(fields),
+            json!({
+                "event.kind": "event",
+                "event.category": ["web"],
+                "event.type": ["access"],
+                "http.request.method": "POST",
+                "url.path": "/login",
+                "url.query": "next=%2F",
+                "url.original": "/login?next=%2F",
+                "http.response.status_code": 401,
+                "event.outcome": "failure",
+                "event.duration": 1_500_000,
+                "http.request.body.bytes": 64,
+                "http.response.body.bytes": 12,
+                "client.ip": "2001:db8::1",
+                "client.address": "2001:db8::1",
+                "user_agent.original": "Mozilla/5.0",
+            })
+        );
+    }
+
+    #[test]
+    fn test_fields_minimal() {
+        assert_eq!(
+            Value::Object(AccessLog::new("GET", "/").fields()),
+            json!({
+                "event.kind": "event",
+                "event.category": ["web"],
+                "event.type": ["access"],
+                "http.request.method": "GET",
+                "url.path": "/",
+                "url.original": "/",
+            })
+        );
+    }
+}
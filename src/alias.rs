@@ -0,0 +1,201 @@
+//! Aliases of fields renamed between ECS major versions
+
+use crate::KeyStyle;
+use serde_json::{Map, Value};
+
+type JsonMap = Map<String, Value>;
+
+/// Group of fields which were renamed between ECS 1.x and 8.x.
+///
+/// When a group is enabled with [`FormatOptions::alias_fields`](crate::FormatOptions::alias_fields), an event which has a
+/// field of the group under either name gets the other name too, so that dashboards built for either version keep working
+/// while indices are migrated. A field which already has both names is left untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AliasGroup {
+    /// `log.original` (1.x) and `event.original` (8.x).
+    LogOriginal,
+
+    /// `process.ppid` (1.x) and `process.parent.pid` (8.x).
+    ProcessParentPid,
+
+    /// `host.user.*` (1.x) and `user.*` (8.x).
+    HostUser,
+}
+
+impl AliasGroup {
+    /// All groups, i.e. the fields renamed in ECS 8.0
+    const ALL: [AliasGroup; 3] = [
+        AliasGroup::LogOriginal,
+        AliasGroup::ProcessParentPid,
+        AliasGroup::HostUser,
+    ];
+
+    /// Returns the 1.x and 8.x names of the group, which are matched against whole keys and key prefixes
+    fn names(self) -> (&'static str, &'static str) {
+        match self {
+            AliasGroup::LogOriginal => ("log.original", "event.original"),
+            AliasGroup::ProcessParentPid => ("process.ppid", "process.parent.pid"),
+            AliasGroup::HostUser => ("host.user", "user"),
+        }
+    }
+}
+
+/// Inserts the missing aliases of the fields of `groups` into `json_map` with dotted keys
+pub(crate) fn add_aliases(json_map: &mut JsonMap, groups: &[AliasGroup]) {
+    if groups.is_empty() {
+        return;
+    }
+
+    let flat = KeyStyle::Flat.apply(json_map.clone());
+    for group in groups {
+        let (old, new) = group.names();
+        for (from, to) in [(old, new), (new, old)] {
+            for (key, value) in &flat {
+                let Some(rest) = strip_name(key, from) else {
+                    continue;
+                };
+
+                let alias = format!("{}{}", to, rest);
+                if !flat.contains_key(&alias) && !json_map.contains_key(&alias) {
+                    json_map.insert(alias, value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Moves the fields of `json_map` which have ECS 1.x names to their 8.x names, unless the 8.x names are set already
+pub(crate) fn rename_to_v8(json_map: &mut JsonMap) {
+    for group in AliasGroup::ALL {
+        let (old, new) = group.names();
+        let renamed: JsonMap = take_fields(json_map, old)
+            .into_iter()
+            .map(|(rest, value)| (format!("{}{}", new, rest), value))
+            .collect();
+        if renamed.is_empty() {
+            continue;
+        }
+
+        let flat = KeyStyle::Flat.apply(json_map.clone());
+        for (key, value) in KeyStyle::Flat.apply(renamed) {
+            if !flat.contains_key(&key) && !json_map.contains_key(&key) {
+                json_map.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Removes `name` and the fields under it from `json_map`, whether the keys are dotted or nested,
+/// and returns them with the rest of their keys, e.g. `(".name", "root")` for `host.user.name`
+fn take_fields(json_map: &mut JsonMap, name: &str) -> Vec<(String, Value)> {
+    let mut fields = Vec::new();
+    let keys: Vec<String> = json_map.keys().cloned().collect();
+    for key in keys {
+        if let Some(rest) = strip_name(&key, name) {
+            let rest = rest.to_string();
+            if let Some(value) = json_map.remove(&key) {
+                fields.push((rest, value));
+            }
+        } else if let Some(inner) = name
+            .strip_prefix(key.as_str())
+            .and_then(|rest| rest.strip_prefix('.'))
+        {
+            if let Some(Value::Object(object)) = json_map.get_mut(&key) {
+                fields.extend(take_fields(object, inner));
+                if object.is_empty() {
+                    json_map.remove(&key);
+                }
+            }
+        }
+    }
+    fields
+}
+
+/// Returns the rest of `key` if it is `name` or a field under `name`
+fn strip_name<'a>(key: &'a str, name: &str) -> Option<&'a str> {
+    let rest = key.strip_prefix(name)?;
+    (rest.is_empty() || rest.starts_with('.')).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_add_aliases() {
+        let mut map = json!({
+            "log.original": "raw line",
+            "process": { "ppid": 1 },
+            "user.name": "alice",
+            "host.user.name": "root",
+            "host.user.id": "0",
+            "username": "not an alias",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        add_aliases(
+            &mut map,
+            &[
+                AliasGroup::LogOriginal,
+                AliasGroup::ProcessParentPid,
+                AliasGroup::HostUser,
+            ],
+        );
+        assert_eq!(
+            Value::Object(map),
+            json!({
+                "log.original": "raw line",
+                "process": { "ppid": 1 },
+                "user.name": "alice",
+                "host.user.name": "root",
+                "host.user.id": "0",
+                "username": "not an alias",
+                "event.original": "raw line",
+                "process.parent.pid": 1,
+                "user.id": "0",
+            })
+        );
+    }
+
+    #[test]
+    fn test_rename_to_v8() {
+        let mut map = json!({
+            "log.original": "raw line",
+            "process": { "ppid": 1, "pid": 2 },
+            "host": { "user": { "name": "root" }, "name": "web-1" },
+            "host.user.id": "0",
+            "user.id": "42",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        rename_to_v8(&mut map);
+        assert_eq!(
+            Value::Object(map),
+            json!({
+                "process": { "pid": 2 },
+                "host": { "name": "web-1" },
+                "user.id": "42",
+                "event.original": "raw line",
+                "process.parent.pid": 1,
+                "user.name": "root",
+            })
+        );
+    }
+
+    #[test]
+    fn test_add_aliases_disabled() {
+        let mut map = json!({ "event.original": "raw line" })
+            .as_object()
+            .unwrap()
+            .clone();
+
+        add_aliases(&mut map, &[AliasGroup::HostUser]);
+        assert_eq!(Value::Object(map), json!({ "event.original": "raw line" }));
+    }
+}
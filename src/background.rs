@@ -0,0 +1,177 @@
+//! Deferred formatting on a background thread
+
+use crate::ecs::ProcessThread;
+use crate::extra_fields::{scoped_fields, with_scoped_fields};
+use crate::function::{current_function, with_function};
+use crate::owned::OwnedRecord;
+use crate::process::{current_thread, with_thread};
+use crate::sink::Sink;
+use crate::template::{current_template, with_template};
+use crate::trace::TraceContextProvider;
+use crate::FormatOptions;
+use chrono::{DateTime, Utc};
+use log::Record;
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+thread_local! {
+    /// Whether the current thread is the writer thread.
+    static ON_WRITER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Everything captured on the logging thread which is needed to encode an event later.
+struct Deferred {
+    timestamp: DateTime<Utc>,
+    record: OwnedRecord,
+    scoped_fields: Map<String, Value>,
+    thread: ProcessThread<'static>,
+    template: Option<&'static str>,
+    function: Option<&'static str>,
+}
+
+enum Message {
+    Event(Box<Deferred>),
+    Flush(SyncSender<()>),
+}
+
+/// Handle to the thread which encodes and writes events.
+///
+/// The thread exits once the handle is dropped and all queued events are written.
+pub(crate) struct Background {
+    sender: SyncSender<Message>,
+    /// Number of the events queued and not yet taken by the writer thread
+    queue_depth: Arc<AtomicUsize>,
+    trace_context: Option<TraceContextProvider>,
+}
+
+impl Background {
+    /// Spawns the writer thread with a queue of `capacity` events.
+    pub(crate) fn spawn(sink: Sink, mut options: FormatOptions, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let queue_depth = Arc::new(AtomicUsize::new(0));
+        // The trace context belongs to the logging thread, so it is captured with the record instead.
+        let trace_context = options.trace_context.take();
+
+        thread::Builder::new()
+            .name("ecs-logger".to_string())
+            .spawn({
+                let queue_depth = Arc::clone(&queue_depth);
+                move || run(receiver, queue_depth, sink, options)
+            })
+            .expect("failed to spawn the ecs-logger thread");
+
+        Background {
+            sender,
+            queue_depth,
+            trace_context,
+        }
+    }
+
+    /// Captures `record` and queues it, blocking while the queue is full.
+    pub(crate) fn send(&self, record: &Record) {
+        let mut scoped_fields = scoped_fields();
+        if let Some(trace_context) = &self.trace_context {
+            trace_context.insert_into(&mut scoped_fields);
+        }
+
+        let deferred = Deferred {
+            timestamp: crate::timestamp::get_timestamp(),
+            record: OwnedRecord::from(record),
+            scoped_fields,
+            thread: current_thread(),
+            template: current_template(),
+            function: current_function(),
+        };
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+        if self
+            .sender
+            .send(Message::Event(Box::new(deferred)))
+            .is_err()
+        {
+            self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the counter of the events which are queued and not yet taken by the writer thread.
+    pub(crate) fn queue_depth(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.queue_depth)
+    }
+
+    /// Waits until all queued events are written and the writer is flushed.
+    pub(crate) fn flush(&self) {
+        let (ack_sender, ack_receiver) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+    }
+
+    /// Like [`flush`](Self::flush), but gives up after `timeout`, and returns immediately on the writer thread,
+    /// which cannot wait for itself.
+    pub(crate) fn flush_timeout(&self, timeout: Duration) {
+        if ON_WRITER.with(Cell::get) {
+            return;
+        }
+
+        let (ack_sender, ack_receiver) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv_timeout(timeout);
+        }
+    }
+}
+
+fn run(
+    receiver: Receiver<Message>,
+    queue_depth: Arc<AtomicUsize>,
+    mut sink: Sink,
+    options: FormatOptions,
+) {
+    ON_WRITER.with(|w| w.set(true));
+    let mut buf = Vec::new();
+
+    for message in receiver {
+        match message {
+            Message::Event(deferred) => {
+                queue_depth.fetch_sub(1, Ordering::Relaxed);
+                let Deferred {
+                    timestamp,
+                    record,
+                    scoped_fields,
+                    thread,
+                    template,
+                    function,
+                } = *deferred;
+
+                buf.clear();
+                let write = || {
+                    with_scoped_fields(scoped_fields, || {
+                        record.with_record(|record| {
+                            crate::write_event_at(&mut buf, timestamp, record, &options)
+                        })
+                    })
+                };
+                let write = || match template {
+                    Some(template) => with_template(template, write),
+                    None => write(),
+                };
+                let result = with_thread(thread, || match function {
+                    Some(function) => with_function(function, write),
+                    None => write(),
+                });
+                if result.is_ok() {
+                    sink.write_line(&buf);
+                }
+            }
+            Message::Flush(ack) => {
+                let _ = sink.flush();
+                let _ = ack.send(());
+            }
+        }
+    }
+
+    let _ = sink.flush();
+}
@@ -0,0 +1,246 @@
+//! Broadcast of rendered events to multiple consumers
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Channel which delivers each rendered event to every [`BroadcastReceiver`].
+///
+/// The channel keeps the latest `capacity` events. A receiver which falls further behind skips the oldest events
+/// and is told how many it missed, so a slow consumer (e.g. a WebSocket client) never blocks the logger.
+///
+/// Register it to a logger with [`Builder::broadcast`](crate::Builder::broadcast).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::Broadcast;
+///
+/// let broadcast = Broadcast::new(1024);
+/// ecs_logger::Builder::new()
+///     .filter_level(log::LevelFilter::Info)
+///     .broadcast(&broadcast)
+///     .init();
+///
+/// let mut receiver = broadcast.subscribe();
+/// log::info!("Hello {}!", "world");
+/// assert!(receiver.recv().unwrap().contains("Hello world!"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half of a [`Broadcast`].
+///
+/// Each receiver gets the events sent after it is created.
+#[derive(Debug)]
+pub struct BroadcastReceiver {
+    shared: Arc<Shared>,
+    next: u64,
+}
+
+/// Error returned when a [`BroadcastReceiver`] fell behind and the oldest events were dropped.
+///
+/// The receiver continues from the oldest event still kept by the channel.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("receiver lagged behind and missed {0} events")]
+pub struct Lagged(pub u64);
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    sent: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    events: VecDeque<Arc<str>>,
+    /// Sequence number of `events[0]`
+    head: u64,
+    capacity: usize,
+}
+
+impl State {
+    fn tail(&self) -> u64 {
+        self.head + self.events.len() as u64
+    }
+}
+
+impl Broadcast {
+    /// Creates a channel which keeps the latest `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+
+        Broadcast {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    events: VecDeque::with_capacity(capacity),
+                    head: 0,
+                    capacity,
+                }),
+                sent: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Creates a receiver which gets the events sent from now on.
+    pub fn subscribe(&self) -> BroadcastReceiver {
+        let next = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .tail();
+
+        BroadcastReceiver {
+            shared: Arc::clone(&self.shared),
+            next,
+        }
+    }
+
+    /// Sends `event` to all receivers, dropping the oldest event if the channel is full.
+    pub fn send(&self, event: &str) {
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if state.events.len() == state.capacity {
+            state.events.pop_front();
+            state.head += 1;
+        }
+        state.events.push_back(Arc::from(event));
+
+        self.shared.sent.notify_all();
+    }
+}
+
+impl BroadcastReceiver {
+    /// Blocks until the next event is available and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Lagged`] if events were dropped before this receiver got them.
+    pub fn recv(&mut self) -> Result<Arc<str>, Lagged> {
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        loop {
+            if let Some(event) = take(&mut self.next, &state)? {
+                return Ok(event);
+            }
+            state = self
+                .shared
+                .sent
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Returns the next event if available, or `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Lagged`] if events were dropped before this receiver got them.
+    pub fn try_recv(&mut self) -> Result<Option<Arc<str>>, Lagged> {
+        let state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        take(&mut self.next, &state)
+    }
+
+    /// Waits up to `timeout` for the next event and returns it, or `None` if no event is sent in time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Lagged`] if events were dropped before this receiver got them.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Arc<str>>, Lagged> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        loop {
+            if let Some(event) = take(&mut self.next, &state)? {
+                return Ok(Some(event));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            state = self
+                .shared
+                .sent
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+/// Returns the event at `next` and advances it, or skips to the oldest event kept in `state` if it was dropped.
+fn take(next: &mut u64, state: &State) -> Result<Option<Arc<str>>, Lagged> {
+    if *next < state.head {
+        let missed = state.head - *next;
+        *next = state.head;
+        return Err(Lagged(missed));
+    }
+
+    let event = state.events.get((*next - state.head) as usize).cloned();
+    if event.is_some() {
+        *next += 1;
+    }
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broadcast() {
+        let broadcast = Broadcast::new(2);
+        broadcast.send("before");
+
+        let mut a = broadcast.subscribe();
+        let mut b = broadcast.subscribe();
+        assert_eq!(a.try_recv(), Ok(None));
+
+        broadcast.send("1");
+        assert_eq!(a.recv().as_deref(), Ok("1"));
+
+        broadcast.send("2");
+        broadcast.send("3");
+        assert_eq!(a.try_recv().unwrap().as_deref(), Some("2"));
+        assert_eq!(a.try_recv().unwrap().as_deref(), Some("3"));
+
+        // `b` missed "1"
+        assert_eq!(b.try_recv(), Err(Lagged(1)));
+        assert_eq!(b.try_recv().unwrap().as_deref(), Some("2"));
+        assert_eq!(b.try_recv().unwrap().as_deref(), Some("3"));
+        assert_eq!(b.recv_timeout(Duration::from_millis(1)), Ok(None));
+    }
+
+    #[test]
+    fn test_broadcast_recv_blocks() {
+        let broadcast = Broadcast::new(4);
+        let mut receiver = broadcast.subscribe();
+
+        let sender = broadcast.clone();
+        let handle = std::thread::spawn(move || sender.send("hello"));
+
+        assert_eq!(receiver.recv().as_deref(), Ok("hello"));
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! `event.kind`, `event.category`, `event.type`, and `event.outcome` fields
+
+use crate::extra_fields::to_json_map;
+use crate::Outcome;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+
+/// [ECS categorization fields](https://www.elastic.co/guide/en/ecs/current/ecs-category-field-values-reference.html) of the log records.
+///
+/// Detection rules of Elastic Security select events by these fields, so security and audit logs should set them.
+/// Set them for every record with [`FormatOptions::event_categorization`](crate::FormatOptions::event_categorization),
+/// or for the records of a block with [`in_scope`](Self::in_scope), [`enter`](Self::enter), or the other ways of [`ToEcsFields`](crate::ToEcsFields).
+/// The values are not validated against the allowed values of ECS.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{Categorization, Outcome};
+///
+/// ecs_logger::init();
+///
+/// Categorization::new()
+///     .kind("event")
+///     .category("file")
+///     .event_type("deletion")
+///     .outcome(Outcome::Success)
+///     .in_scope(|| log::info!("Deleted /tmp/report.csv"));
+/// // {"log.level":"INFO","message":"Deleted /tmp/report.csv","event.kind":"event","event.category":["file"],"event.type":["deletion"],"event.outcome":"success",...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Categorization {
+    kind: Option<String>,
+    category: Vec<String>,
+    event_type: Vec<String>,
+    outcome: Option<Outcome>,
+}
+
+impl Categorization {
+    /// Creates an empty categorization.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `event.kind`, e.g. `event`, `alert`, or `metric`.
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    /// Adds a value to `event.category`, e.g. `authentication` or `network`.
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.category.push(category.into());
+        self
+    }
+
+    /// Adds a value to `event.type`, e.g. `start` or `denied`.
+    pub fn event_type(mut self, event_type: impl Into<String>) -> Self {
+        self.event_type.push(event_type.into());
+        self
+    }
+
+    /// Sets `event.outcome`.
+    pub fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    /// Inserts the fields into `event`, keeping the fields which are set already as dotted or nested keys.
+    pub(crate) fn insert_into(&self, event: &mut Map<String, Value>) {
+        let fields =
+            to_json_map(self).expect("Categorization should be converted into a JSON object");
+        for (key, value) in fields {
+            let name = key.strip_prefix("event.").unwrap_or(&key);
+            let nested = event.get("event").and_then(|e| e.get(name)).is_some();
+            if !nested && !event.contains_key(&key) {
+                event.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Serialize for Categorization {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(kind) = &self.kind {
+            map.serialize_entry("event.kind", kind)?;
+        }
+        if !self.category.is_empty() {
+            map.serialize_entry("event.category", &self.category)?;
+        }
+        if !self.event_type.is_empty() {
+            map.serialize_entry("event.type", &self.event_type)?;
+        }
+        if let Some(outcome) = self.outcome {
+            map.serialize_entry("event.outcome", &outcome)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(Categorization::new()).unwrap(),
+            json!({})
+        );
+        assert_eq!(
+            serde_json::to_value(
+                Categorization::new()
+                    .kind("event")
+                    .category("authentication")
+                    .category("iam")
+                    .event_type("start")
+                    .outcome(Outcome::Failure)
+            )
+            .unwrap(),
+            json!({
+                "event.kind": "event",
+                "event.category": ["authentication", "iam"],
+                "event.type": ["start"],
+                "event.outcome": "failure",
+            })
+        );
+    }
+
+    #[test]
+    fn test_in_scope() {
+        let fields = Categorization::new().kind("alert").in_scope(scoped_fields);
+        assert_eq!(Value::Object(fields), json!({ "event.kind": "alert" }));
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn test_insert_into_keeps_explicit_values() {
+        let categorization = Categorization::new()
+            .kind("event")
+            .category("web")
+            .outcome(Outcome::Success);
+
+        let mut event = json!({ "event.outcome": "failure", "event": { "kind": "alert" } })
+            .as_object()
+            .unwrap()
+            .clone();
+        categorization.insert_into(&mut event);
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "event.outcome": "failure",
+                "event": { "kind": "alert" },
+                "event.category": ["web"],
+            })
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Metadata of the cloud instance added to the `cloud.*` fields
+//!
+//! The metadata is queried from the instance metadata service (IMDS) of AWS, Google Cloud, and Azure,
+//! which all listen on the link-local address `169.254.169.254`.
+//! Where the metadata services are blocked, a subset of the fields is derived from the environment variables set by the platforms instead.
+
+use crate::ecs::{Cloud, CloudAccount, CloudInstance, CloudProject};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static CLOUD: OnceLock<Option<Cloud<'static>>> = OnceLock::new();
+static CLOUD_FROM_ENV: OnceLock<Option<Cloud<'static>>> = OnceLock::new();
+
+/// Address of the instance metadata services.
+const METADATA_ADDR: ([u8; 4], u16) = ([169, 254, 169, 254], 80);
+
+/// Timeout of each connection and read, so that startup is not delayed outside of a cloud.
+const TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Returns the metadata of the cloud instance, querying it on the first call.
+///
+/// Returns `None` if the process is not running on a supported cloud.
+pub(crate) fn cloud() -> Option<&'static Cloud<'static>> {
+    CLOUD.get_or_init(detect).as_ref()
+}
+
+/// Returns the metadata of the cloud derived from the environment variables, reading them on the first call.
+///
+/// Returns `None` if none of the known variables is set.
+pub(crate) fn cloud_from_env() -> Option<&'static Cloud<'static>> {
+    CLOUD_FROM_ENV
+        .get_or_init(|| from_env(|name| std::env::var(name).ok()))
+        .as_ref()
+}
+
+fn detect() -> Option<Cloud<'static>> {
+    // Skip the providers at once if nothing listens on the address
+    TcpStream::connect_timeout(&SocketAddr::from(METADATA_ADDR), TIMEOUT).ok()?;
+
+    detect_aws().or_else(detect_gcp).or_else(detect_azure)
+}
+
+fn detect_aws() -> Option<Cloud<'static>> {
+    // IMDSv2 requires a session token
+    let token = request(
+        "PUT",
+        "/latest/api/token",
+        &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+    )?;
+    let document = request(
+        "GET",
+        "/latest/dynamic/instance-identity/document",
+        &[("X-aws-ec2-metadata-token", token.trim())],
+    )?;
+    parse_aws(&document)
+}
+
+fn detect_gcp() -> Option<Cloud<'static>> {
+    let instance = request(
+        "GET",
+        "/computeMetadata/v1/instance/?recursive=true",
+        &[("Metadata-Flavor", "Google")],
+    )?;
+    parse_gcp(&instance)
+}
+
+fn detect_azure() -> Option<Cloud<'static>> {
+    let compute = request(
+        "GET",
+        "/metadata/instance/compute?api-version=2021-02-01",
+        &[("Metadata", "true")],
+    )?;
+    parse_azure(&compute)
+}
+
+/// Sends an HTTP/1.0 request to the metadata service, and returns the body if the status is 200.
+fn request(method: &str, path: &str, headers: &[(&str, &str)]) -> Option<String> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::from(METADATA_ADDR), TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: 169.254.169.254\r\n", method, path);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("Content-Length: 0\r\n\r\n");
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    parse_response(&response).map(str::to_string)
+}
+
+/// Returns the body of an HTTP response if its status is 200.
+fn parse_response(response: &str) -> Option<&str> {
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    let status = head.lines().next()?.split_whitespace().nth(1)?;
+    (status == "200").then_some(body)
+}
+
+/// Parses the [instance identity document](https://docs.aws.amazon.com/AWSEC2/latest/UserGuide/instance-identity-documents.html) of EC2.
+fn parse_aws(document: &str) -> Option<Cloud<'static>> {
+    let document: Value = serde_json::from_str(document).ok()?;
+    Some(Cloud {
+        provider: Some(Cow::Borrowed("aws")),
+        region: string(&document["region"]),
+        availability_zone: string(&document["availabilityZone"]),
+        instance: Some(CloudInstance {
+            id: string(&document["instanceId"]),
+        }),
+        account: Some(CloudAccount {
+            id: string(&document["accountId"]),
+        }),
+        project: None,
+    })
+}
+
+/// Parses the instance metadata of Compute Engine.
+fn parse_gcp(instance: &str) -> Option<Cloud<'static>> {
+    let instance: Value = serde_json::from_str(instance).ok()?;
+    // e.g. `projects/123456789/zones/us-central1-a`
+    let zone = instance["zone"].as_str()?.rsplit('/').next()?;
+    let region = zone.rsplit_once('-').map(|(region, _)| region.to_string());
+    // The id is a number which exceeds the precision of JavaScript
+    let id = match &instance["id"] {
+        Value::Number(id) => Some(Cow::Owned(id.to_string())),
+        id => string(id),
+    };
+
+    Some(Cloud {
+        provider: Some(Cow::Borrowed("gcp")),
+        region: region.map(Cow::Owned),
+        availability_zone: Some(Cow::Owned(zone.to_string())),
+        instance: Some(CloudInstance { id }),
+        account: None,
+        project: None,
+    })
+}
+
+/// Parses the compute metadata of an Azure virtual machine.
+fn parse_azure(compute: &str) -> Option<Cloud<'static>> {
+    let compute: Value = serde_json::from_str(compute).ok()?;
+    Some(Cloud {
+        provider: Some(Cow::Borrowed("azure")),
+        region: string(&compute["location"]),
+        availability_zone: string(&compute["zone"]),
+        instance: Some(CloudInstance {
+            id: string(&compute["vmId"]),
+        }),
+        account: None,
+        project: None,
+    })
+}
+
+/// Derives the metadata from the environment variables, where `var` returns the value of a variable.
+fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Cloud<'static>> {
+    let var = |name: &str| var(name).filter(|value| !value.is_empty()).map(Cow::Owned);
+    let first = |names: &[&str]| names.iter().find_map(|name| var(name));
+
+    // Lambda, ECS, and the SDKs
+    let region = first(&["AWS_REGION", "AWS_DEFAULT_REGION"]);
+    if region.is_some()
+        || var("ECS_CONTAINER_METADATA_URI_V4").is_some()
+        || var("ECS_CONTAINER_METADATA_URI").is_some()
+    {
+        return Some(Cloud {
+            provider: Some(Cow::Borrowed("aws")),
+            region,
+            ..Cloud::default()
+        });
+    }
+
+    // Cloud Run, Cloud Functions, App Engine, and the SDKs
+    if let Some(project) = first(&["GOOGLE_CLOUD_PROJECT", "GCLOUD_PROJECT", "GCP_PROJECT"]) {
+        return Some(Cloud {
+            provider: Some(Cow::Borrowed("gcp")),
+            region: var("FUNCTION_REGION"),
+            project: Some(CloudProject { id: Some(project) }),
+            ..Cloud::default()
+        });
+    }
+
+    // App Service and Functions
+    if var("WEBSITE_SITE_NAME").is_some() {
+        return Some(Cloud {
+            provider: Some(Cow::Borrowed("azure")),
+            region: var("REGION_NAME"),
+            ..Cloud::default()
+        });
+    }
+
+    None
+}
+
+/// Returns the string if `value` is a non-empty string.
+fn string(value: &Value) -> Option<Cow<'static, str>> {
+    value
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(|s| Cow::Owned(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response("HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\ntoken"),
+            Some("token")
+        );
+        assert_eq!(
+            parse_response("HTTP/1.1 401 Unauthorized\r\n\r\nunauthorized"),
+            None
+        );
+        assert_eq!(parse_response("HTTP/1.0 200 OK\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_aws() {
+        let cloud = parse_aws(
+            r#"{"accountId":"123456789012","availabilityZone":"us-east-1c","instanceId":"i-0123456789abcdef0","region":"us-east-1"}"#,
+        )
+        .unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("aws"));
+        assert_eq!(cloud.region.as_deref(), Some("us-east-1"));
+        assert_eq!(cloud.availability_zone.as_deref(), Some("us-east-1c"));
+        assert_eq!(
+            cloud.instance.unwrap().id.as_deref(),
+            Some("i-0123456789abcdef0")
+        );
+        assert_eq!(cloud.account.unwrap().id.as_deref(), Some("123456789012"));
+        assert_eq!(parse_aws("not json"), None);
+    }
+
+    #[test]
+    fn test_parse_gcp() {
+        let cloud = parse_gcp(
+            r#"{"id":4520031799277581759,"name":"my-instance","zone":"projects/123456789/zones/us-central1-a"}"#,
+        )
+        .unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("gcp"));
+        assert_eq!(cloud.region.as_deref(), Some("us-central1"));
+        assert_eq!(cloud.availability_zone.as_deref(), Some("us-central1-a"));
+        assert_eq!(
+            cloud.instance.unwrap().id.as_deref(),
+            Some("4520031799277581759")
+        );
+    }
+
+    #[test]
+    fn test_parse_azure() {
+        let cloud = parse_azure(
+            r#"{"location":"westeurope","name":"my-vm","vmId":"02aab8a4-74ef-476e-8182-f6d2ba4166a6","zone":""}"#,
+        )
+        .unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("azure"));
+        assert_eq!(cloud.region.as_deref(), Some("westeurope"));
+        assert_eq!(cloud.availability_zone, None);
+        assert_eq!(
+            cloud.instance.unwrap().id.as_deref(),
+            Some("02aab8a4-74ef-476e-8182-f6d2ba4166a6")
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        let from_env = |vars: &[(&str, &str)]| {
+            from_env(|name| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        let cloud = from_env(&[("AWS_REGION", "eu-west-1")]).unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("aws"));
+        assert_eq!(cloud.region.as_deref(), Some("eu-west-1"));
+
+        let cloud = from_env(&[(
+            "ECS_CONTAINER_METADATA_URI_V4",
+            "http://169.254.170.2/v4/abc",
+        )])
+        .unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("aws"));
+        assert_eq!(cloud.region, None);
+
+        let cloud = from_env(&[("GOOGLE_CLOUD_PROJECT", "my-project")]).unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("gcp"));
+        assert_eq!(cloud.project.unwrap().id.as_deref(), Some("my-project"));
+
+        let cloud = from_env(&[
+            ("WEBSITE_SITE_NAME", "my-app"),
+            ("REGION_NAME", "West Europe"),
+        ])
+        .unwrap();
+        assert_eq!(cloud.provider.as_deref(), Some("azure"));
+        assert_eq!(cloud.region.as_deref(), Some("West Europe"));
+
+        assert_eq!(from_env(&[("AWS_REGION", "")]), None);
+        assert_eq!(from_env(&[]), None);
+    }
+}
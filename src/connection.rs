@@ -0,0 +1,149 @@
+//! `client.*` and `server.*` fields of the connection being handled
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::net::{IpAddr, SocketAddr};
+
+/// Connection which the log records belong to.
+///
+/// It adds `client.ip`, `client.port`, `server.ip`, and `server.port` to the records.
+/// Network services can enter it when accepting a connection, so all logs of the connection carry its endpoints.
+/// The other ways to attach it are those of [`ToEcsFields`](crate::ToEcsFields).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::ConnectionContext;
+///
+/// ecs_logger::init();
+///
+/// let _connection = ConnectionContext::new()
+///     .client("192.0.2.1:51234".parse().unwrap())
+///     .server("198.51.100.7:443".parse().unwrap())
+///     .enter();
+/// log::info!("Accepted connection");
+/// // {"log.level":"INFO","message":"Accepted connection","client.ip":"192.0.2.1","client.port":51234,"server.ip":"198.51.100.7","server.port":443,...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionContext {
+    client: Option<Endpoint>,
+    server: Option<Endpoint>,
+}
+
+/// IP address and optional port of one side of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Endpoint {
+    ip: IpAddr,
+    port: Option<u16>,
+}
+
+impl ConnectionContext {
+    /// Creates a context without endpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `client.ip` and `client.port`.
+    pub fn client(mut self, addr: SocketAddr) -> Self {
+        self.client = Some(Endpoint::from(addr));
+        self
+    }
+
+    /// Sets `client.ip`, e.g. when the client is known from the `X-Forwarded-For` header.
+    pub fn client_ip(mut self, ip: IpAddr) -> Self {
+        self.client = Some(Endpoint::from(ip));
+        self
+    }
+
+    /// Sets `server.ip` and `server.port`.
+    pub fn server(mut self, addr: SocketAddr) -> Self {
+        self.server = Some(Endpoint::from(addr));
+        self
+    }
+
+    /// Sets `server.ip`.
+    pub fn server_ip(mut self, ip: IpAddr) -> Self {
+        self.server = Some(Endpoint::from(ip));
+        self
+    }
+}
+
+impl Serialize for ConnectionContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(client) = &self.client {
+            client.serialize_entries(&mut map, "client")?;
+        }
+        if let Some(server) = &self.server {
+            server.serialize_entries(&mut map, "server")?;
+        }
+        map.end()
+    }
+}
+
+impl Endpoint {
+    /// Serializes `<prefix>.ip` and `<prefix>.port` into `map`.
+    pub(crate) fn serialize_entries<M: SerializeMap>(
+        &self,
+        map: &mut M,
+        prefix: &str,
+    ) -> Result<(), M::Error> {
+        map.serialize_entry(&format!("{}.ip", prefix), &self.ip)?;
+        if let Some(port) = self.port {
+            map.serialize_entry(&format!("{}.port", prefix), &port)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<SocketAddr> for Endpoint {
+    fn from(addr: SocketAddr) -> Self {
+        Endpoint {
+            ip: addr.ip(),
+            port: Some(addr.port()),
+        }
+    }
+}
+
+impl From<IpAddr> for Endpoint {
+    fn from(ip: IpAddr) -> Self {
+        Endpoint { ip, port: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(ConnectionContext::new()).unwrap(),
+            json!({})
+        );
+        assert_eq!(
+            serde_json::to_value(
+                ConnectionContext::new()
+                    .client("[2001:db8::1]:51234".parse().unwrap())
+                    .server_ip("198.51.100.7".parse().unwrap())
+            )
+            .unwrap(),
+            json!({
+                "client.ip": "2001:db8::1",
+                "client.port": 51234,
+                "server.ip": "198.51.100.7",
+            })
+        );
+    }
+
+    #[test]
+    fn test_in_scope() {
+        let connection = ConnectionContext::new().client_ip("192.0.2.1".parse().unwrap());
+        assert_eq!(
+            Value::Object(connection.in_scope(scoped_fields)),
+            json!({ "client.ip": "192.0.2.1" })
+        );
+        assert!(scoped_fields().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Metadata of the container added to the `container.*` fields
+
+use crate::ecs::Container;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+static CONTAINER: OnceLock<Option<Container<'static>>> = OnceLock::new();
+
+/// Returns the metadata of the container in which the process runs, collecting it on the first call.
+///
+/// Returns `None` if the process does not run in a container, or the container id is unknown.
+pub(crate) fn container() -> Option<&'static Container<'static>> {
+    CONTAINER.get_or_init(collect).as_ref()
+}
+
+fn collect() -> Option<Container<'static>> {
+    let read = |path| std::fs::read_to_string(path).ok();
+
+    // Under cgroup v2 the cgroup is usually `/` in the namespace of the container, so the id is found in the mounts instead
+    read("/proc/self/cgroup")
+        .and_then(|cgroup| parse_cgroup(&cgroup))
+        .or_else(|| read("/proc/self/mountinfo").and_then(|mountinfo| parse_mountinfo(&mountinfo)))
+}
+
+/// Finds the container id in [`/proc/self/cgroup`](https://man7.org/linux/man-pages/man7/cgroups.7.html).
+///
+/// The paths look like `/docker/<id>` (cgroup v1), `/system.slice/docker-<id>.scope` (systemd),
+/// or `/kubepods.slice/.../cri-containerd-<id>.scope` (Kubernetes).
+fn parse_cgroup(cgroup: &str) -> Option<Container<'static>> {
+    cgroup.lines().find_map(|line| {
+        // hierarchy-ID:controller-list:cgroup-path
+        let path = line.splitn(3, ':').nth(2)?;
+        find_in_path(path)
+    })
+}
+
+/// Finds the container id in [`/proc/self/mountinfo`](https://man7.org/linux/man-pages/man5/proc_pid_mountinfo.5.html),
+/// e.g. in the mount of `/etc/hostname` from `/var/lib/docker/containers/<id>/hostname`.
+fn parse_mountinfo(mountinfo: &str) -> Option<Container<'static>> {
+    mountinfo.lines().find_map(|line| {
+        // The root of the mount is the fourth field
+        let root = line.split_whitespace().nth(3)?;
+        let segments: Vec<&str> = root.split('/').collect();
+        let id = segments.windows(2).find_map(|pair| {
+            let is_parent = pair[0] == "containers" || pair[0] == "overlay-containers";
+            (is_parent && is_container_id(pair[1])).then_some(pair[1])
+        })?;
+
+        Some(Container {
+            id: Some(Cow::Owned(id.to_string())),
+            runtime: root
+                .contains("/docker/containers/")
+                .then_some(Cow::Borrowed("docker")),
+        })
+    })
+}
+
+fn find_in_path(path: &str) -> Option<Container<'static>> {
+    let mut parent = None;
+    for segment in path.split('/') {
+        let name = segment.strip_suffix(".scope").unwrap_or(segment);
+        // e.g. `docker-<id>`, where the prefix names the runtime
+        let (prefix, id) = match name.rsplit_once('-') {
+            Some((prefix, id)) => (Some(prefix), id),
+            None => (parent, name),
+        };
+        if is_container_id(id) {
+            return Some(Container {
+                id: Some(Cow::Owned(id.to_string())),
+                runtime: prefix.and_then(runtime).map(Cow::Borrowed),
+            });
+        }
+        parent = Some(segment);
+    }
+    None
+}
+
+/// Returns the name of the runtime which is named `name` in the cgroup paths.
+fn runtime(name: &str) -> Option<&'static str> {
+    match name {
+        "docker" => Some("docker"),
+        "containerd" | "cri-containerd" => Some("containerd"),
+        "crio" => Some("cri-o"),
+        "libpod" => Some("podman"),
+        _ => None,
+    }
+}
+
+/// Returns whether `id` is a full container id, which is 64 hexadecimal digits.
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4d2b1c0a9e8d7c6b5a493827161504f3e2d1c0b9a8f7e6d5c4b3a291807162";
+
+    fn parsed(container: Option<Container>) -> Option<(String, Option<String>)> {
+        container.map(|container| {
+            (
+                container.id.unwrap().into_owned(),
+                container.runtime.map(Cow::into_owned),
+            )
+        })
+    }
+
+    #[test]
+    fn test_parse_cgroup() {
+        let cgroup = format!("12:pids:/docker/{ID}\n11:memory:/docker/{ID}\n");
+        assert_eq!(
+            parsed(parse_cgroup(&cgroup)),
+            Some((ID.to_string(), Some("docker".to_string())))
+        );
+
+        let cgroup = format!("0::/system.slice/docker-{ID}.scope\n");
+        assert_eq!(
+            parsed(parse_cgroup(&cgroup)),
+            Some((ID.to_string(), Some("docker".to_string())))
+        );
+
+        let cgroup = format!(
+            "0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod1a2b3c4d_5e6f.slice/cri-containerd-{ID}.scope\n"
+        );
+        assert_eq!(
+            parsed(parse_cgroup(&cgroup)),
+            Some((ID.to_string(), Some("containerd".to_string())))
+        );
+
+        let cgroup = format!("1:name=systemd:/kubepods/besteffort/pod1a2b3c4d-5e6f/{ID}\n");
+        assert_eq!(parsed(parse_cgroup(&cgroup)), Some((ID.to_string(), None)));
+
+        assert_eq!(parse_cgroup("0::/\n"), None);
+        assert_eq!(
+            parse_cgroup("0::/user.slice/user-1000.slice/session-1.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let mountinfo = format!(
+            "1120 1101 0:56 / / rw,relatime - overlay overlay rw\n\
+             1130 1120 259:1 /var/lib/docker/containers/{ID}/hostname /etc/hostname rw,relatime - ext4 /dev/nvme0n1p1 rw\n"
+        );
+        assert_eq!(
+            parsed(parse_mountinfo(&mountinfo)),
+            Some((ID.to_string(), Some("docker".to_string())))
+        );
+
+        let mountinfo = format!(
+            "1130 1120 0:33 /containers/storage/overlay-containers/{ID}/userdata/hostname /etc/hostname rw - tmpfs tmpfs rw\n"
+        );
+        assert_eq!(
+            parsed(parse_mountinfo(&mountinfo)),
+            Some((ID.to_string(), None))
+        );
+
+        assert_eq!(
+            parse_mountinfo("1120 1101 0:56 / / rw,relatime - overlay overlay rw\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Stack of context fields added to the log records of the current thread
+//!
+//! Each [`push`] adds a frame of fields, which are added to the log records emitted by the current thread
+//! until the returned [`ContextGuard`] is dropped. Inner frames shadow the fields of the same names in outer frames,
+//! and all frames shadow the fields set by [`set_extra_fields`](crate::extra_fields::set_extra_fields).
+//!
+//! ## Example
+//!
+//! ```
+//! use ecs_logger::context;
+//! use serde_json::json;
+//!
+//! ecs_logger::init();
+//!
+//! let _connection = context::push(json!({ "client.ip": "192.0.2.1" })).unwrap();
+//! {
+//!     let _session = context::push(json!({ "session.id": "s-1" })).unwrap();
+//!     log::info!("Authenticated"); // Has `client.ip` and `session.id`
+//! }
+//! log::info!("Disconnected"); // Has `client.ip` only
+//! ```
+
+use crate::extra_fields::{
+    push_scoped_fields, push_scoped_frame, to_json_map, truncate_scoped_fields, ScopedFrame,
+    SetExtraFieldsError,
+};
+use crate::fields::ToEcsFields;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// Guard which pops a context frame, and all frames pushed after it, when dropped.
+///
+/// The guard cannot be sent to another thread, because the stack belongs to the thread which pushed the frame.
+#[must_use = "the context frame is popped when the guard is dropped"]
+#[derive(Debug)]
+pub struct ContextGuard {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Pushes a frame of fields to the context stack of the current thread.
+///
+/// `fields` must be serialized into a JSON object, like [`set_extra_fields`](crate::extra_fields::set_extra_fields).
+///
+/// # Errors
+///
+/// Returns [`SetExtraFieldsError`] if `fields` cannot be converted into a JSON object.
+pub fn push(fields: impl serde::Serialize) -> Result<ContextGuard, SetExtraFieldsError> {
+    let depth = push_scoped_fields(to_json_map(fields)?);
+
+    Ok(ContextGuard {
+        depth,
+        _not_send: PhantomData,
+    })
+}
+
+/// Pushes a frame of the fields of `value` to the context stack of the current thread.
+///
+/// See [`ToEcsFields`] for how the types describe their fields.
+pub fn push_fields(value: &(impl ToEcsFields + ?Sized)) -> ContextGuard {
+    let depth = push_scoped_fields(value.to_ecs_fields());
+
+    ContextGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// Pushes a frame of fields computed by `f` to the context stack of the current thread.
+///
+/// `f` runs each time a record which passes the filters of the logger is emitted by the current thread,
+/// so the fields which are expensive to compute cost nothing for the records which are filtered out.
+/// The fields are skipped if they cannot be converted into a JSON object.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::context;
+/// use serde_json::json;
+/// use std::rc::Rc;
+///
+/// ecs_logger::init();
+///
+/// let cart = Rc::new(vec!["apple", "banana"]);
+/// let _cart = context::push_lazy({
+///     let cart = Rc::clone(&cart);
+///     move || json!({ "cart.items": *cart })
+/// });
+/// log::trace!("Cart updated"); // `cart.items` is serialized only if `TRACE` is enabled
+/// ```
+pub fn push_lazy<T: serde::Serialize>(f: impl Fn() -> T + 'static) -> ContextGuard {
+    let depth = push_scoped_frame(ScopedFrame::Lazy(Rc::new(move || to_json_map(f()).ok())));
+
+    ContextGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+impl ContextGuard {
+    /// Pops the frame. This is the same as dropping the guard.
+    pub fn pop(self) {}
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        truncate_scoped_fields(self.depth);
+    }
+}
+
+/// Runs a block with fields added to the log records emitted by the current thread, like a span.
+///
+/// The fields are given as a JSON object in the syntax of [`serde_json::json!`], and they are pushed to the context stack
+/// for the dynamic extent of the block, on top of the extra fields and the outer frames.
+/// The block is not wrapped in a closure, so `?`, `return`, and `break` work as usual, and the frame is popped however the block exits.
+/// It evaluates to the value of the block.
+///
+/// # Panics
+///
+/// Panics if the fields are not a JSON object.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+///
+/// let id = "r-42";
+/// let len = ecs_logger::with_ecs_fields!({ "request.id": id }, {
+///     log::info!("Handling the request"); // Has `request.id`
+///     12
+/// });
+/// log::info!("Handled {} bytes", len); // Does not have `request.id`
+/// ```
+#[macro_export]
+macro_rules! with_ecs_fields {
+    ($fields:tt, $body:block $(,)?) => {{
+        let _guard = $crate::context::push($crate::__private::serde_json::json!($fields))
+            .expect("the fields of with_ecs_fields! should be a JSON object");
+        $body
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::json;
+
+    #[test]
+    fn test_push_pop() {
+        let outer = push(json!({ "a": 1, "b": { "c": 2 } })).unwrap();
+        let inner = push(json!({ "a": 3, "b": { "d": 4 } })).unwrap();
+        assert_eq!(
+            serde_json::Value::Object(scoped_fields()),
+            json!({ "a": 3, "b": { "c": 2, "d": 4 } })
+        );
+
+        inner.pop();
+        assert_eq!(
+            serde_json::Value::Object(scoped_fields()),
+            json!({ "a": 1, "b": { "c": 2 } })
+        );
+
+        // Dropping an outer guard unwinds the inner frames too
+        let _inner = push(json!({ "e": 5 })).unwrap();
+        drop(outer);
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn test_push_fields() {
+        let guard = push_fields(&crate::UserContext::new("42"));
+        assert_eq!(
+            serde_json::Value::Object(scoped_fields()),
+            json!({ "user.id": "42" })
+        );
+        drop(guard);
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn test_push_lazy() {
+        use std::cell::Cell;
+
+        let calls = Rc::new(Cell::new(0));
+        let outer = push(json!({ "a": 1 })).unwrap();
+        let lazy = push_lazy({
+            let calls = Rc::clone(&calls);
+            move || {
+                calls.set(calls.get() + 1);
+                json!({ "a": 2, "b": calls.get() })
+            }
+        });
+        assert_eq!(calls.get(), 0);
+
+        assert_eq!(
+            serde_json::Value::Object(scoped_fields()),
+            json!({ "a": 2, "b": 1 })
+        );
+        assert_eq!(
+            serde_json::Value::Object(scoped_fields()),
+            json!({ "a": 2, "b": 2 })
+        );
+
+        lazy.pop();
+        drop(outer);
+        assert!(scoped_fields().is_empty());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_with_ecs_fields() {
+        fn parse(s: &str) -> Result<serde_json::Value, std::num::ParseIntError> {
+            let id = 7;
+            crate::with_ecs_fields!({ "request.id": id, "input": s }, {
+                assert_eq!(
+                    serde_json::Value::Object(scoped_fields()),
+                    json!({ "request.id": 7, "input": s })
+                );
+                let n: i32 = s.parse()?;
+                Ok(json!(n))
+            })
+        }
+
+        assert_eq!(parse("12").unwrap(), json!(12));
+        assert!(scoped_fields().is_empty());
+        assert!(parse("x").is_err());
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn test_push_not_object() {
+        assert!(matches!(push(42), Err(SetExtraFieldsError::NotObject)));
+    }
+}
@@ -0,0 +1,151 @@
+//! `data_stream.*` fields
+
+use crate::extra_fields::to_json_map;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+
+/// [Data stream](https://www.elastic.co/guide/en/ecs/current/ecs-data_stream.html) which the records are routed to,
+/// i.e. the `{type}-{dataset}-{namespace}` data stream of Elasticsearch.
+///
+/// Setting the fields in the records lets them be shipped directly to the right data stream without rewriting them in an ingest pipeline.
+/// The dataset and the namespace are lowercased, and the characters which are not allowed in the names of data streams are replaced with `_`.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{DataStream, FormatOptions};
+///
+/// let options = FormatOptions::new()
+///     .data_stream(DataStream::new().dataset("my_app").namespace("production"));
+/// // "data_stream.type":"logs","data_stream.dataset":"my_app","data_stream.namespace":"production"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataStream {
+    stream_type: String,
+    dataset: Option<String>,
+    namespace: Option<String>,
+}
+
+impl Default for DataStream {
+    fn default() -> Self {
+        DataStream {
+            stream_type: "logs".to_string(),
+            dataset: None,
+            namespace: None,
+        }
+    }
+}
+
+impl DataStream {
+    /// Creates a data stream of the `logs` type, whose dataset and namespace are left to Elasticsearch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `data_stream.type`, e.g. `logs` or `metrics`. Defaults to `logs`.
+    pub fn stream_type(mut self, stream_type: impl Into<String>) -> Self {
+        self.stream_type = stream_type.into();
+        self
+    }
+
+    /// Sets `data_stream.dataset`, e.g. `my_app` or `nginx.access`.
+    pub fn dataset(mut self, dataset: &str) -> Self {
+        self.dataset = Some(sanitize(dataset));
+        self
+    }
+
+    /// Sets `data_stream.namespace`, e.g. `production` or the name of a team.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(sanitize(namespace));
+        self
+    }
+
+    /// Inserts the fields into `event`, except for those set already, e.g. with extra fields.
+    pub(crate) fn insert_into(&self, event: &mut Map<String, Value>) {
+        let fields = to_json_map(self).expect("DataStream should be converted into a JSON object");
+        for (key, value) in fields {
+            let name = key.strip_prefix("data_stream.").unwrap_or(&key);
+            let nested = event.get("data_stream").and_then(|d| d.get(name)).is_some();
+            if !nested && !event.contains_key(&key) {
+                event.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Serialize for DataStream {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("data_stream.type", &self.stream_type)?;
+        if let Some(dataset) = &self.dataset {
+            map.serialize_entry("data_stream.dataset", dataset)?;
+        }
+        if let Some(namespace) = &self.namespace {
+            map.serialize_entry("data_stream.namespace", namespace)?;
+        }
+        map.end()
+    }
+}
+
+/// Lowercases `name` and replaces the characters which are not allowed in the names of data streams, including `-` which separates the parts.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '\\' | '/' | '*' | '?' | '"' | '<' | '>' | '|' | ' ' | ',' | '#' | ':' | '-' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        // Each part is limited to 100 bytes
+        .scan(0, |len, c| {
+            *len += c.len_utf8();
+            (*len <= 100).then_some(c)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(DataStream::new()).unwrap(),
+            json!({ "data_stream.type": "logs" })
+        );
+        assert_eq!(
+            serde_json::to_value(
+                DataStream::new()
+                    .stream_type("metrics")
+                    .dataset("My-App")
+                    .namespace("team a")
+            )
+            .unwrap(),
+            json!({
+                "data_stream.type": "metrics",
+                "data_stream.dataset": "my_app",
+                "data_stream.namespace": "team_a",
+            })
+        );
+        assert_eq!(sanitize(&"a".repeat(120)).len(), 100);
+    }
+
+    #[test]
+    fn test_insert_into() {
+        let data_stream = DataStream::new().dataset("my_app").namespace("production");
+
+        let mut event = Map::new();
+        event.insert("data_stream.namespace".to_string(), json!("staging"));
+        event.insert("data_stream".to_string(), json!({ "dataset": "nginx" }));
+        data_stream.insert_into(&mut event);
+
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "data_stream.namespace": "staging",
+                "data_stream": { "dataset": "nginx" },
+                "data_stream.type": "logs",
+            })
+        );
+    }
+}
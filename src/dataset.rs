@@ -0,0 +1,143 @@
+//! `event.dataset` field
+
+use crate::FormatOptions;
+use serde_json::{Map, Value};
+
+/// How the [`event.dataset`](https://www.elastic.co/guide/en/ecs/current/ecs-event.html#field-event-dataset) field is set.
+///
+/// Elastic data streams are routed by the dataset, so services which share an index usually need distinct datasets.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{EventDataset, FormatOptions};
+///
+/// let options = FormatOptions::new()
+///     .event_dataset(EventDataset::Fixed("my_app.log".to_string()))
+///     .event_dataset_for("my_app::access_log", "my_app.access");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum EventDataset {
+    /// `event.dataset` is not set, except for the targets configured with
+    /// [`FormatOptions::event_dataset_for`](crate::FormatOptions::event_dataset_for).
+    #[default]
+    None,
+
+    /// `service.name` if it is set, or the crate name of the target otherwise, e.g. `my_app` for `my_app::server`.
+    Auto,
+
+    /// The given value.
+    Fixed(String),
+}
+
+/// Sets `event.dataset` for a record of `target` according to `options`, unless it is set already.
+pub(crate) fn set_event_dataset(
+    event: &mut Map<String, Value>,
+    target: &str,
+    options: &FormatOptions,
+) {
+    let nested = event.get("event").and_then(|e| e.get("dataset")).is_some();
+    if nested || event.contains_key("event.dataset") {
+        return;
+    }
+
+    let dataset = match target_dataset(&options.event_dataset_targets, target) {
+        Some(dataset) => dataset,
+        None => match &options.event_dataset {
+            EventDataset::None => return,
+            EventDataset::Auto => options
+                .service
+                .as_ref()
+                .and_then(|service| service.name.as_deref())
+                .unwrap_or_else(|| target.split("::").next().unwrap_or(target)),
+            EventDataset::Fixed(dataset) => dataset,
+        },
+    };
+
+    event.insert("event.dataset".to_string(), dataset.into());
+}
+
+/// Returns the dataset of the most specific target which `target` is in
+fn target_dataset<'a>(targets: &'a [(String, String)], target: &str) -> Option<&'a str> {
+    targets
+        .iter()
+        .filter(|(prefix, _)| {
+            target
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, dataset)| dataset.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::Service;
+    use serde_json::json;
+
+    fn dataset(target: &str, options: &FormatOptions) -> Option<Value> {
+        let mut event = Map::new();
+        set_event_dataset(&mut event, target, options);
+        event.remove("event.dataset")
+    }
+
+    #[test]
+    fn test_set_event_dataset() {
+        assert_eq!(dataset("my_app::server", &FormatOptions::new()), None);
+
+        let options = FormatOptions::new().event_dataset(EventDataset::Auto);
+        assert_eq!(dataset("my_app::server", &options), Some(json!("my_app")));
+
+        let options = options.service(Service {
+            name: Some("checkout".into()),
+            ..Service::default()
+        });
+        assert_eq!(dataset("my_app::server", &options), Some(json!("checkout")));
+
+        let options = FormatOptions::new()
+            .event_dataset(EventDataset::Fixed("my_app.log".to_string()))
+            .event_dataset_for("my_app::http", "my_app.http")
+            .event_dataset_for("my_app::http::access", "my_app.access");
+        assert_eq!(
+            dataset("my_app::server", &options),
+            Some(json!("my_app.log"))
+        );
+        assert_eq!(
+            dataset("my_app::http", &options),
+            Some(json!("my_app.http"))
+        );
+        assert_eq!(
+            dataset("my_app::http::client", &options),
+            Some(json!("my_app.http"))
+        );
+        assert_eq!(
+            dataset("my_app::http::access", &options),
+            Some(json!("my_app.access"))
+        );
+        assert_eq!(
+            dataset("my_app::https", &options),
+            Some(json!("my_app.log"))
+        );
+    }
+
+    #[test]
+    fn test_set_event_dataset_keeps_explicit_value() {
+        let options = FormatOptions::new().event_dataset(EventDataset::Auto);
+
+        let mut event = json!({ "event.dataset": "custom" })
+            .as_object()
+            .unwrap()
+            .clone();
+        set_event_dataset(&mut event, "my_app", &options);
+        assert_eq!(event["event.dataset"], "custom");
+
+        let mut event = json!({ "event": { "dataset": "custom" } })
+            .as_object()
+            .unwrap()
+            .clone();
+        set_event_dataset(&mut event, "my_app", &options);
+        assert!(!event.contains_key("event.dataset"));
+    }
+}
@@ -5,52 +5,77 @@
 //! ## Example
 //!
 //! ```
-//! use ecs_logger::ecs::{Event, LogOrigin, LogOriginFile, LogOriginRust};
+//! use ecs_logger::ecs::Event;
 //!
-//! let event = Event {
-//!     timestamp: chrono::Utc::now(),
-//!     log_level: "ERROR",
-//!     message: "Error!".to_string(),
-//!     ecs_version: "1.12.1",
-//!     log_origin: LogOrigin {
-//!         file: LogOriginFile {
-//!             line: Some(144),
-//!             name: Some("server.rs"),
-//!         },
-//!         rust: LogOriginRust {
-//!             target: "myApp",
-//!             module_path: Some("my_app::server"),
-//!             file_path: Some("src/server.rs"),
-//!         },
-//!     },
-//! };
+//! let event = Event::from_parts(chrono::Utc::now(), log::Level::Error, "myApp", "Error!")
+//!     .with_file_path("src/server.rs")
+//!     .with_line(144)
+//!     .with_module_path("my_app::server");
 //!
-//! println!("{}", serde_json::to_string(&event).unwrap());
+//! let json = serde_json::to_string(&event).unwrap();
+//! println!("{}", json);
+//!
+//! // Events can be parsed back, e.g. to compare them in tests
+//! let parsed: Event = serde_json::from_str(&json).unwrap();
+//! assert_eq!(parsed, event);
 //! ```
+//!
+//! ## `no_std` support
+//!
+//! This module only depends on `core` and `alloc`, so it is available even if the default `std` feature is disabled.
+//! Firmware can build events with [`Event::new`] and serialize them with [`serde_json`]'s `alloc` API,
+//! leaving the shipping of the records to a host-side forwarder.
 
-use chrono::{DateTime, Utc};
-use serde::Serialize;
-use std::path::Path;
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 /// Represents Elastic Common Schema version.
-const ECS_VERSION: &str = "1.12.1";
+pub(crate) const ECS_VERSION: &str = "1.12.1";
+
+/// Version of the Elastic Common Schema which the events conform to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum EcsVersion {
+    /// ECS 1.12.1, which the events have always conformed to.
+    #[default]
+    V1,
+
+    /// ECS 8.11.0. The fields renamed in 8.0, e.g. `log.original` and `host.user.*`, are emitted under their new names.
+    V8,
+}
+
+impl EcsVersion {
+    /// Returns the value of the `ecs.version` field, e.g. `1.12.1`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EcsVersion::V1 => ECS_VERSION,
+            EcsVersion::V8 => "8.11.0",
+        }
+    }
+}
 
 /// Representation of an event compatible with ECS logging.
 ///
 /// The event follows [ECS Logging spec](https://github.com/elastic/ecs-logging/tree/master/spec).
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+///
+/// When deserialized, the string fields borrow from the input where possible, and unknown fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Event<'a> {
     /// Date and time when the message is logged.
     ///
     /// Mapped to `@timestamp` field.
     #[serde(rename = "@timestamp")]
+    #[cfg_attr(feature = "std", serde(serialize_with = "crate::timestamp::serialize"))]
     pub timestamp: DateTime<Utc>,
 
     /// The verbosity level of the message.
     ///
     /// Mapped to `log.level` field.
-    #[serde(rename = "log.level")]
-    pub log_level: &'static str,
+    #[serde(rename = "log.level", borrow)]
+    pub log_level: Cow<'a, str>,
 
     /// The message body.
     ///
@@ -60,36 +85,74 @@ pub struct Event<'a> {
     /// ECS version this event conforms to.
     ///
     /// Mapped to `ecs.version` field.
-    #[serde(rename = "ecs.version")]
-    pub ecs_version: &'static str,
+    #[serde(rename = "ecs.version", borrow)]
+    pub ecs_version: Cow<'a, str>,
 
     /// Information about the source code which logged the message.
     ///
     /// Mapped to `log.origin` field.
-    #[serde(rename = "log.origin")]
+    #[serde(rename = "log.origin", borrow)]
     pub log_origin: LogOrigin<'a>,
 }
 
+/// Representation of an event which is not stamped with a wall-clock time yet.
+///
+/// Devices without a reliable real-time clock may emit this instead of [`Event`].
+/// The forwarder which receives the serialized event stamps `@timestamp` later with [`stamp_timestamp`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnstampedEvent<'a> {
+    /// The verbosity level of the message.
+    ///
+    /// Mapped to `log.level` field.
+    #[serde(rename = "log.level", borrow)]
+    pub log_level: Cow<'a, str>,
+
+    /// The message body.
+    ///
+    /// Mapped to `message` field.
+    pub message: String,
+
+    /// ECS version this event conforms to.
+    ///
+    /// Mapped to `ecs.version` field.
+    #[serde(rename = "ecs.version", borrow)]
+    pub ecs_version: Cow<'a, str>,
+
+    /// Information about the source code which logged the message.
+    ///
+    /// Mapped to `log.origin` field.
+    #[serde(rename = "log.origin", borrow)]
+    pub log_origin: LogOrigin<'a>,
+
+    /// Sequence number of the event, which preserves the order of events until they are stamped.
+    ///
+    /// Mapped to `event.sequence` field.
+    #[serde(rename = "event.sequence")]
+    pub sequence: u64,
+}
+
 /// Information about the source code which logged the message.
 ///
 /// <https://www.elastic.co/guide/en/ecs/current/ecs-log.html>
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LogOrigin<'a> {
     /// Representation of the source code which logged the message.
     ///
     /// Mapped to `log.origin.file` field.
+    #[serde(borrow)]
     pub file: LogOriginFile<'a>,
 
     /// Rust-specific information about the source code which logged the message.
     ///
     /// Mapped to `log.origin.rust` field.
+    #[serde(borrow)]
     pub rust: LogOriginRust<'a>,
 }
 
 /// Representation of the source code which logged the message.
 ///
 /// <https://www.elastic.co/guide/en/ecs/current/ecs-log.html>
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LogOriginFile<'a> {
     /// The line number of the source code which logged the message.
     ///
@@ -99,57 +162,498 @@ pub struct LogOriginFile<'a> {
 
     /// The filename of the source code which logged the message.
     ///
+    /// This is the final component of [`log::Record::file`], which is always valid UTF-8, so it is never lost on unusual filesystems.
+    ///
     /// Mapped to `log.origin.file.name` field.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
 }
 
 /// Rust-specific information about the source code which logged the message.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LogOriginRust<'a> {
     /// The name of the log target.
     ///
     /// Mapped to `log.origin.rust.target` field.
-    pub target: &'a str,
+    #[serde(borrow)]
+    pub target: Cow<'a, str>,
 
     /// The module path of the source code which logged the message.
     ///
     /// Mapped to `log.origin.rust.module_path` field.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub module_path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub module_path: Option<Cow<'a, str>>,
 
     /// The file path of the source code which logged the message.
     ///
     /// Mapped to `log.origin.rust.file_path` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub file_path: Option<Cow<'a, str>>,
+}
+
+/// Information about the service which logged the message.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-service.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Service<'a> {
+    /// Name of the service, e.g. `my-app`.
+    ///
+    /// Mapped to `service.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+
+    /// Version of the service, e.g. `1.2.3`.
+    ///
+    /// Mapped to `service.version` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub version: Option<Cow<'a, str>>,
+
+    /// Environment the service is running in, e.g. `production`.
+    ///
+    /// Mapped to `service.environment` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub environment: Option<Cow<'a, str>>,
+
+    /// Instance of the service, which distinguishes the replicas of a horizontally scaled service.
+    ///
+    /// Mapped to `service.node` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub node: Option<ServiceNode<'a>>,
+}
+
+/// Instance of the service which logged the message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceNode<'a> {
+    /// Name of the instance, e.g. the name of the pod or the id of the cloud instance.
+    ///
+    /// Mapped to `service.node.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+}
+
+/// Information about the host on which the message is logged.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-host.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Host<'a> {
+    /// Name of the host, e.g. its fully qualified domain name or a name chosen by the user.
+    ///
+    /// Mapped to `host.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+
+    /// Hostname of the host, as returned by the `hostname` command.
+    ///
+    /// Mapped to `host.hostname` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub hostname: Option<Cow<'a, str>>,
+
+    /// Operating system of the host.
+    ///
+    /// Mapped to `host.os` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub os: Option<Os<'a>>,
+
+    /// CPU architecture of the host, e.g. `x86_64`.
+    ///
+    /// Mapped to `host.architecture` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub architecture: Option<Cow<'a, str>>,
+}
+
+/// Operating system of the host.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-os.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Os<'a> {
+    /// OS family, e.g. `debian`, `redhat`, or `windows`.
+    ///
+    /// Mapped to `os.family` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub family: Option<Cow<'a, str>>,
+
+    /// Operating system name, without the version, e.g. `Ubuntu`.
+    ///
+    /// Mapped to `os.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+
+    /// Operating system version as a raw string, e.g. `22.04`.
+    ///
+    /// Mapped to `os.version` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub version: Option<Cow<'a, str>>,
+}
+
+/// Information about the process which logged the message.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-process.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Process<'a> {
+    /// Process id.
+    ///
+    /// Mapped to `process.pid` field.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub file_path: Option<&'a str>,
+    pub pid: Option<u32>,
+
+    /// Process name, e.g. `my-app`.
+    ///
+    /// Mapped to `process.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+
+    /// Absolute path to the process executable.
+    ///
+    /// Mapped to `process.executable` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub executable: Option<Cow<'a, str>>,
+
+    /// Thread which logged the message.
+    ///
+    /// Mapped to `process.thread` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub thread: Option<ProcessThread<'a>>,
+}
+
+/// Thread which logged the message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessThread<'a> {
+    /// Thread id.
+    ///
+    /// Mapped to `process.thread.id` field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+
+    /// Thread name.
+    ///
+    /// Mapped to `process.thread.name` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub name: Option<Cow<'a, str>>,
+}
+
+/// Information about the cloud instance on which the message is logged.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-cloud.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cloud<'a> {
+    /// Name of the cloud provider, e.g. `aws`, `gcp`, or `azure`.
+    ///
+    /// Mapped to `cloud.provider` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub provider: Option<Cow<'a, str>>,
+
+    /// Region in which the instance is running, e.g. `us-east-1`.
+    ///
+    /// Mapped to `cloud.region` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub region: Option<Cow<'a, str>>,
+
+    /// Availability zone in which the instance is running, e.g. `us-east-1c`.
+    ///
+    /// Mapped to `cloud.availability_zone` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub availability_zone: Option<Cow<'a, str>>,
+
+    /// Instance on which the message is logged.
+    ///
+    /// Mapped to `cloud.instance` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub instance: Option<CloudInstance<'a>>,
+
+    /// Account which owns the instance.
+    ///
+    /// Mapped to `cloud.account` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub account: Option<CloudAccount<'a>>,
+
+    /// Project which the instance belongs to.
+    ///
+    /// Mapped to `cloud.project` field.
+    #[serde(skip_serializing_if = "Option::is_none", default, borrow)]
+    pub project: Option<CloudProject<'a>>,
+}
+
+/// Cloud instance on which the message is logged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudInstance<'a> {
+    /// Instance id assigned by the cloud provider.
+    ///
+    /// Mapped to `cloud.instance.id` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub id: Option<Cow<'a, str>>,
+}
+
+/// Cloud account which owns the instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudAccount<'a> {
+    /// Account id, e.g. the 12-digit id of an AWS account.
+    ///
+    /// Mapped to `cloud.account.id` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub id: Option<Cow<'a, str>>,
+}
+
+/// Cloud project which the instance belongs to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudProject<'a> {
+    /// Project id, e.g. the id of a Google Cloud project.
+    ///
+    /// Mapped to `cloud.project.id` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub id: Option<Cow<'a, str>>,
+}
+
+/// Information about the container in which the message is logged.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-container.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Container<'a> {
+    /// Unique container id.
+    ///
+    /// Mapped to `container.id` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub id: Option<Cow<'a, str>>,
+
+    /// Runtime managing the container, e.g. `docker` or `containerd`.
+    ///
+    /// Mapped to `container.runtime` field.
+    #[serde(skip_serializing_if = "Option::is_none", borrow)]
+    pub runtime: Option<Cow<'a, str>>,
 }
 
 impl<'a> Event<'a> {
     /// Creates ECS log event from a [`log::Record`].
     pub fn new(timestamp: DateTime<Utc>, record: &'a log::Record<'a>) -> Self {
-        let file_path = record.file().map(Path::new);
-
         Event {
             timestamp,
-            log_level: record.level().as_str(),
+            log_level: record.level().as_str().into(),
             message: record.args().to_string(),
-            ecs_version: ECS_VERSION,
+            ecs_version: ECS_VERSION.into(),
             log_origin: LogOrigin {
                 file: LogOriginFile {
                     line: record.line(),
-                    name: file_path
-                        .and_then(|p| p.file_name())
-                        .and_then(|os_str| os_str.to_str()),
+                    name: record.file().and_then(file_name).map(Cow::Borrowed),
                 },
                 rust: LogOriginRust {
-                    target: record.target(),
-                    module_path: record.module_path(),
-                    file_path: record.file(),
+                    target: record.target().into(),
+                    module_path: record.module_path().map(Cow::Borrowed),
+                    file_path: record.file().map(Cow::Borrowed),
                 },
             },
         }
     }
+
+    /// Creates ECS log event without a [`log::Record`]. The source location is empty until set by the `with_*` methods.
+    pub fn from_parts(
+        timestamp: DateTime<Utc>,
+        level: log::Level,
+        target: &'a str,
+        message: impl Into<String>,
+    ) -> Self {
+        Event {
+            timestamp,
+            log_level: level.as_str().into(),
+            message: message.into(),
+            ecs_version: ECS_VERSION.into(),
+            log_origin: LogOrigin {
+                file: LogOriginFile {
+                    line: None,
+                    name: None,
+                },
+                rust: LogOriginRust {
+                    target: target.into(),
+                    module_path: None,
+                    file_path: None,
+                },
+            },
+        }
+    }
+
+    /// Sets `log.origin.file.line`.
+    pub fn with_line(mut self, line: u32) -> Self {
+        self.log_origin.file.line = Some(line);
+        self
+    }
+
+    /// Sets `log.origin.rust.file_path`, and `log.origin.file.name` to its final component.
+    pub fn with_file_path(mut self, file_path: &'a str) -> Self {
+        self.log_origin.file.name = file_name(file_path).map(Cow::Borrowed);
+        self.log_origin.rust.file_path = Some(file_path.into());
+        self
+    }
+
+    /// Sets `log.origin.rust.module_path`.
+    pub fn with_module_path(mut self, module_path: &'a str) -> Self {
+        self.log_origin.rust.module_path = Some(module_path.into());
+        self
+    }
+}
+
+impl Service<'_> {
+    /// Converts the service into one which owns all of its fields.
+    pub fn into_owned(self) -> Service<'static> {
+        Service {
+            name: self.name.map(into_owned),
+            version: self.version.map(into_owned),
+            environment: self.environment.map(into_owned),
+            node: self.node.map(|node| ServiceNode {
+                name: node.name.map(into_owned),
+            }),
+        }
+    }
+}
+
+impl Host<'_> {
+    /// Converts the host into one which owns all of its fields.
+    pub fn into_owned(self) -> Host<'static> {
+        Host {
+            name: self.name.map(into_owned),
+            hostname: self.hostname.map(into_owned),
+            os: self.os.map(Os::into_owned),
+            architecture: self.architecture.map(into_owned),
+        }
+    }
+}
+
+impl Os<'_> {
+    /// Converts the operating system into one which owns all of its fields.
+    pub fn into_owned(self) -> Os<'static> {
+        Os {
+            family: self.family.map(into_owned),
+            name: self.name.map(into_owned),
+            version: self.version.map(into_owned),
+        }
+    }
+}
+
+impl Process<'_> {
+    /// Converts the process into one which owns all of its fields.
+    pub fn into_owned(self) -> Process<'static> {
+        Process {
+            pid: self.pid,
+            name: self.name.map(into_owned),
+            executable: self.executable.map(into_owned),
+            thread: self.thread.map(ProcessThread::into_owned),
+        }
+    }
+}
+
+impl ProcessThread<'_> {
+    /// Converts the thread into one which owns all of its fields.
+    pub fn into_owned(self) -> ProcessThread<'static> {
+        ProcessThread {
+            id: self.id,
+            name: self.name.map(into_owned),
+        }
+    }
+}
+
+impl Cloud<'_> {
+    /// Converts the cloud into one which owns all of its fields.
+    pub fn into_owned(self) -> Cloud<'static> {
+        Cloud {
+            provider: self.provider.map(into_owned),
+            region: self.region.map(into_owned),
+            availability_zone: self.availability_zone.map(into_owned),
+            instance: self.instance.map(|instance| CloudInstance {
+                id: instance.id.map(into_owned),
+            }),
+            account: self.account.map(|account| CloudAccount {
+                id: account.id.map(into_owned),
+            }),
+            project: self.project.map(|project| CloudProject {
+                id: project.id.map(into_owned),
+            }),
+        }
+    }
+}
+
+impl Container<'_> {
+    /// Converts the container into one which owns all of its fields.
+    pub fn into_owned(self) -> Container<'static> {
+        Container {
+            id: self.id.map(into_owned),
+            runtime: self.runtime.map(into_owned),
+        }
+    }
+}
+
+fn into_owned(s: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
+}
+
+impl<'a> UnstampedEvent<'a> {
+    /// Creates ECS log event without a timestamp from a [`log::Record`].
+    pub fn new(sequence: u64, record: &'a log::Record<'a>) -> Self {
+        let Event {
+            log_level,
+            message,
+            ecs_version,
+            log_origin,
+            ..
+        } = Event::new(DateTime::default(), record);
+
+        UnstampedEvent {
+            log_level,
+            message,
+            ecs_version,
+            log_origin,
+            sequence,
+        }
+    }
+}
+
+/// Stamps `@timestamp` on a serialized event which has no timestamp, such as [`UnstampedEvent`].
+///
+/// `@timestamp` is inserted as the first field, and any existing `@timestamp` is replaced.
+///
+/// # Errors
+///
+/// Returns [`serde_json::Error`] if `event_json` is not a JSON object.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::ecs::stamp_timestamp;
+///
+/// let stamped = stamp_timestamp(
+///     r#"{"log.level":"INFO","message":"booted","event.sequence":1}"#,
+///     chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     stamped,
+///     r#"{"@timestamp":"2023-11-14T22:13:20Z","log.level":"INFO","message":"booted","event.sequence":1}"#
+/// );
+/// ```
+pub fn stamp_timestamp(
+    event_json: &str,
+    timestamp: DateTime<Utc>,
+) -> Result<String, serde_json::Error> {
+    let mut event_json_map: Map<String, Value> = serde_json::from_str(event_json)?;
+    event_json_map.remove("@timestamp");
+
+    let mut stamped = Map::new();
+    stamped.insert(
+        "@timestamp".to_string(),
+        timestamp
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            .into(),
+    );
+    stamped.extend(event_json_map);
+
+    serde_json::to_string(&stamped)
+}
+
+/// Returns the final component of `path`, like [`std::path::Path::file_name`] but without `std`.
+pub(crate) fn file_name(path: &str) -> Option<&str> {
+    let is_separator = |c: char| c == '/' || (cfg!(windows) && c == '\\');
+
+    path.trim_end_matches(is_separator)
+        .rsplit(is_separator)
+        .next()
+        .filter(|name| !name.is_empty() && *name != "..")
 }
 
 #[cfg(test)]
@@ -177,24 +681,82 @@ mod tests {
             event,
             Event {
                 timestamp,
-                log_level: "ERROR",
+                log_level: "ERROR".into(),
                 message: "Error!".to_string(),
-                ecs_version: "1.12.1",
+                ecs_version: "1.12.1".into(),
                 log_origin: LogOrigin {
                     file: LogOriginFile {
                         line: Some(144),
-                        name: Some("server.rs")
+                        name: Some("server.rs".into())
                     },
                     rust: LogOriginRust {
-                        target: "myApp",
-                        module_path: Some("my_app::server"),
-                        file_path: Some("src/server.rs")
+                        target: "myApp".into(),
+                        module_path: Some("my_app::server".into()),
+                        file_path: Some("src/server.rs".into())
                     }
-                }
+                },
             }
         );
     }
 
+    #[test]
+    fn test_unstamped_event() {
+        let record = log::Record::builder()
+            .args(format_args!("booted"))
+            .level(log::Level::Info)
+            .target("firmware")
+            .build();
+
+        let event = UnstampedEvent::new(42, &record);
+        let event_json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            event_json,
+            r#"{"log.level":"INFO","message":"booted","ecs.version":"1.12.1","log.origin":{"file":{},"rust":{"target":"firmware"}},"event.sequence":42}"#
+        );
+
+        let timestamp = DateTime::parse_from_rfc3339("2021-11-24T17:38:21.000098765Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            stamp_timestamp(&event_json, timestamp).unwrap(),
+            r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"INFO","message":"booted","ecs.version":"1.12.1","log.origin":{"file":{},"rust":{"target":"firmware"}},"event.sequence":42}"#
+        );
+
+        assert!(stamp_timestamp("[]", timestamp).is_err());
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("src/server.rs"), Some("server.rs"));
+        assert_eq!(file_name("/abs/path/lib.rs"), Some("lib.rs"));
+        assert_eq!(file_name("main.rs"), Some("main.rs"));
+        assert_eq!(file_name("src/"), Some("src"));
+        assert_eq!(file_name("src/.."), None);
+        assert_eq!(file_name("/"), None);
+        assert_eq!(file_name(""), None);
+    }
+
+    #[test]
+    fn test_from_log_record_with_non_ascii_path() {
+        let record = log::Record::builder()
+            .args(format_args!("Error!"))
+            .level(log::Level::Error)
+            .target("myApp")
+            .file(Some("src/モジュール/ファイル\u{fffd}.rs"))
+            .build();
+
+        let event = Event::new(DateTime::default(), &record);
+
+        assert_eq!(
+            event.log_origin.file.name.as_deref(),
+            Some("ファイル\u{fffd}.rs")
+        );
+        assert_eq!(
+            event.log_origin.rust.file_path.as_deref(),
+            Some("src/モジュール/ファイル\u{fffd}.rs")
+        );
+    }
+
     #[test]
     fn test_serialize() {
         let timestamp = DateTime::parse_from_rfc3339("2021-11-24T17:38:21.000098765Z")
@@ -203,18 +765,18 @@ mod tests {
 
         let event = Event {
             timestamp,
-            log_level: "TRACE",
+            log_level: "TRACE".into(),
             message: "tracing msg".to_string(),
-            ecs_version: "1.12.1",
+            ecs_version: "1.12.1".into(),
             log_origin: LogOrigin {
                 file: LogOriginFile {
                     line: Some(1234),
-                    name: Some("file.rs"),
+                    name: Some("file.rs".into()),
                 },
                 rust: LogOriginRust {
-                    target: "myCustomTarget123",
-                    module_path: Some("my_app::path::to::your::file"),
-                    file_path: Some("src/path/to/your/file.rs"),
+                    target: "myCustomTarget123".into(),
+                    module_path: Some("my_app::path::to::your::file".into()),
+                    file_path: Some("src/path/to/your/file.rs".into()),
                 },
             },
         };
@@ -233,16 +795,16 @@ mod tests {
 
         let event = Event {
             timestamp,
-            log_level: "TRACE",
+            log_level: "TRACE".into(),
             message: "tracing msg".to_string(),
-            ecs_version: "1.12.1",
+            ecs_version: "1.12.1".into(),
             log_origin: LogOrigin {
                 file: LogOriginFile {
                     line: None,
                     name: None,
                 },
                 rust: LogOriginRust {
-                    target: "myCustomTarget123",
+                    target: "myCustomTarget123".into(),
                     module_path: None,
                     file_path: None,
                 },
@@ -254,4 +816,55 @@ mod tests {
             r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"TRACE","message":"tracing msg","ecs.version":"1.12.1","log.origin":{"file":{},"rust":{"target":"myCustomTarget123"}}}"#
         );
     }
+
+    #[test]
+    fn test_from_parts() {
+        let record = log::Record::builder()
+            .args(format_args!("Error!"))
+            .level(log::Level::Error)
+            .target("myApp")
+            .file(Some("src/server.rs"))
+            .line(Some(144))
+            .module_path(Some("my_app::server"))
+            .build();
+
+        assert_eq!(
+            Event::from_parts(DateTime::default(), log::Level::Error, "myApp", "Error!")
+                .with_file_path("src/server.rs")
+                .with_line(144)
+                .with_module_path("my_app::server"),
+            Event::new(DateTime::default(), &record)
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let timestamp = DateTime::parse_from_rfc3339("2021-11-24T17:38:21.000098765Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let event = Event::from_parts(timestamp, log::Level::Warn, "myApp", "tab\tsep")
+            .with_file_path("src/path.rs")
+            .with_line(7);
+
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+        let json = json.replacen('{', r#"{"unknown":1,"#, 1);
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+
+        let event = event.with_file_path("src\\windows.rs");
+        let escaped = serde_json::to_string(&event).unwrap();
+        assert!(escaped.contains(r#""file_path":"src\\windows.rs""#));
+        assert_eq!(serde_json::from_str::<Event>(&escaped).unwrap(), event);
+
+        let record = log::Record::builder()
+            .args(format_args!("booted"))
+            .target("firmware")
+            .build();
+        let unstamped = UnstampedEvent::new(3, &record);
+        assert_eq!(
+            serde_json::from_str::<UnstampedEvent>(&serde_json::to_string(&unstamped).unwrap())
+                .unwrap(),
+            unstamped
+        );
+    }
 }
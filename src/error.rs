@@ -0,0 +1,569 @@
+//! `error.*` fields built from [`std::error::Error`]
+
+use crate::extra_fields::{scoped_fields, to_json_map, with_scoped_fields};
+use log::Level;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::fmt;
+use std::panic::Location;
+
+/// Extension trait which decorates errors with ECS [`error.*`](https://www.elastic.co/guide/en/ecs/current/ecs-error.html) fields.
+///
+/// If the error has a [`source`](Error::source), the chain of the errors is written to `error.stack_trace`,
+/// one `Caused by:` line per source.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::ErrorExt;
+///
+/// ecs_logger::init();
+///
+/// let err = "abc".parse::<i32>().unwrap_err();
+/// err.ecs()
+///     .with_code("INVALID_NUMBER")
+///     .with_type_name()
+///     .in_scope(|| log::error!("Failed to parse the port"));
+/// // {"log.level":"ERROR","message":"Failed to parse the port","error.message":"invalid digit found in string",
+/// //  "error.code":"INVALID_NUMBER","error.type":"core::num::error::ParseIntError",...}
+/// ```
+pub trait ErrorExt: Error {
+    /// Starts building the `error.*` fields of the error. `error.message` is set to the error's [`Display`](fmt::Display) output.
+    fn ecs(&self) -> EcsError<'_>;
+}
+
+impl<E: Error> ErrorExt for E {
+    fn ecs(&self) -> EcsError<'_> {
+        EcsError {
+            error: self,
+            type_name: Some(std::any::type_name::<E>()),
+            error_type: None,
+            code: None,
+            id: None,
+            backtrace: None,
+        }
+    }
+}
+
+/// Builder of the `error.*` fields of an error, created by [`ErrorExt::ecs`].
+///
+/// It serializes into the `error.*` fields like the field types described in [`ToEcsFields`](crate::ToEcsFields).
+#[derive(Debug, Clone)]
+pub struct EcsError<'a> {
+    error: &'a (dyn Error + 'a),
+    type_name: Option<&'static str>,
+    error_type: Option<String>,
+    code: Option<String>,
+    id: Option<String>,
+    backtrace: Option<String>,
+}
+
+impl<'a> EcsError<'a> {
+    /// Starts building the `error.*` fields of an error trait object. `error.message` is set to the error's [`Display`](fmt::Display) output.
+    ///
+    /// Prefer [`ErrorExt::ecs`] for concrete error types, which also knows the type name of the error.
+    pub fn new(error: &'a (dyn Error + 'a)) -> Self {
+        EcsError {
+            error,
+            type_name: None,
+            error_type: None,
+            code: None,
+            id: None,
+            backtrace: None,
+        }
+    }
+
+    /// Sets `error.code`.
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Sets `error.id`.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets `error.id` to a random version 4 UUID, e.g. to correlate the logs of an incident with the response shown to the user.
+    ///
+    /// The id can be read back with [`id`](Self::id).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::ErrorExt;
+    ///
+    /// let err = "abc".parse::<i32>().unwrap_err();
+    /// let error = err.ecs().with_code("INVALID_NUMBER").with_generated_id();
+    /// let id = error.id().unwrap().to_string();
+    /// error.log(log::Level::Error, "Failed to parse the port");
+    /// println!("Something went wrong. Please contact support with the incident id {}.", id);
+    /// ```
+    pub fn with_generated_id(self) -> Self {
+        self.with_id(generate_id())
+    }
+
+    /// Returns `error.id`, if set.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Returns `error.code`, if set.
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    /// Sets `error.type` to the Rust type name of the error, e.g. `std::io::error::Error`.
+    ///
+    /// The type name is not guaranteed to be stable across compiler versions.
+    /// This does nothing if the builder is created from a trait object with [`new`](Self::new).
+    pub fn with_type_name(mut self) -> Self {
+        if let Some(type_name) = self.type_name {
+            self.error_type = Some(type_name.to_string());
+        }
+        self
+    }
+
+    /// Sets `error.type`.
+    pub fn with_type(mut self, error_type: impl Into<String>) -> Self {
+        self.error_type = Some(error_type.into());
+        self
+    }
+
+    /// Appends `backtrace` to `error.stack_trace`, after the cause chain if any, unless it was not captured.
+    pub fn with_backtrace(mut self, backtrace: &Backtrace) -> Self {
+        if backtrace.status() == BacktraceStatus::Captured {
+            self.backtrace = Some(backtrace.to_string());
+        }
+        self
+    }
+
+    /// Returns the error.
+    pub fn error(&self) -> &'a (dyn Error + 'a) {
+        self.error
+    }
+
+    /// Runs `f` with the `error.*` fields added to the log records emitted by the current thread.
+    pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let fields = to_json_map(self).expect("EcsError should be converted into a JSON object");
+        with_scoped_fields(fields, f)
+    }
+
+    /// Logs `message` at `level` with the `error.*` fields, including the code and the id set with the builder.
+    ///
+    /// Like [`log_error`], `log.origin.file` points to the caller and the target is `ecs_logger`.
+    #[track_caller]
+    pub fn log(self, level: Level, message: &str) {
+        log_ecs_error(level, message, self, Location::caller());
+    }
+
+    /// Returns the messages of the error and its sources joined with `Caused by:` lines, or `None` if the error has no source.
+    fn cause_chain(&self) -> Option<String> {
+        let mut source = self.error.source()?;
+        let mut chain = self.error.to_string();
+        loop {
+            chain.push_str("\nCaused by: ");
+            chain.push_str(&source.to_string());
+            match source.source() {
+                Some(next) => source = next,
+                None => return Some(chain),
+            }
+        }
+    }
+}
+
+/// Logs `error` at the `ERROR` level with `message` and the `error.*` fields.
+///
+/// This is useful for errors which are not in a [`Result`], e.g. boxed errors received from a channel.
+/// Like [`ResultExt`], `log.origin.file` points to the caller and the target is `ecs_logger`.
+///
+/// # Example
+///
+/// ```
+/// use std::error::Error;
+///
+/// ecs_logger::init();
+///
+/// let err: Box<dyn Error> = "abc".parse::<i32>().unwrap_err().into();
+/// ecs_logger::log_error(err.as_ref(), "Failed to parse the port");
+/// // {"log.level":"ERROR","message":"Failed to parse the port","error.message":"invalid digit found in string",...}
+/// ```
+#[track_caller]
+pub fn log_error(error: &dyn Error, message: &str) {
+    log_ecs_error(
+        Level::Error,
+        message,
+        EcsError::new(error),
+        Location::caller(),
+    );
+}
+
+/// Extension trait which logs the error of a [`Result`] with the `error.*` fields, and returns the result unchanged.
+///
+/// The message of the event is `context`, and the error is described by `error.message`.
+/// `log.origin.file` points to the caller. Because the caller's module is not known, the target is `ecs_logger`.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::ResultExt;
+///
+/// ecs_logger::init();
+///
+/// let config = std::fs::read_to_string("/nonexistent/config.toml").log_err("loading config");
+/// // {"log.level":"ERROR","message":"loading config","error.message":"No such file or directory (os error 2)",...}
+/// assert!(config.is_err());
+/// ```
+pub trait ResultExt {
+    /// Logs the error at the `ERROR` level if the result is [`Err`].
+    fn log_err(self, context: &str) -> Self;
+
+    /// Logs the error at the `WARN` level if the result is [`Err`].
+    fn log_warn(self, context: &str) -> Self;
+}
+
+impl<T, E: Error> ResultExt for Result<T, E> {
+    #[track_caller]
+    fn log_err(self, context: &str) -> Self {
+        if let Err(err) = &self {
+            log_ecs_error(Level::Error, context, err.ecs(), Location::caller());
+        }
+        self
+    }
+
+    #[track_caller]
+    fn log_warn(self, context: &str) -> Self {
+        if let Err(err) = &self {
+            log_ecs_error(Level::Warn, context, err.ecs(), Location::caller());
+        }
+        self
+    }
+}
+
+/// Runs `f` with a backtrace of the current thread added to `error.stack_trace`, after the cause chain if any.
+pub(crate) fn with_stack_trace<R>(f: impl FnOnce() -> R) -> R {
+    let backtrace = Backtrace::force_capture();
+    let stack_trace = match scoped_fields().get("error.stack_trace") {
+        Some(Value::String(chain)) => format!("{}\n\n{}", chain, backtrace),
+        _ => backtrace.to_string(),
+    };
+
+    let mut fields = Map::new();
+    fields.insert("error.stack_trace".to_string(), stack_trace.into());
+    with_scoped_fields(fields, f)
+}
+
+/// Errors accepted by [`ecs_log!`](crate::ecs_log!), i.e. the types which implement [`Error`] and the `dyn Error` trait objects,
+/// including those in a [`Box`]. See also [`AsEcsErrorReport`].
+pub trait AsEcsError {
+    /// Returns the `error.*` fields of the error, with `error.type` if the type is known statically.
+    fn as_ecs_error(&self) -> EcsError<'_>;
+}
+
+impl<E: Error> AsEcsError for E {
+    fn as_ecs_error(&self) -> EcsError<'_> {
+        self.ecs().with_type_name()
+    }
+}
+
+macro_rules! impl_as_ecs_error {
+    ($($ty:ty),+) => {$(
+        impl AsEcsError for $ty {
+            fn as_ecs_error(&self) -> EcsError<'_> {
+                EcsError::new(self)
+            }
+        }
+    )+};
+}
+
+impl_as_ecs_error!(
+    dyn Error + '_,
+    dyn Error + Send + '_,
+    dyn Error + Send + Sync + '_
+);
+
+/// Error reports which are accepted by [`ecs_log!`](crate::ecs_log!) but do not implement [`Error`], e.g. `anyhow::Error`.
+///
+/// It is separate from [`AsEcsError`], whose blanket implementation would conflict with theirs.
+pub trait AsEcsErrorReport {
+    /// Returns the `error.*` fields of the report.
+    fn as_ecs_error(&self) -> EcsError<'_>;
+}
+
+#[cfg(feature = "anyhow")]
+impl AsEcsErrorReport for anyhow::Error {
+    fn as_ecs_error(&self) -> EcsError<'_> {
+        self.into()
+    }
+}
+
+/// Builds the `error.*` fields of an [`anyhow::Error`], with its chain and its backtrace if it was captured.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Context;
+/// use ecs_logger::EcsError;
+///
+/// ecs_logger::init();
+///
+/// let result = std::fs::read_to_string("/nonexistent/config.toml").context("failed to load config");
+/// if let Err(err) = result {
+///     EcsError::from(&err).with_code("CONFIG").log(log::Level::Error, "Startup failed");
+///     // {"log.level":"ERROR","message":"Startup failed","error.message":"failed to load config","error.code":"CONFIG",
+///     //  "error.stack_trace":"failed to load config\nCaused by: No such file or directory (os error 2)",...}
+/// }
+/// ```
+#[cfg(feature = "anyhow")]
+impl<'a> From<&'a anyhow::Error> for EcsError<'a> {
+    fn from(error: &'a anyhow::Error) -> Self {
+        EcsError::new(error.as_ref()).with_backtrace(error.backtrace())
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl AsEcsErrorReport for eyre::Report {
+    fn as_ecs_error(&self) -> EcsError<'_> {
+        self.into()
+    }
+}
+
+/// Builds the `error.*` fields of an [`eyre::Report`], with its chain.
+///
+/// Only the chain of the errors is used, so the fields are the same whichever [`EyreHandler`](eyre::EyreHandler)
+/// is installed, e.g. the one of `color-eyre`, and contain no ANSI escape sequences.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::EcsError;
+/// use eyre::WrapErr;
+///
+/// ecs_logger::init();
+///
+/// let result = std::fs::read_to_string("/nonexistent/config.toml").wrap_err("failed to load config");
+/// if let Err(report) = result {
+///     EcsError::from(&report).log(log::Level::Error, "Startup failed");
+///     // {"log.level":"ERROR","message":"Startup failed","error.message":"failed to load config",
+///     //  "error.stack_trace":"failed to load config\nCaused by: No such file or directory (os error 2)",...}
+/// }
+/// ```
+#[cfg(feature = "eyre")]
+impl<'a> From<&'a eyre::Report> for EcsError<'a> {
+    fn from(report: &'a eyre::Report) -> Self {
+        EcsError::new(report.as_ref())
+    }
+}
+
+/// Runs `f` with the `error.*` fields of `error` added to the log records emitted by the current thread.
+///
+/// Unless `error` has a backtrace already, the backtrace of the current thread is added to `error.stack_trace`
+/// after the cause chain if backtraces are enabled with `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+pub fn with_error<R>(mut error: EcsError, f: impl FnOnce() -> R) -> R {
+    if error.backtrace.is_none() {
+        error = error.with_backtrace(&Backtrace::capture());
+    }
+    error.in_scope(f)
+}
+
+/// Returns 128 random bits for the ids. They are not cryptographically secure.
+pub(crate) fn random_bits() -> u128 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // Each `RandomState` is seeded randomly, and the counter makes the ids differ even if the seeds are reused
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let random = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        hasher.write_u64(salt);
+        hasher.finish()
+    };
+    (u128::from(random(0)) << 64) | u128::from(random(1))
+}
+
+/// Returns a random version 4 UUID in the hyphenated form.
+fn generate_id() -> String {
+    let bits = random_bits();
+    // Version 4 and variant 1
+    let bits = (bits & !(0xf << 76) & !(0b11 << 62)) | (0x4 << 76) | (0b10 << 62);
+
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn log_ecs_error(level: Level, context: &str, error: EcsError, location: &Location) {
+    error.in_scope(|| crate::log_at(level, format_args!("{}", context), location));
+}
+
+impl Serialize for EcsError<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("error.message", &self.error.to_string())?;
+        if let Some(code) = &self.code {
+            map.serialize_entry("error.code", code)?;
+        }
+        if let Some(id) = &self.id {
+            map.serialize_entry("error.id", id)?;
+        }
+        if let Some(error_type) = &self.error_type {
+            map.serialize_entry("error.type", error_type)?;
+        }
+        let stack_trace = match (self.cause_chain(), &self.backtrace) {
+            (Some(chain), Some(backtrace)) => Some(format!("{}\n\n{}", chain, backtrace)),
+            (Some(chain), None) => Some(chain),
+            (None, backtrace) => backtrace.clone(),
+        };
+        if let Some(stack_trace) = stack_trace {
+            map.serialize_entry("error.stack_trace", &stack_trace)?;
+        }
+        map.end()
+    }
+}
+
+impl fmt::Display for EcsError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.error, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ecs_error() {
+        let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "query timed out");
+
+        assert_eq!(
+            serde_json::to_value(err.ecs()).unwrap(),
+            json!({ "error.message": "query timed out" })
+        );
+        assert_eq!(
+            serde_json::to_value(
+                err.ecs()
+                    .with_code("DB_TIMEOUT")
+                    .with_id("e-1")
+                    .with_type_name()
+            )
+            .unwrap(),
+            json!({
+                "error.message": "query timed out",
+                "error.code": "DB_TIMEOUT",
+                "error.id": "e-1",
+                "error.type": "std::io::error::Error",
+            })
+        );
+        assert_eq!(err.ecs().to_string(), "query timed out");
+    }
+
+    #[test]
+    fn test_generated_id() {
+        let err = std::fmt::Error;
+        let error = err.ecs().with_code("FMT").with_generated_id();
+        assert_eq!(error.code(), Some("FMT"));
+
+        let id = error.id().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+        assert_eq!(
+            id.split('-').map(str::len).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert_ne!(err.ecs().with_generated_id().id(), Some(id));
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_anyhow() {
+        use anyhow::Context;
+
+        let err = Err::<(), _>(std::fmt::Error)
+            .context("failed to render")
+            .unwrap_err();
+        let fields = to_json_map(EcsError::from(&err).with_code("RENDER")).unwrap();
+        assert_eq!(fields["error.message"], "failed to render");
+        assert_eq!(fields["error.code"], "RENDER");
+        assert!(fields["error.stack_trace"].as_str().unwrap().starts_with(
+            "failed to render\nCaused by: an error occurred when formatting an argument"
+        ));
+    }
+
+    #[cfg(feature = "eyre")]
+    #[test]
+    fn test_eyre() {
+        use eyre::WrapErr;
+
+        let report = Err::<(), _>(std::fmt::Error)
+            .wrap_err("failed to render")
+            .unwrap_err();
+        assert_eq!(
+            serde_json::to_value(EcsError::from(&report)).unwrap(),
+            json!({
+                "error.message": "failed to render",
+                "error.stack_trace": "failed to render\nCaused by: an error occurred when formatting an argument",
+            })
+        );
+    }
+
+    #[test]
+    fn test_cause_chain() {
+        #[derive(Debug)]
+        struct Outer(std::io::Error);
+
+        impl fmt::Display for Outer {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("failed to load config")
+            }
+        }
+
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let err = Outer(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "config.toml not found",
+        ));
+        let dyn_err: &dyn Error = &err;
+
+        assert_eq!(
+            serde_json::to_value(EcsError::new(dyn_err).with_type_name()).unwrap(),
+            json!({
+                "error.message": "failed to load config",
+                "error.stack_trace": "failed to load config\nCaused by: config.toml not found",
+            })
+        );
+    }
+
+    #[test]
+    fn test_in_scope() {
+        let err = std::fmt::Error;
+        let fields = err
+            .ecs()
+            .with_type("Fmt")
+            .in_scope(crate::extra_fields::scoped_fields);
+
+        assert_eq!(
+            serde_json::Value::Object(fields),
+            json!({ "error.message": "an error occurred when formatting an argument", "error.type": "Fmt" })
+        );
+    }
+}
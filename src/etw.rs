@@ -0,0 +1,349 @@
+//! Sink which emits events to Event Tracing for Windows (ETW)
+//!
+//! This module is available when the `etw` feature is enabled. `EtwProvider` is only available on Windows.
+//!
+//! Each event is written as a [TraceLogging](https://learn.microsoft.com/en-us/windows/win32/tracelogging/trace-logging-portal) event named `Log`,
+//! whose properties are the ECS fields with dotted names (e.g. `log.origin.file.line`).
+//! The ETW level is derived from `log.level`.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use ecs_logger::etw::EtwProvider;
+//!
+//! // GUID of the provider name, as computed by `EventSource.GetGuid` or `tracelogging::Guid::from_name`
+//! let provider = EtwProvider::new("MyCompany.MyService", 0x3970f9cf_2c0c_4f11_b1cc_e3a1e9958833).unwrap();
+//!
+//! ecs_logger::Builder::from_env()
+//!     .subscribe(move |event| provider.write_json(event))
+//!     .init();
+//! ```
+
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::KeyStyle;
+use serde_json::Value;
+
+/// Name of the TraceLogging events.
+const EVENT_NAME: &str = "Log";
+
+/// Channel which marks an event as TraceLogging
+const TRACELOGGING_CHANNEL: u8 = 11;
+
+// TraceLogging field types
+const IN_ANSISTRING: u8 = 2;
+const IN_INT64: u8 = 9;
+const IN_UINT64: u8 = 10;
+const IN_DOUBLE: u8 = 12;
+const IN_BOOL32: u8 = 13;
+const OUT_UTF8: u8 = 35;
+/// Flag of the in type which tells that an out type follows
+const CHAIN: u8 = 0x80;
+
+/// Event metadata and payload in the TraceLogging layout.
+#[derive(Debug, PartialEq)]
+struct EncodedEvent {
+    level: u8,
+    metadata: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+/// Returns the provider traits blob for `name`.
+fn encode_provider_traits(name: &str) -> Vec<u8> {
+    let mut traits = vec![0, 0];
+    push_str(&mut traits, name);
+    set_size_prefix(&mut traits).expect("provider name should be shorter than 64 KiB");
+    traits
+}
+
+/// Encodes an ECS event serialized as JSON. Returns `None` if `event_json` is not a JSON object or is too large.
+fn encode_event(event_json: &str) -> Option<EncodedEvent> {
+    let Ok(Value::Object(map)) = serde_json::from_str::<Value>(event_json) else {
+        return None;
+    };
+
+    let level = match map.get("log.level").and_then(Value::as_str) {
+        Some("ERROR") => 2,
+        Some("WARN") => 3,
+        Some("INFO") => 4,
+        _ => 5,
+    };
+
+    let mut metadata = vec![0, 0, 0]; // size and tags
+    let mut payload = Vec::new();
+    push_str(&mut metadata, EVENT_NAME);
+
+    for (key, value) in KeyStyle::Flat.apply(map) {
+        let in_type = match &value {
+            Value::Null => continue,
+            Value::Bool(b) => {
+                payload.extend_from_slice(&u32::from(*b).to_le_bytes());
+                IN_BOOL32
+            }
+            Value::Number(n) => {
+                if let Some(n) = n.as_i64() {
+                    payload.extend_from_slice(&n.to_le_bytes());
+                    IN_INT64
+                } else if let Some(n) = n.as_u64() {
+                    payload.extend_from_slice(&n.to_le_bytes());
+                    IN_UINT64
+                } else {
+                    let n = n.as_f64().unwrap_or(f64::NAN);
+                    payload.extend_from_slice(&n.to_le_bytes());
+                    IN_DOUBLE
+                }
+            }
+            Value::String(s) => {
+                push_str(&mut payload, s);
+                IN_ANSISTRING | CHAIN
+            }
+            Value::Array(_) | Value::Object(_) => {
+                push_str(&mut payload, &value.to_string());
+                IN_ANSISTRING | CHAIN
+            }
+        };
+
+        push_str(&mut metadata, &key);
+        metadata.push(in_type);
+        if in_type & CHAIN != 0 {
+            metadata.push(OUT_UTF8);
+        }
+    }
+
+    set_size_prefix(&mut metadata)?;
+
+    Some(EncodedEvent {
+        level,
+        metadata,
+        payload,
+    })
+}
+
+/// Appends `s` as a nul-terminated string, truncated at the first nul character
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    let s = s.split('\0').next().unwrap_or_default();
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
+
+/// Writes the length of `buf` to its first two bytes
+fn set_size_prefix(buf: &mut [u8]) -> Option<()> {
+    let size = u16::try_from(buf.len()).ok()?;
+    buf[..2].copy_from_slice(&size.to_le_bytes());
+    Some(())
+}
+
+#[cfg(windows)]
+pub use provider::EtwProvider;
+
+#[cfg(windows)]
+mod provider {
+    use super::{encode_event, encode_provider_traits, TRACELOGGING_CHANNEL};
+    use std::ffi::c_void;
+    use std::io;
+
+    #[repr(C)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct EventDescriptor {
+        id: u16,
+        version: u8,
+        channel: u8,
+        level: u8,
+        opcode: u8,
+        task: u16,
+        keyword: u64,
+    }
+
+    #[repr(C)]
+    struct EventDataDescriptor {
+        ptr: u64,
+        size: u32,
+        kind: u8,
+        reserved1: u8,
+        reserved2: u16,
+    }
+
+    const EVENT_DATA_DESCRIPTOR_TYPE_NONE: u8 = 0;
+    const EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA: u8 = 1;
+    const EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA: u8 = 2;
+    const EVENT_PROVIDER_SET_TRAITS: i32 = 2;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn EventRegister(
+            provider_id: *const Guid,
+            enable_callback: *const c_void,
+            callback_context: *const c_void,
+            reg_handle: *mut u64,
+        ) -> u32;
+        fn EventUnregister(reg_handle: u64) -> u32;
+        fn EventSetInformation(
+            reg_handle: u64,
+            information_class: i32,
+            event_information: *const c_void,
+            information_length: u32,
+        ) -> u32;
+        fn EventProviderEnabled(reg_handle: u64, level: u8, keyword: u64) -> u8;
+        fn EventWriteTransfer(
+            reg_handle: u64,
+            event_descriptor: *const EventDescriptor,
+            activity_id: *const Guid,
+            related_activity_id: *const Guid,
+            user_data_count: u32,
+            user_data: *const EventDataDescriptor,
+        ) -> u32;
+    }
+
+    /// Registered ETW provider which ECS events are written to.
+    #[derive(Debug)]
+    pub struct EtwProvider {
+        handle: u64,
+        traits: Vec<u8>,
+    }
+
+    impl EtwProvider {
+        /// Registers a TraceLogging provider named `name` with the provider GUID `guid`.
+        ///
+        /// # Errors
+        ///
+        /// Returns the error reported by `EventRegister`.
+        pub fn new(name: &str, guid: u128) -> io::Result<Self> {
+            let guid = Guid {
+                data1: (guid >> 96) as u32,
+                data2: (guid >> 80) as u16,
+                data3: (guid >> 64) as u16,
+                data4: (guid as u64).to_be_bytes(),
+            };
+            let traits = encode_provider_traits(name);
+
+            let mut handle = 0;
+            // SAFETY: `guid` and `handle` are valid for the duration of the call.
+            let status =
+                unsafe { EventRegister(&guid, std::ptr::null(), std::ptr::null(), &mut handle) };
+            if status != 0 {
+                return Err(io::Error::from_raw_os_error(status as i32));
+            }
+
+            // SAFETY: `traits` is valid for the duration of the call. Failing to set the traits is not fatal.
+            unsafe {
+                EventSetInformation(
+                    handle,
+                    EVENT_PROVIDER_SET_TRAITS,
+                    traits.as_ptr().cast(),
+                    traits.len() as u32,
+                );
+            }
+
+            Ok(EtwProvider { handle, traits })
+        }
+
+        /// Writes an ECS event serialized as JSON, as passed to [`Builder::subscribe`](crate::Builder::subscribe).
+        ///
+        /// Events which are not JSON objects, or too large for ETW, are dropped.
+        pub fn write_json(&self, event_json: &str) {
+            // SAFETY: `handle` is registered until `self` is dropped.
+            if unsafe { EventProviderEnabled(self.handle, 5, 0) } == 0 {
+                return;
+            }
+            let Some(event) = encode_event(event_json) else {
+                return;
+            };
+
+            let descriptor = EventDescriptor {
+                id: 0,
+                version: 0,
+                channel: TRACELOGGING_CHANNEL,
+                level: event.level,
+                opcode: 0,
+                task: 0,
+                keyword: 0,
+            };
+            let data = [
+                data_descriptor(&self.traits, EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA),
+                data_descriptor(&event.metadata, EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA),
+                data_descriptor(&event.payload, EVENT_DATA_DESCRIPTOR_TYPE_NONE),
+            ];
+
+            // SAFETY: the descriptors point to buffers which are valid for the duration of the call.
+            unsafe {
+                EventWriteTransfer(
+                    self.handle,
+                    &descriptor,
+                    std::ptr::null(),
+                    std::ptr::null(),
+                    data.len() as u32,
+                    data.as_ptr(),
+                );
+            }
+        }
+    }
+
+    impl Drop for EtwProvider {
+        fn drop(&mut self) {
+            // SAFETY: `handle` was registered by `EventRegister` and is unregistered only once.
+            unsafe {
+                EventUnregister(self.handle);
+            }
+        }
+    }
+
+    fn data_descriptor(data: &[u8], kind: u8) -> EventDataDescriptor {
+        EventDataDescriptor {
+            ptr: data.as_ptr() as u64,
+            size: data.len() as u32,
+            kind,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_provider_traits() {
+        assert_eq!(encode_provider_traits("My.App"), b"\x09\x00My.App\0");
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let event = encode_event(
+            r#"{"log.level":"WARN","message":"hi","log.origin":{"file":{"line":12}},"ok":true,"n":null,"x":-1.5,"tags":["a"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(event.level, 3);
+
+        let mut metadata = b"\0\0\0Log\0".to_vec();
+        metadata.extend_from_slice(b"log.level\0\x82\x23");
+        metadata.extend_from_slice(b"message\0\x82\x23");
+        metadata.extend_from_slice(b"log.origin.file.line\0\x09");
+        metadata.extend_from_slice(b"ok\0\x0d");
+        metadata.extend_from_slice(b"x\0\x0c");
+        metadata.extend_from_slice(b"tags\0\x82\x23");
+        set_size_prefix(&mut metadata).unwrap();
+        assert_eq!(event.metadata, metadata);
+
+        let mut payload = b"WARN\0hi\0".to_vec();
+        payload.extend_from_slice(&12i64.to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&(-1.5f64).to_le_bytes());
+        payload.extend_from_slice(b"[\"a\"]\0");
+        assert_eq!(event.payload, payload);
+    }
+
+    #[test]
+    fn test_encode_event_invalid() {
+        assert_eq!(encode_event("[1]"), None);
+        assert_eq!(encode_event("not json"), None);
+    }
+}
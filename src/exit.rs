@@ -0,0 +1,55 @@
+//! Best-effort flush of the global logger when the process exits or panics
+
+use std::cell::Cell;
+use std::ffi::c_int;
+use std::panic;
+use std::sync::Once;
+
+thread_local! {
+    /// Whether the current thread is flushing from an exit handler, where blocking may deadlock.
+    static EXITING: Cell<bool> = const { Cell::new(false) };
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
+
+/// Registers the handlers which flush the global logger. Calling this more than once has no effect.
+///
+/// - An `atexit` handler flushes on [`std::process::exit`] and when `main` returns.
+/// - A panic hook flushes after the previous hook runs, which also covers `panic = "abort"` builds.
+///
+/// Fatal signals are not handled, because flushing is not async-signal-safe.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        // SAFETY: `flush_at_exit` is a valid `extern "C"` function which never unwinds.
+        unsafe {
+            atexit(flush_at_exit);
+        }
+
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            flush();
+        }));
+    });
+}
+
+/// Returns `true` if the current thread is flushing from an exit handler.
+pub(crate) fn is_exiting() -> bool {
+    EXITING.with(Cell::get)
+}
+
+extern "C" fn flush_at_exit() {
+    // Unwinding out of an `extern "C"` function aborts, so the flush must not panic.
+    let _ = panic::catch_unwind(flush);
+}
+
+/// Flushes the global logger, giving up instead of blocking where it may deadlock.
+pub(crate) fn flush() {
+    EXITING.with(|e| e.set(true));
+    log::logger().flush();
+    EXITING.with(|e| e.set(false));
+}
@@ -24,16 +24,50 @@
 //!
 //! extra_fields::clear_extra_fields();
 //! ```
+//!
+//! ## Lazy fields
+//!
+//! Fields which are expensive to compute can be registered with [`add_lazy_extra_fields`], or pushed to the context stack with
+//! [`context::push_lazy`](crate::context::push_lazy). They are computed only for the records which pass the filters of the logger.
+//!
+//! ## `log-mdc` compatibility
+//!
+//! When the `mdc` feature is enabled, the entries of the [`log-mdc`](https://docs.rs/log-mdc) map of the logging thread are added to each event as string fields.
+//! They override the fields of the same names set by [`set_extra_fields`].
+//!
+//! ```ignore
+//! log_mdc::insert("request.id", "f7c2d1");
+//! log::info!("Hello {}!", "world"); // {"request.id":"f7c2d1",...}
+//! ```
 
 use serde_json::{Map, Value};
-use std::sync::RwLock;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, PoisonError, RwLock};
 use thiserror::Error;
 
 type JsonMap = Map<String, Value>;
 
+/// Function which computes lazy extra fields, or `None` if they cannot be converted into a JSON object
+type LazyExtraFields = Arc<dyn Fn() -> Option<JsonMap> + Send + Sync>;
+
 static EXTRA_FIELDS: RwLock<Option<JsonMap>> = RwLock::new(None);
 
-/// Error returned by [`set_extra_fields`].
+static LAZY_EXTRA_FIELDS: RwLock<Vec<LazyExtraFields>> = RwLock::new(Vec::new());
+
+/// Frame of the scoped fields of a thread.
+#[derive(Clone)]
+pub(crate) enum ScopedFrame {
+    Fields(Rc<JsonMap>),
+    Lazy(Rc<dyn Fn() -> Option<JsonMap>>),
+}
+
+thread_local! {
+    /// Fields added to the log records emitted by the current thread, innermost last.
+    static SCOPED_FIELDS: RefCell<Vec<ScopedFrame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Error returned by [`set_extra_fields`] and [`context::push`](crate::context::push).
 #[derive(Error, Debug)]
 pub enum SetExtraFieldsError {
     /// The data cannot be converted into JSON.
@@ -87,22 +121,45 @@ pub enum SetExtraFieldsError {
 /// }).unwrap();
 /// ```
 pub fn set_extra_fields(extra_fields: impl serde::Serialize) -> Result<(), SetExtraFieldsError> {
-    let v = serde_json::to_value(extra_fields)?;
-    let json_map = match v {
-        Value::Object(m) => Some(m),
-        _ => return Err(SetExtraFieldsError::NotObject),
-    };
+    let json_map = Some(to_json_map(extra_fields)?);
 
     {
-        let mut w = EXTRA_FIELDS.write().unwrap();
+        let mut w = EXTRA_FIELDS.write().unwrap_or_else(PoisonError::into_inner);
         *w = json_map;
     }
 
     Ok(())
 }
 
+/// Registers a function which computes extra fields for each log record which passes the filters of the logger.
+///
+/// Use it for the fields which are expensive to compute, e.g. serializing a large context object,
+/// so that no time is spent on them for the records which are filtered out.
+/// The fields override those set by [`set_extra_fields`], and the functions registered later override the earlier ones.
+/// The fields are skipped if they cannot be converted into a JSON object.
+///
+/// The function may run on another thread than the one which logs the record,
+/// e.g. with [`Builder::deferred_formatting`](crate::Builder::deferred_formatting).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::extra_fields;
+/// use serde_json::json;
+///
+/// extra_fields::add_lazy_extra_fields(|| json!({ "process.memory.rss": 123_456_789 }));
+/// ```
+pub fn add_lazy_extra_fields<T: serde::Serialize>(f: impl Fn() -> T + Send + Sync + 'static) {
+    let mut w = LAZY_EXTRA_FIELDS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    w.push(Arc::new(move || to_json_map(f()).ok()));
+}
+
 /// Clear all extra fields previously set by [`set_extra_fields`].
 ///
+/// The functions registered with [`add_lazy_extra_fields`] are kept. Use [`clear_lazy_extra_fields`] to remove them.
+///
 /// # Example
 ///
 /// ```
@@ -121,22 +178,137 @@ pub fn set_extra_fields(extra_fields: impl serde::Serialize) -> Result<(), SetEx
 /// extra_fields::clear_extra_fields();
 /// ```
 pub fn clear_extra_fields() {
-    let mut w = EXTRA_FIELDS.write().unwrap();
+    let mut w = EXTRA_FIELDS.write().unwrap_or_else(PoisonError::into_inner);
     *w = None;
 }
 
-/// Deep merge extra fields into `json_map`
+/// Removes all functions previously registered with [`add_lazy_extra_fields`].
+///
+/// The extra fields set by [`set_extra_fields`] are kept.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::extra_fields;
+/// use serde_json::json;
+///
+/// extra_fields::add_lazy_extra_fields(|| json!({ "process.memory.rss": 123_456_789 }));
+///
+/// extra_fields::clear_lazy_extra_fields();
+/// ```
+pub fn clear_lazy_extra_fields() {
+    let mut w = LAZY_EXTRA_FIELDS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    w.clear();
+}
+
+/// Run `f` with `fields` added to the log records emitted by the current thread.
+pub(crate) fn with_scoped_fields<R>(fields: JsonMap, f: impl FnOnce() -> R) -> R {
+    /// Pops the scoped fields even if `f` panics
+    struct PopGuard(usize);
+
+    impl Drop for PopGuard {
+        fn drop(&mut self) {
+            truncate_scoped_fields(self.0);
+        }
+    }
+
+    let _guard = PopGuard(push_scoped_fields(fields));
+
+    f()
+}
+
+/// Push `fields` to the scoped fields of the current thread, and return the depth of the stack before the push
+pub(crate) fn push_scoped_fields(fields: JsonMap) -> usize {
+    push_scoped_frame(ScopedFrame::Fields(Rc::new(fields)))
+}
+
+/// Push `frame` to the scoped fields of the current thread, and return the depth of the stack before the push
+pub(crate) fn push_scoped_frame(frame: ScopedFrame) -> usize {
+    SCOPED_FIELDS.with(|s| {
+        let mut s = s.borrow_mut();
+        s.push(frame);
+        s.len() - 1
+    })
+}
+
+/// Pop the scoped fields of the current thread until the depth of the stack is `depth`
+pub(crate) fn truncate_scoped_fields(depth: usize) {
+    SCOPED_FIELDS.with(|s| s.borrow_mut().truncate(depth));
+}
+
+/// Convert `data` into a JSON object
+pub(crate) fn to_json_map(data: impl serde::Serialize) -> Result<JsonMap, SetExtraFieldsError> {
+    match serde_json::to_value(data)? {
+        Value::Object(m) => Ok(m),
+        _ => Err(SetExtraFieldsError::NotObject),
+    }
+}
+
+/// Deep merge extra fields and scoped fields into `json_map`
 pub(crate) fn merge_extra_fields(mut json_map: JsonMap) -> JsonMap {
-    let r = EXTRA_FIELDS.read().unwrap();
-    if let Some(extra_fields) = &*r {
-        extend_json_map(&mut json_map, extra_fields);
+    {
+        let r = EXTRA_FIELDS.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(extra_fields) = &*r {
+            extend_json_map(&mut json_map, extra_fields);
+        }
+    }
+
+    // Cloned so that the functions may log without deadlocking
+    let lazy_extra_fields = LAZY_EXTRA_FIELDS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    for f in lazy_extra_fields {
+        if let Some(extra_fields) = f() {
+            extend_json_map(&mut json_map, &extra_fields);
+        }
+    }
+
+    merge_scoped_fields(json_map)
+}
+
+/// Returns the scoped fields of the current thread merged into one map
+pub(crate) fn scoped_fields() -> JsonMap {
+    merge_scoped_fields(JsonMap::new())
+}
+
+/// Deep merge scoped fields of the current thread into `json_map`
+fn merge_scoped_fields(mut json_map: JsonMap) -> JsonMap {
+    #[cfg(feature = "mdc")]
+    merge_mdc(&mut json_map);
+
+    // Cloned so that the lazy frames may log or push frames
+    let frames = SCOPED_FIELDS.with(|s| s.borrow().clone());
+    for frame in frames {
+        match frame {
+            ScopedFrame::Fields(fields) => extend_json_map(&mut json_map, &fields),
+            ScopedFrame::Lazy(f) => {
+                if let Some(fields) = f() {
+                    extend_json_map(&mut json_map, &fields);
+                }
+            }
+        }
     }
 
     json_map
 }
 
+/// Insert the entries of the current thread's [`log_mdc`] map into `json_map`, sorted by key
+#[cfg(feature = "mdc")]
+fn merge_mdc(json_map: &mut JsonMap) {
+    let mut entries = Vec::new();
+    log_mdc::iter(|k, v| entries.push((k.to_string(), v.to_string())));
+    entries.sort();
+
+    for (k, v) in entries {
+        json_map.insert(k, Value::String(v));
+    }
+}
+
 /// Deep merge `b` into `a`
-fn extend_json_map(a: &mut JsonMap, b: &JsonMap) {
+pub(crate) fn extend_json_map(a: &mut JsonMap, b: &JsonMap) {
     for (k, v) in b {
         match (a.get_mut(k), v) {
             (Some(Value::Object(a)), Value::Object(b)) => extend_json_map(a, b),
@@ -166,7 +338,7 @@ mod tests {
         }))
         .unwrap();
 
-        let r = EXTRA_FIELDS.read().unwrap();
+        let r = EXTRA_FIELDS.read().unwrap_or_else(PoisonError::into_inner);
         assert!(r.is_some());
         assert_eq!(
             serde_json::to_string(r.as_ref().unwrap()).unwrap(),
@@ -207,7 +379,7 @@ mod tests {
 
         clear_extra_fields();
 
-        let r = EXTRA_FIELDS.read().unwrap();
+        let r = EXTRA_FIELDS.read().unwrap_or_else(PoisonError::into_inner);
         assert!(r.is_none());
     }
 
@@ -243,6 +415,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extra_fields_after_poisoned() {
+        let _ = std::thread::spawn(|| {
+            let _w = EXTRA_FIELDS.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(EXTRA_FIELDS.is_poisoned());
+
+        // None of these panic
+        set_extra_fields(json!({ "a": 1 })).unwrap();
+        merge_extra_fields(JsonMap::new());
+        clear_extra_fields();
+    }
+
+    #[test]
+    fn test_with_scoped_fields() {
+        let outer = json!({ "a": 1, "b": { "c": 2 } });
+        let inner = json!({ "b": { "d": 3 } });
+
+        let merged = with_scoped_fields(outer.as_object().unwrap().clone(), || {
+            with_scoped_fields(inner.as_object().unwrap().clone(), || {
+                merge_scoped_fields(JsonMap::new())
+            })
+        });
+        assert_eq!(
+            serde_json::to_string(&Value::Object(merged)).unwrap(),
+            json!({
+                "a": 1,
+                "b": {
+                    "c": 2,
+                    "d": 3,
+                },
+            })
+            .to_string()
+        );
+
+        assert!(merge_scoped_fields(JsonMap::new()).is_empty());
+    }
+
+    #[cfg(feature = "mdc")]
+    #[test]
+    fn test_merge_mdc() {
+        let _guard = log_mdc::extend_scoped([("user.id", "42"), ("request.id", "abc")]);
+
+        let merged = with_scoped_fields(
+            json!({ "user.id": "7" }).as_object().unwrap().clone(),
+            || merge_scoped_fields(JsonMap::new()),
+        );
+        assert_eq!(
+            serde_json::to_string(&Value::Object(merged)).unwrap(),
+            json!({
+                "request.id": "abc",
+                "user.id": "7",
+            })
+            .to_string()
+        );
+    }
+
     #[test]
     fn test_extend_json_map() {
         let mut a = json!({
@@ -276,4 +507,36 @@ mod tests {
             .to_string()
         );
     }
+
+    #[test]
+    fn test_lazy_extra_fields() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        clear_extra_fields();
+        clear_lazy_extra_fields();
+        set_extra_fields(json!({ "a": 1, "b": 2 })).unwrap();
+        add_lazy_extra_fields(|| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            json!({ "b": 3, "c": 4 })
+        });
+        add_lazy_extra_fields(|| 42);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+
+        assert_eq!(
+            Value::Object(merge_extra_fields(JsonMap::new())),
+            json!({ "a": 1, "b": 3, "c": 4 })
+        );
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+
+        clear_extra_fields();
+        assert_eq!(
+            Value::Object(merge_extra_fields(JsonMap::new())),
+            json!({ "b": 3, "c": 4 })
+        );
+
+        clear_lazy_extra_fields();
+        assert!(merge_extra_fields(JsonMap::new()).is_empty());
+    }
 }
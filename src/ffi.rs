@@ -0,0 +1,216 @@
+//! C API for embedding the logger into non-Rust components
+//!
+//! This module is available when the `ffi` feature is enabled.
+//! The `ecs-logger-ffi` crate in the `ffi` directory of the repository builds it into a shared and a static library
+//! with `cargo build --release -p ecs-logger-ffi`, and `include/ecs_logger.h` declares the functions.
+//! See the README of the repository for how to link against them.
+//!
+//! ## Example
+//!
+//! ```c
+//! #include "ecs_logger.h"
+//!
+//! int main(void) {
+//!     ecs_logger_init("info");
+//!     ecs_logger_log(ECS_LOGGER_LEVEL_ERROR, "my_component", "Hello world!", "{\"user.id\":\"42\"}");
+//!     ecs_logger_flush();
+//!     return 0;
+//! }
+//! ```
+
+use crate::extra_fields::with_scoped_fields;
+use crate::Builder;
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{self, AssertUnwindSafe};
+
+/// The operation succeeded.
+pub const ECS_LOGGER_OK: c_int = 0;
+
+/// An argument is null, not valid UTF-8, or otherwise invalid.
+pub const ECS_LOGGER_ERR_INVALID_ARGUMENT: c_int = -1;
+
+/// The global logger has already been initialized.
+pub const ECS_LOGGER_ERR_ALREADY_INITIALIZED: c_int = -2;
+
+/// The logger panicked, e.g. in a custom [`Target`](crate::Target) or a formatting hook.
+/// The panic is caught so that it does not unwind into the C caller.
+pub const ECS_LOGGER_ERR_PANIC: c_int = -3;
+
+/// Initializes the global logger with ECS-Logging formatting.
+///
+/// `config` is a filter string in the same syntax as the `RUST_LOG` environment variable (e.g. `"info,my_component=debug"`).
+/// If `config` is null, the `RUST_LOG` environment variable is used instead.
+///
+/// Returns [`ECS_LOGGER_OK`] on success, [`ECS_LOGGER_ERR_INVALID_ARGUMENT`] if `config` is not valid UTF-8,
+/// [`ECS_LOGGER_ERR_ALREADY_INITIALIZED`] if the global logger has already been initialized, or [`ECS_LOGGER_ERR_PANIC`] if it panicked.
+///
+/// # Safety
+///
+/// `config` must be null or a pointer to a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ecs_logger_init(config: *const c_char) -> c_int {
+    catch_panic(|| init_logger(config))
+}
+
+unsafe fn init_logger(config: *const c_char) -> c_int {
+    let mut builder = if config.is_null() {
+        Builder::from_env()
+    } else {
+        let Ok(config) = CStr::from_ptr(config).to_str() else {
+            return ECS_LOGGER_ERR_INVALID_ARGUMENT;
+        };
+
+        let mut builder = Builder::new();
+        builder.parse_filters(config);
+        builder
+    };
+
+    match builder.try_init() {
+        Ok(()) => ECS_LOGGER_OK,
+        Err(_) => ECS_LOGGER_ERR_ALREADY_INITIALIZED,
+    }
+}
+
+/// Logs a message.
+///
+/// `level` is one of `1` (error), `2` (warn), `3` (info), `4` (debug), and `5` (trace).
+/// `fields_json` is null or a JSON object whose fields are merged into the event.
+///
+/// Returns [`ECS_LOGGER_OK`] on success, [`ECS_LOGGER_ERR_INVALID_ARGUMENT`] if any argument is invalid,
+/// or [`ECS_LOGGER_ERR_PANIC`] if the logger panicked.
+/// The message is silently dropped if it is filtered out or the logger has not been initialized.
+///
+/// # Safety
+///
+/// `target` and `message` must be pointers to valid nul-terminated strings.
+/// `fields_json` must be null or a pointer to a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ecs_logger_log(
+    level: c_int,
+    target: *const c_char,
+    message: *const c_char,
+    fields_json: *const c_char,
+) -> c_int {
+    catch_panic(|| log_message(level, target, message, fields_json))
+}
+
+unsafe fn log_message(
+    level: c_int,
+    target: *const c_char,
+    message: *const c_char,
+    fields_json: *const c_char,
+) -> c_int {
+    let Some(level) = level_from_c_int(level) else {
+        return ECS_LOGGER_ERR_INVALID_ARGUMENT;
+    };
+    let Some(target) = str_from_ptr(target) else {
+        return ECS_LOGGER_ERR_INVALID_ARGUMENT;
+    };
+    let Some(message) = str_from_ptr(message) else {
+        return ECS_LOGGER_ERR_INVALID_ARGUMENT;
+    };
+    let fields = if fields_json.is_null() {
+        serde_json::Map::new()
+    } else {
+        match str_from_ptr(fields_json).map(serde_json::from_str) {
+            Some(Ok(serde_json::Value::Object(m))) => m,
+            _ => return ECS_LOGGER_ERR_INVALID_ARGUMENT,
+        }
+    };
+
+    with_scoped_fields(fields, || {
+        log::logger().log(
+            &log::Record::builder()
+                .args(format_args!("{}", message))
+                .level(level)
+                .target(target)
+                .build(),
+        );
+    });
+
+    ECS_LOGGER_OK
+}
+
+/// Flushes buffered log records.
+///
+/// Call this before the process exits. The global logger cannot be uninstalled, so this is also the shutdown routine.
+/// A panic while flushing is caught and ignored.
+#[no_mangle]
+pub extern "C" fn ecs_logger_flush() {
+    let _ = panic::catch_unwind(|| log::logger().flush());
+}
+
+/// Runs `f`, turning a panic into [`ECS_LOGGER_ERR_PANIC`] since unwinding into C is undefined behavior.
+fn catch_panic(f: impl FnOnce() -> c_int) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(ECS_LOGGER_ERR_PANIC)
+}
+
+fn level_from_c_int(level: c_int) -> Option<log::Level> {
+    match level {
+        1 => Some(log::Level::Error),
+        2 => Some(log::Level::Warn),
+        3 => Some(log::Level::Info),
+        4 => Some(log::Level::Debug),
+        5 => Some(log::Level::Trace),
+        _ => None,
+    }
+}
+
+unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::null;
+
+    #[test]
+    fn test_level_from_c_int() {
+        assert_eq!(level_from_c_int(1), Some(log::Level::Error));
+        assert_eq!(level_from_c_int(5), Some(log::Level::Trace));
+        assert_eq!(level_from_c_int(0), None);
+        assert_eq!(level_from_c_int(6), None);
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(|| ECS_LOGGER_OK), ECS_LOGGER_OK);
+        assert_eq!(catch_panic(|| panic!("boom")), ECS_LOGGER_ERR_PANIC);
+    }
+
+    #[test]
+    fn test_log_invalid_argument() {
+        unsafe {
+            assert_eq!(
+                ecs_logger_log(0, c"target".as_ptr(), c"message".as_ptr(), null()),
+                ECS_LOGGER_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                ecs_logger_log(1, null(), c"message".as_ptr(), null()),
+                ECS_LOGGER_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                ecs_logger_log(1, c"target".as_ptr(), null(), null()),
+                ECS_LOGGER_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                ecs_logger_log(1, c"target".as_ptr(), c"message".as_ptr(), c"[1]".as_ptr()),
+                ECS_LOGGER_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                ecs_logger_log(
+                    1,
+                    c"target".as_ptr(),
+                    c"message".as_ptr(),
+                    c"{\"a\":1}".as_ptr()
+                ),
+                ECS_LOGGER_OK
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversion of application types into ECS fields
+
+use crate::context::{self, ContextGuard};
+use crate::extra_fields::{to_json_map, with_scoped_fields};
+use crate::{
+    AccessLog, Categorization, ConnectionContext, Detection, FileFields, HttpContext,
+    KubernetesMetadata, Labels, MetricEvent, NetworkEvent, SecurityEvent, TlsContext, TraceContext,
+    Transaction, UrlFields, UserContext,
+};
+use serde_json::{Map, Value};
+
+/// Type which describes how it maps into ECS fields, e.g. a request or an order of the application.
+///
+/// The fields can be attached to a scope with [`with_fields`] or [`context::push_fields`],
+/// or to a single log call with `fields:` of [`ecs_log!`](crate::ecs_log!).
+/// The keys may be dotted, e.g. `http.request.method`, or nested, and they are merged like the extra fields.
+///
+/// It is implemented for the field types of this crate, e.g. [`HttpContext`] and [`UserContext`], and for JSON objects.
+/// The field types also serialize into the same fields, with dotted keys or as `{"labels":{...}}` for [`Labels`],
+/// so they can be passed to [`context::push`] and [`set_extra_fields`](crate::extra_fields::set_extra_fields) as well.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::ToEcsFields;
+/// use serde_json::{json, Map, Value};
+///
+/// struct Order {
+///     id: u64,
+///     customer: String,
+///     secret_note: String,
+/// }
+///
+/// impl ToEcsFields for Order {
+///     fn to_ecs_fields(&self) -> Map<String, Value> {
+///         let mut fields = Map::new();
+///         fields.insert("transaction.id".to_string(), self.id.to_string().into());
+///         fields.insert("user.name".to_string(), json!(self.customer));
+///         fields
+///     }
+/// }
+///
+/// ecs_logger::init();
+///
+/// let order = Order { id: 42, customer: "alice".to_string(), secret_note: String::new() };
+/// ecs_logger::with_fields(&order, || log::info!("Order placed"));
+/// // {"log.level":"INFO","message":"Order placed","transaction.id":"42","user.name":"alice",...}
+/// ```
+pub trait ToEcsFields {
+    /// Returns the ECS fields of the value.
+    fn to_ecs_fields(&self) -> Map<String, Value>;
+}
+
+impl<T: ToEcsFields + ?Sized> ToEcsFields for &T {
+    fn to_ecs_fields(&self) -> Map<String, Value> {
+        (**self).to_ecs_fields()
+    }
+}
+
+impl ToEcsFields for Map<String, Value> {
+    fn to_ecs_fields(&self) -> Map<String, Value> {
+        self.clone()
+    }
+}
+
+/// Implements [`ToEcsFields`] for the types which serialize into a JSON object.
+///
+/// The types given with a description of their fields also get `in_scope` and `enter`, which attach the fields to the current thread.
+macro_rules! impl_serialize {
+    ($($ty:ty => $fields:literal),+ $(,)?) => {
+        impl_serialize!($($ty),+);
+        $(
+            impl $ty {
+                #[doc = concat!("Runs `f` with ", $fields, " added to the log records emitted by the current thread.")]
+                pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+                    with_fields(self, f)
+                }
+
+                #[doc = concat!("Adds ", $fields, " to the log records emitted by the current thread until the returned guard is dropped.")]
+                ///
+                /// See [`context::push`] for how the guards nest.
+                pub fn enter(&self) -> ContextGuard {
+                    context::push_fields(self)
+                }
+            }
+        )+
+    };
+    ($($ty:ty),+ $(,)?) => {$(
+        impl ToEcsFields for $ty {
+            fn to_ecs_fields(&self) -> Map<String, Value> {
+                to_json_map(self).expect(concat!(stringify!($ty), " should be converted into a JSON object"))
+            }
+        }
+    )+};
+}
+
+impl_serialize!(
+    Categorization => "the categorization fields",
+    ConnectionContext => "the `client.*` and `server.*` fields",
+    FileFields => "the `file.*` fields",
+    HttpContext => "the `http.*`, `url.path`, and `user_agent.original` fields",
+    Labels => "the labels",
+    TlsContext => "the `tls.*` fields",
+    TraceContext => "the `trace.id`, `span.id`, and `transaction.id` fields",
+    Transaction => "the `transaction.*` fields",
+    UrlFields => "the `url.*` fields",
+    UserContext => "the `user.*` fields",
+);
+
+impl_serialize!(KubernetesMetadata);
+
+/// Implements [`ToEcsFields`] for the types which have `fields()`
+macro_rules! impl_fields {
+    ($($ty:ty),+) => {$(
+        impl ToEcsFields for $ty {
+            fn to_ecs_fields(&self) -> Map<String, Value> {
+                self.fields()
+            }
+        }
+    )+};
+}
+
+impl_fields!(
+    AccessLog,
+    Detection,
+    MetricEvent,
+    NetworkEvent,
+    SecurityEvent
+);
+
+/// Runs `f` with the fields of `value` added to the log records emitted by the current thread.
+///
+/// See [`ToEcsFields`] for an example.
+pub fn with_fields<R>(value: &(impl ToEcsFields + ?Sized), f: impl FnOnce() -> R) -> R {
+    with_scoped_fields(value.to_ecs_fields(), f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::json;
+
+    struct Request {
+        method: &'static str,
+        path: &'static str,
+    }
+
+    impl ToEcsFields for Request {
+        fn to_ecs_fields(&self) -> Map<String, Value> {
+            let mut fields = Map::new();
+            fields.insert("http.request.method".to_string(), self.method.into());
+            fields.insert("url.path".to_string(), self.path.into());
+            fields
+        }
+    }
+
+    #[test]
+    fn test_with_fields() {
+        let request = Request {
+            method: "GET",
+            path: "/health",
+        };
+        let fields = with_fields(&request, || {
+            with_fields(&UserContext::new("42"), scoped_fields)
+        });
+        assert_eq!(
+            Value::Object(fields),
+            json!({ "http.request.method": "GET", "url.path": "/health", "user.id": "42" })
+        );
+        assert!(scoped_fields().is_empty());
+    }
+}
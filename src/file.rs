@@ -0,0 +1,167 @@
+//! `file.*` fields of a file on the filesystem
+
+use crate::timestamp::format_timestamp;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+
+/// File which the log records are about, e.g. for tools which log filesystem activity.
+///
+/// | Field | Source |
+/// |---|---|
+/// | `file.path` | the path, as given |
+/// | `file.name` | the last component of the path |
+/// | `file.extension` | the extension of the name, without the leading dot |
+/// | `file.directory` | the parent directory of the path |
+/// | `file.type` | `file`, `dir`, or `symlink`, from the metadata |
+/// | `file.size` | the size in bytes of a regular file, from the metadata |
+/// | `file.mtime` | the last modification time, from the metadata |
+///
+/// The paths which are not valid UTF-8 are converted lossily.
+/// See [`ToEcsFields`](crate::ToEcsFields) for how to attach the fields.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::FileFields;
+///
+/// ecs_logger::init();
+///
+/// let path = std::env::temp_dir().join("report.csv");
+/// std::fs::write(&path, "id,name\n").unwrap();
+///
+/// FileFields::from_path(&path)
+///     .unwrap()
+///     .in_scope(|| log::info!("Uploaded"));
+/// // {"log.level":"INFO","message":"Uploaded","file.path":"/tmp/report.csv","file.name":"report.csv","file.extension":"csv","file.size":8,...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileFields {
+    path: String,
+    name: Option<String>,
+    extension: Option<String>,
+    directory: Option<String>,
+    file_type: Option<&'static str>,
+    size: Option<u64>,
+    mtime: Option<String>,
+}
+
+impl FileFields {
+    /// Creates the fields derived from `path` only, without accessing the filesystem.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let lossy = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+        FileFields {
+            path: path.to_string_lossy().into_owned(),
+            name: path.file_name().map(lossy),
+            extension: path.extension().map(lossy),
+            directory: path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .map(|parent| lossy(parent.as_os_str())),
+            ..FileFields::default()
+        }
+    }
+
+    /// Creates the fields of the file at `path`, reading its metadata.
+    ///
+    /// Symbolic links are followed.
+    pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let metadata = std::fs::metadata(path.as_ref())?;
+        Ok(Self::new(path).metadata(&metadata))
+    }
+
+    /// Sets `file.type`, `file.size`, and `file.mtime` from `metadata`.
+    pub fn metadata(mut self, metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        self.file_type = if file_type.is_symlink() {
+            Some("symlink")
+        } else if file_type.is_dir() {
+            Some("dir")
+        } else if file_type.is_file() {
+            Some("file")
+        } else {
+            None
+        };
+        self.size = file_type.is_file().then_some(metadata.len());
+        self.mtime = metadata
+            .modified()
+            .ok()
+            .map(|mtime| format_timestamp(&DateTime::<Utc>::from(mtime), SecondsFormat::Millis));
+        self
+    }
+}
+
+impl Serialize for FileFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("file.path", &self.path)?;
+        let strings = [
+            ("file.name", &self.name),
+            ("file.extension", &self.extension),
+            ("file.directory", &self.directory),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        if let Some(file_type) = self.file_type {
+            map.serialize_entry("file.type", file_type)?;
+        }
+        if let Some(size) = self.size {
+            map.serialize_entry("file.size", &size)?;
+        }
+        if let Some(mtime) = &self.mtime {
+            map.serialize_entry("file.mtime", mtime)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::to_json_map;
+    use serde_json::json;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            serde_json::to_value(FileFields::new("/var/log/app.log.gz")).unwrap(),
+            json!({
+                "file.path": "/var/log/app.log.gz",
+                "file.name": "app.log.gz",
+                "file.extension": "gz",
+                "file.directory": "/var/log",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(FileFields::new("Makefile")).unwrap(),
+            json!({ "file.path": "Makefile", "file.name": "Makefile" })
+        );
+    }
+
+    #[test]
+    fn test_from_path() {
+        let dir = std::env::temp_dir().join(format!("ecs_logger_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+        std::fs::write(&path, "{}\n").unwrap();
+
+        let fields = to_json_map(FileFields::from_path(&path).unwrap()).unwrap();
+        assert_eq!(fields["file.name"], "data.json");
+        assert_eq!(fields["file.type"], "file");
+        assert_eq!(fields["file.size"], 3);
+        assert!(fields["file.mtime"].as_str().unwrap().ends_with('Z'));
+
+        let fields = to_json_map(FileFields::from_path(&dir).unwrap()).unwrap();
+        assert_eq!(fields["file.type"], "dir");
+        assert!(fields.get("file.size").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(FileFields::from_path(&path).is_err());
+    }
+}
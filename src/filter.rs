@@ -0,0 +1,382 @@
+//! Filters layered on top of the `RUST_LOG` directives
+
+#[cfg(not(feature = "env_logger"))]
+use log::{LevelFilter, Metadata, Record};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of records dropped by [`MessageFilter`]s.
+static SUPPRESSED: AtomicU64 = AtomicU64::new(0);
+
+/// Level directives in the syntax of the `RUST_LOG` environment variable, e.g. `info,my_app=debug/timeout`.
+///
+/// With the `env_logger` feature, they are parsed and matched by `env_logger` itself.
+#[cfg(feature = "env_logger")]
+pub(crate) use env_logger::filter::{Builder as DirectivesBuilder, Filter as Directives};
+
+/// Builder of [`Directives`], a minimal replacement of `env_logger::filter::Builder` for the builds without `env_logger`.
+#[cfg(not(feature = "env_logger"))]
+#[derive(Debug, Default)]
+pub(crate) struct DirectivesBuilder {
+    directives: Vec<Directive>,
+    /// Substring which messages must contain
+    message: Option<String>,
+}
+
+/// Level directives in the syntax of the `RUST_LOG` environment variable, e.g. `info,my_app=debug/timeout`.
+#[cfg(not(feature = "env_logger"))]
+#[derive(Debug)]
+pub(crate) struct Directives {
+    /// Sorted by the length of the name, so that the last matching directive is the most specific
+    directives: Vec<Directive>,
+    /// Substring which messages must contain
+    message: Option<String>,
+}
+
+#[cfg(not(feature = "env_logger"))]
+#[derive(Debug)]
+struct Directive {
+    name: Option<String>,
+    level: LevelFilter,
+}
+
+#[cfg(not(feature = "env_logger"))]
+impl DirectivesBuilder {
+    /// Adds the directives of `filters`. Invalid directives are reported to stderr and ignored, like `env_logger` does.
+    pub(crate) fn parse(&mut self, filters: &str) -> &mut Self {
+        let mut parts = filters.split('/');
+        let modules = parts.next().unwrap_or_default();
+        let message = parts.next();
+        if parts.next().is_some() {
+            eprintln!(
+                "warning: invalid logging spec '{}', ignoring it (too many '/'s)",
+                filters
+            );
+            return self;
+        }
+
+        for directive in modules.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+
+            let mut parts = directive.split('=');
+            let (name, level) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), None, None) => match name.parse() {
+                    Ok(level) => (None, level),
+                    Err(_) => (Some(name), LevelFilter::Trace),
+                },
+                (Some(name), Some(level), None) => match level.parse() {
+                    Ok(level) => (Some(name), level),
+                    Err(_) => {
+                        eprintln!("warning: invalid logging spec '{}', ignoring it", level);
+                        continue;
+                    }
+                },
+                _ => {
+                    eprintln!("warning: invalid logging spec '{}', ignoring it", directive);
+                    continue;
+                }
+            };
+            self.filter(name, level);
+        }
+
+        if let Some(message) = message {
+            self.message = Some(message.to_string());
+        }
+        self
+    }
+
+    /// Adds a directive for targets starting with `name`, or for all targets if `name` is `None`.
+    /// An existing directive with the same name is replaced.
+    pub(crate) fn filter(&mut self, name: Option<&str>, level: LevelFilter) -> &mut Self {
+        match self
+            .directives
+            .iter_mut()
+            .find(|d| d.name.as_deref() == name)
+        {
+            Some(directive) => directive.level = level,
+            None => self.directives.push(Directive {
+                name: name.map(str::to_string),
+                level,
+            }),
+        }
+        self
+    }
+
+    /// Builds the directives. Only `error` level logs are enabled if there is no directive.
+    pub(crate) fn build(&mut self) -> Directives {
+        let mut builder = std::mem::take(self);
+        if builder.directives.is_empty() {
+            builder.filter(None, LevelFilter::Error);
+        }
+        builder
+            .directives
+            .sort_by_key(|d| d.name.as_ref().map_or(0, String::len));
+        Directives {
+            directives: builder.directives,
+            message: builder.message,
+        }
+    }
+}
+
+#[cfg(not(feature = "env_logger"))]
+impl Directives {
+    /// Returns the maximum level enabled by any directive.
+    pub(crate) fn filter(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|d| d.level)
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+
+    /// Returns `true` if the most specific directive matching the target enables the level.
+    pub(crate) fn enabled(&self, metadata: &Metadata) -> bool {
+        self.directives
+            .iter()
+            .rev()
+            .find(|d| match &d.name {
+                Some(name) => metadata.target().starts_with(name.as_str()),
+                None => true,
+            })
+            .is_some_and(|d| metadata.level() <= d.level)
+    }
+
+    /// Returns `true` if the record is enabled and its message contains the message filter.
+    pub(crate) fn matches(&self, record: &Record) -> bool {
+        self.enabled(record.metadata())
+            && self
+                .message
+                .as_ref()
+                .is_none_or(|m| record.args().to_string().contains(m.as_str()))
+    }
+}
+
+/// Allow and deny lists of patterns matched against log targets.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TargetFilter {
+    allow: Vec<Pattern>,
+    deny: Vec<Pattern>,
+}
+
+/// Pattern matched against a log target.
+#[derive(Debug, Clone)]
+pub(crate) enum Pattern {
+    Glob(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+/// List of patterns matched against rendered messages to drop entirely.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MessageFilter {
+    suppress: Vec<Pattern>,
+}
+
+impl MessageFilter {
+    pub(crate) fn suppress(&mut self, pattern: Pattern) {
+        self.suppress.push(pattern);
+    }
+
+    /// Returns `true` if the message of `record` matches no suppression pattern, counting the suppressed records.
+    pub(crate) fn matches(&self, record: &log::Record) -> bool {
+        if self.suppress.is_empty() {
+            return true;
+        }
+
+        let suppressed = match record.args().as_str() {
+            Some(message) => self.is_suppressed(message),
+            None => self.is_suppressed(&record.args().to_string()),
+        };
+        if suppressed {
+            SUPPRESSED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        !suppressed
+    }
+
+    fn is_suppressed(&self, message: &str) -> bool {
+        self.suppress.iter().any(|p| p.matches(message))
+    }
+}
+
+/// Returns the number of records dropped because their messages matched a suppression pattern.
+///
+/// The count is shared by all loggers in the process. See [`Builder::suppress_message`](crate::Builder::suppress_message).
+pub fn suppressed_count() -> u64 {
+    SUPPRESSED.load(Ordering::Relaxed)
+}
+
+impl Pattern {
+    fn matches(&self, target: &str) -> bool {
+        match self {
+            Pattern::Glob(pattern) => glob_match(pattern, target),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.is_match(target),
+        }
+    }
+}
+
+impl TargetFilter {
+    pub(crate) fn allow(&mut self, pattern: Pattern) {
+        self.allow.push(pattern);
+    }
+
+    pub(crate) fn deny(&mut self, pattern: Pattern) {
+        self.deny.push(pattern);
+    }
+
+    /// Returns `true` if `target` matches any allow pattern (or there is none) and matches no deny pattern.
+    pub(crate) fn matches(&self, target: &str) -> bool {
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|p| p.matches(target));
+        allowed && !self.deny.iter().any(|p| p.matches(target))
+    }
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any sequence of characters and `?` matches any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text position it is matched against
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{LevelFilter, Metadata};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("h2::*", "h2::proto::streams"));
+        assert!(!glob_match("h2::*", "h2"));
+        assert!(!glob_match("h2::*", "hyper::client"));
+        assert!(glob_match("*::tests", "my_app::server::tests"));
+        assert!(glob_match("my_app::*::db", "my_app::server::db"));
+        assert!(glob_match("tokio?", "tokio1"));
+        assert!(!glob_match("tokio?", "tokio"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn test_directives() {
+        let enabled = |directives: &Directives, target: &str, level: log::Level| {
+            directives.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+
+        let directives = DirectivesBuilder::default().build();
+        assert!(enabled(&directives, "my_app", log::Level::Error));
+        assert!(!enabled(&directives, "my_app", log::Level::Warn));
+        assert_eq!(directives.filter(), LevelFilter::Error);
+
+        let directives = DirectivesBuilder::default()
+            .parse("warn, my_app=debug,my_app::db=off,h2,bad=loud,a=b=c")
+            .build();
+        assert!(enabled(&directives, "hyper", log::Level::Warn));
+        assert!(!enabled(&directives, "hyper", log::Level::Info));
+        assert!(enabled(&directives, "my_app::server", log::Level::Debug));
+        assert!(!enabled(&directives, "my_app::server", log::Level::Trace));
+        assert!(!enabled(&directives, "my_app::db", log::Level::Error));
+        assert!(enabled(&directives, "h2::proto", log::Level::Trace));
+        assert!(!enabled(&directives, "bad", log::Level::Info));
+        assert_eq!(directives.filter(), LevelFilter::Trace);
+
+        let directives = DirectivesBuilder::default()
+            .parse("info/timeout")
+            .filter(None, LevelFilter::Debug)
+            .build();
+        assert!(directives.matches(
+            &log::Record::builder()
+                .level(log::Level::Debug)
+                .args(format_args!("request timeout after {}s", 30))
+                .build()
+        ));
+        assert!(!directives.matches(
+            &log::Record::builder()
+                .level(log::Level::Debug)
+                .args(format_args!("request done"))
+                .build()
+        ));
+    }
+
+    #[test]
+    fn test_target_filter() {
+        let mut filter = TargetFilter::default();
+        assert!(filter.matches("anything"));
+
+        filter.deny(Pattern::Glob("h2::*".to_string()));
+        filter.deny(Pattern::Glob("rustls::*".to_string()));
+        assert!(filter.matches("my_app"));
+        assert!(!filter.matches("h2::proto"));
+        assert!(!filter.matches("rustls::conn"));
+
+        filter.allow(Pattern::Glob("my_app*".to_string()));
+        filter.allow(Pattern::Glob("hyper::*".to_string()));
+        assert!(filter.matches("my_app::server"));
+        assert!(filter.matches("hyper::client"));
+        assert!(!filter.matches("tokio::runtime"));
+
+        filter.deny(Pattern::Glob("my_app::noisy".to_string()));
+        assert!(!filter.matches("my_app::noisy"));
+    }
+
+    #[test]
+    fn test_message_filter() {
+        let mut filter = MessageFilter::default();
+        filter.suppress(Pattern::Glob("*connection reset by peer*".to_string()));
+
+        let before = suppressed_count();
+        assert!(filter.matches(
+            &log::Record::builder()
+                .args(format_args!("hello {}", "world"))
+                .build()
+        ));
+        assert!(!filter.matches(
+            &log::Record::builder()
+                .args(format_args!("io error: {}", "connection reset by peer"))
+                .build()
+        ));
+        assert!(suppressed_count() > before);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_target_filter_regex() {
+        let mut filter = TargetFilter::default();
+        filter.allow(Pattern::Regex(regex::Regex::new(r"^my_app(::|$)").unwrap()));
+        filter.deny(Pattern::Regex(regex::Regex::new(r"::(db|cache)$").unwrap()));
+
+        assert!(filter.matches("my_app"));
+        assert!(filter.matches("my_app::server"));
+        assert!(!filter.matches("my_app_2"));
+        assert!(!filter.matches("my_app::db"));
+        assert!(!filter.matches("my_app::server::cache"));
+    }
+}
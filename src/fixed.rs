@@ -0,0 +1,296 @@
+//! Allocation-free encoding into fixed-size buffers
+//!
+//! Memory-constrained real-time environments often forbid heap growth after initialization.
+//! [`FixedBuffer`] owns a preallocated buffer and encodes records into it without allocating,
+//! dropping (and counting) events which do not fit.
+//!
+//! The output is identical to serializing [`Event`](crate::ecs::Event) with [`serde_json`], so the fields added by the logger, e.g. extra fields and `process.thread`, are not supported.
+//! Like the [`ecs`](crate::ecs) module, this module is available in `no_std` builds.
+//! With the `std` feature, [`Builder::fixed_buffer`](crate::Builder::fixed_buffer) configures a logger which encodes the events with it.
+//!
+//! ## Example
+//!
+//! ```
+//! use ecs_logger::fixed::FixedBuffer;
+//!
+//! // Allocate once at startup
+//! let mut buffer = FixedBuffer::<512>::new();
+//!
+//! let record = log::Record::builder()
+//!     .args(format_args!("Hello world!"))
+//!     .level(log::Level::Info)
+//!     .target("sensor")
+//!     .build();
+//!
+//! if let Ok(line) = buffer.encode(chrono::Utc::now(), &record) {
+//!     // Ship `line` over UART, RTT, etc.
+//!     assert!(line.ends_with(b"\n"));
+//! }
+//! ```
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use core::fmt::{self, Write};
+
+/// Error returned when an encoded event does not fit in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferFull;
+
+impl fmt::Display for BufferFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the encoded event does not fit in the buffer")
+    }
+}
+
+/// A preallocated buffer which ECS log lines are encoded into.
+#[derive(Debug, Clone)]
+pub struct FixedBuffer<const N: usize> {
+    buf: [u8; N],
+    dropped: u64,
+}
+
+impl<const N: usize> Default for FixedBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FixedBuffer<N> {
+    /// Creates a buffer of `N` bytes.
+    pub const fn new() -> Self {
+        FixedBuffer {
+            buf: [0; N],
+            dropped: 0,
+        }
+    }
+
+    /// Encodes `record` as an ECS log line terminated by a newline, and returns the encoded bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferFull`] and increments [`dropped`](Self::dropped) if the line does not fit in the buffer.
+    pub fn encode(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        record: &log::Record,
+    ) -> Result<&[u8], BufferFull> {
+        match encode(&mut self.buf, timestamp, record) {
+            Ok(len) => Ok(&self.buf[..len]),
+            Err(e) => {
+                self.dropped += 1;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the number of events dropped because they did not fit in the buffer.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Encodes `record` as an ECS log line terminated by a newline into `buf`, and returns the number of bytes written.
+///
+/// # Errors
+///
+/// Returns [`BufferFull`] if the line does not fit in `buf`. The content of `buf` is unspecified in that case.
+pub fn encode(
+    buf: &mut [u8],
+    timestamp: DateTime<Utc>,
+    record: &log::Record,
+) -> Result<usize, BufferFull> {
+    let mut w = SliceWriter { buf, len: 0 };
+    write_record(&mut w, timestamp, record).map_err(|_| BufferFull)?;
+    Ok(w.len)
+}
+
+fn write_record(
+    w: &mut SliceWriter,
+    timestamp: DateTime<Utc>,
+    record: &log::Record,
+) -> fmt::Result {
+    w.write_str("{\"@timestamp\":\"")?;
+    write_timestamp(w, timestamp)?;
+    w.write_str("\",\"log.level\":\"")?;
+    w.write_str(record.level().as_str())?;
+    w.write_str("\",\"message\":\"")?;
+    write!(JsonEscape(w), "{}", record.args())?;
+    w.write_str("\",\"ecs.version\":\"")?;
+    w.write_str(crate::ecs::ECS_VERSION)?;
+    w.write_str("\",\"log.origin\":{\"file\":{")?;
+
+    let line = record.line();
+    if let Some(line) = line {
+        write!(w, "\"line\":{}", line)?;
+    }
+    if let Some(name) = record.file().and_then(crate::ecs::file_name) {
+        if line.is_some() {
+            w.write_char(',')?;
+        }
+        write_string(w, "name")?;
+        w.write_char(':')?;
+        write_string(w, name)?;
+    }
+
+    w.write_str("},\"rust\":{\"target\":")?;
+    write_string(w, record.target())?;
+    if let Some(module_path) = record.module_path() {
+        w.write_str(",\"module_path\":")?;
+        write_string(w, module_path)?;
+    }
+    if let Some(file_path) = record.file() {
+        w.write_str(",\"file_path\":")?;
+        write_string(w, file_path)?;
+    }
+    w.write_str("}}}\n")
+}
+
+/// Writes `timestamp` in the same format as the `serde` implementation of [`DateTime`].
+fn write_timestamp(w: &mut impl Write, timestamp: DateTime<Utc>) -> fmt::Result {
+    write!(
+        w,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        timestamp.year(),
+        timestamp.month(),
+        timestamp.day(),
+        timestamp.hour(),
+        timestamp.minute(),
+        timestamp.second()
+    )?;
+
+    match timestamp.nanosecond() % 1_000_000_000 {
+        0 => {}
+        nanos if nanos % 1_000_000 == 0 => write!(w, ".{:03}", nanos / 1_000_000)?,
+        nanos if nanos % 1_000 == 0 => write!(w, ".{:06}", nanos / 1_000)?,
+        nanos => write!(w, ".{:09}", nanos)?,
+    }
+
+    w.write_char('Z')
+}
+
+fn write_string(w: &mut SliceWriter, s: &str) -> fmt::Result {
+    w.write_char('"')?;
+    JsonEscape(w).write_str(s)?;
+    w.write_char('"')
+}
+
+/// [`fmt::Write`] adapter over a byte slice which fails instead of growing.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// [`fmt::Write`] adapter which escapes the content of a JSON string in the same way as [`serde_json`].
+struct JsonEscape<'a, 'b>(&'a mut SliceWriter<'b>);
+
+impl Write for JsonEscape<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+
+        for (i, c) in s.char_indices() {
+            let escaped = match c {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                '\u{08}' => "\\b",
+                '\u{0c}' => "\\f",
+                c if (c as u32) < 0x20 => "",
+                _ => continue,
+            };
+
+            self.0.write_str(&s[start..i])?;
+            if escaped.is_empty() {
+                write!(self.0, "\\u{:04x}", c as u32)?;
+            } else {
+                self.0.write_str(escaped)?;
+            }
+            start = i + c.len_utf8();
+        }
+
+        self.0.write_str(&s[start..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::Event;
+
+    fn assert_same_as_serde(timestamp: &str, record: &log::Record) {
+        let timestamp = DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let mut buf = [0; 1024];
+        let len = encode(&mut buf, timestamp, record).unwrap();
+
+        assert_eq!(
+            core::str::from_utf8(&buf[..len]).unwrap(),
+            serde_json::to_string(&Event::new(timestamp, record)).unwrap() + "\n"
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_same_as_serde(
+            "2021-11-24T17:38:21.000098765Z",
+            &log::Record::builder()
+                .args(format_args!("tracing \"msg\"\n\t\u{1}\\ ✓"))
+                .level(log::Level::Trace)
+                .target("myCustomTarget123")
+                .file(Some("src/path/to/your/file.rs"))
+                .line(Some(1234))
+                .module_path(Some("my_app::path::to::your::file"))
+                .build(),
+        );
+        assert_same_as_serde(
+            "2021-11-24T17:38:21.120Z",
+            &log::Record::builder()
+                .args(format_args!("{} {}", 1, 2))
+                .level(log::Level::Error)
+                .target("t")
+                .file(Some("file.rs"))
+                .build(),
+        );
+        assert_same_as_serde(
+            "2021-11-24T17:38:21Z",
+            &log::Record::builder()
+                .args(format_args!("no origin"))
+                .level(log::Level::Info)
+                .line(Some(1))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn test_fixed_buffer_drops_large_events() {
+        let mut buffer = FixedBuffer::<64>::new();
+        let message = "x".repeat(64);
+
+        assert_eq!(
+            buffer.encode(
+                DateTime::default(),
+                &log::Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(log::Level::Info)
+                    .build()
+            ),
+            Err(BufferFull)
+        );
+        assert_eq!(buffer.dropped(), 1);
+    }
+}
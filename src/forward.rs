@@ -0,0 +1,166 @@
+//! Forwarding of events to other loggers with the ECS fields as key-values
+
+use crate::{FormatOptions, KeyStyle};
+use log::kv::{self, Key, Source, VisitSource};
+use log::{Log, Record};
+use serde_json::Value;
+
+/// Loggers which receive each record with the ECS fields attached as key-values.
+pub(crate) struct Forwarder {
+    loggers: Vec<Box<dyn Log>>,
+    options: FormatOptions,
+}
+
+/// ECS fields with dotted names, as a key-value source.
+struct Fields(Vec<(String, Value)>);
+
+impl Forwarder {
+    pub(crate) fn new(loggers: Vec<Box<dyn Log>>, options: FormatOptions) -> Self {
+        Forwarder { loggers, options }
+    }
+
+    pub(crate) fn log(&self, record: &Record) {
+        let loggers = self
+            .loggers
+            .iter()
+            .filter(|logger| logger.enabled(record.metadata()))
+            .collect::<Vec<_>>();
+        if loggers.is_empty() {
+            return;
+        }
+
+        let fields = Fields::new(crate::event_json_map(
+            crate::timestamp::get_timestamp(),
+            record,
+            &self.options,
+        ));
+        let forwarded = Record::builder()
+            .args(*record.args())
+            .level(record.level())
+            .target(record.target())
+            .module_path(record.module_path())
+            .file(record.file())
+            .line(record.line())
+            .key_values(&fields)
+            .build();
+
+        for logger in loggers {
+            logger.log(&forwarded);
+        }
+    }
+
+    pub(crate) fn flush(&self) {
+        for logger in &self.loggers {
+            logger.flush();
+        }
+    }
+}
+
+impl Fields {
+    /// Collapses `event` into dotted keys, leaving out the message, which is forwarded as the record body.
+    fn new(event: serde_json::Map<String, Value>) -> Self {
+        let fields = KeyStyle::Flat
+            .apply(event)
+            .into_iter()
+            .filter(|(k, _)| k != "message")
+            .map(|(k, v)| match v {
+                // Key-values cannot hold arrays or objects without serde support
+                Value::Array(_) | Value::Object(_) => (k, Value::String(v.to_string())),
+                v => (k, v),
+            })
+            .collect();
+
+        Fields(fields)
+    }
+}
+
+impl Source for Fields {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (k, v) in &self.0 {
+            let value = match v {
+                Value::Null => kv::Value::null(),
+                Value::Bool(b) => kv::Value::from(*b),
+                Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                    (Some(n), _) => kv::Value::from(n),
+                    (None, Some(n)) => kv::Value::from(n),
+                    (None, None) => kv::Value::from(n.as_f64().unwrap_or(f64::NAN)),
+                },
+                Value::String(s) => kv::Value::from(s.as_str()),
+                Value::Array(_) | Value::Object(_) => unreachable!("converted into strings"),
+            };
+            visitor.visit_pair(Key::from_str(k), value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Metadata;
+    use std::sync::{Arc, Mutex};
+
+    /// Logger which records the key-values it receives as strings
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Log for Recorder {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            struct Visitor<'a>(&'a mut Vec<(String, String)>);
+
+            impl<'kvs> VisitSource<'kvs> for Visitor<'_> {
+                fn visit_pair(
+                    &mut self,
+                    key: Key<'kvs>,
+                    value: kv::Value<'kvs>,
+                ) -> Result<(), kv::Error> {
+                    self.0.push((key.to_string(), value.to_string()));
+                    Ok(())
+                }
+            }
+
+            let mut recorded = self.0.lock().unwrap();
+            recorded.push(("message".to_string(), record.args().to_string()));
+            record
+                .key_values()
+                .visit(&mut Visitor(&mut recorded))
+                .unwrap();
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_forwarder() {
+        let recorder = Recorder::default();
+        let forwarder = Forwarder::new(vec![Box::new(recorder.clone())], FormatOptions::default());
+
+        forwarder.log(
+            &Record::builder()
+                .args(format_args!("Hello {}!", "world"))
+                .level(log::Level::Warn)
+                .target("my_app")
+                .line(Some(12))
+                .build(),
+        );
+
+        let recorded = recorder.0.lock().unwrap().clone();
+        let get = |key: &str| {
+            recorded
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get("message"), Some("Hello world!"));
+        assert_eq!(get("log.level"), Some("WARN"));
+        assert_eq!(get("log.origin.file.line"), Some("12"));
+        assert_eq!(get("log.origin.rust.target"), Some("my_app"));
+        assert_eq!(get("@timestamp"), Some(crate::timestamp::MOCK_TIMESTAMP));
+        assert_eq!(recorded.iter().filter(|(k, _)| k == "message").count(), 1);
+    }
+}
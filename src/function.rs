@@ -0,0 +1,146 @@
+//! Name of the enclosing function added as `log.origin.function`
+//!
+//! The name is set by [`log_fn!`](crate::log_fn) for the duration of the log call.
+
+use std::cell::Cell;
+
+thread_local! {
+    /// Function which logs the record being logged by the current thread.
+    static FUNCTION: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `function` reported as the function which logs the records emitted by the current thread.
+pub fn with_function<R>(function: &'static str, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous function even if `f` panics
+    struct RestoreGuard(Option<&'static str>);
+
+    impl Drop for RestoreGuard {
+        fn drop(&mut self) {
+            FUNCTION.with(|t| t.set(self.0));
+        }
+    }
+
+    let _guard = RestoreGuard(FUNCTION.with(|t| t.replace(Some(function))));
+
+    f()
+}
+
+/// Returns the function which logs the record being logged by the current thread, if it is known.
+pub(crate) fn current_function() -> Option<&'static str> {
+    FUNCTION.try_with(Cell::get).ok().flatten()
+}
+
+/// Returns the name of the function enclosing the item whose type name is `item`, relative to `module_path`.
+///
+/// `item` is the type name of a nested function named `f`, e.g. `my_app::Server::handle::{{closure}}::f`.
+pub fn function_name(item: &'static str, module_path: &str) -> &'static str {
+    let mut name = item.strip_suffix("::f").unwrap_or(item);
+    while let Some(outer) = name.strip_suffix("::{{closure}}") {
+        name = outer;
+    }
+
+    if let Some(relative) = name
+        .strip_prefix(module_path)
+        .and_then(|name| name.strip_prefix("::"))
+    {
+        return relative;
+    }
+
+    // Modules declared in a function body are prefixed with the path of the function in the type name
+    let module = module_path.rsplit("::").next().unwrap_or(module_path);
+    match name.rfind(&format!("::{}::", module)) {
+        Some(i) => &name[i + module.len() + 4..],
+        None => name,
+    }
+}
+
+/// Expands to the name of the enclosing function relative to the current module, e.g. `Server::handle`.
+///
+/// Closures are attributed to the function which defines them.
+///
+/// # Example
+///
+/// ```
+/// mod server {
+///     pub fn handle() -> &'static str {
+///         ecs_logger::function_name!()
+///     }
+/// }
+///
+/// assert_eq!(server::handle(), "handle");
+/// ```
+#[macro_export]
+macro_rules! function_name {
+    () => {{
+        fn f() {}
+        $crate::__private::function_name(::core::any::type_name_of_val(&f), ::core::module_path!())
+    }};
+}
+
+/// Logs a message like [`log::log!`], adding the name of the enclosing function as `log.origin.function`.
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// fn handle_request() {
+///     ecs_logger::log_fn!(Level::Info, "Handling request");
+///     // {"log.level":"INFO","message":"Handling request","log.origin":{"function":"handle_request",...},...}
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_fn {
+    (target: $target:expr, $lvl:expr, $($arg:tt)+) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            $crate::__private::with_function($crate::function_name!(), || {
+                $crate::__private::log::log!(target: $target, lvl, $($arg)+);
+            });
+        }
+    }};
+    ($lvl:expr, $($arg:tt)+) => {
+        $crate::log_fn!(target: ::core::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Server;
+
+    fn call(f: impl FnOnce() -> &'static str) -> &'static str {
+        f()
+    }
+
+    impl Server {
+        fn handle(&self) -> &'static str {
+            crate::function_name!()
+        }
+
+        fn handle_in_closure(&self) -> &'static str {
+            call(|| crate::function_name!())
+        }
+    }
+
+    #[test]
+    fn test_function_name() {
+        assert_eq!(crate::function_name!(), "test_function_name");
+        assert_eq!(Server.handle(), "Server::handle");
+        assert_eq!(Server.handle_in_closure(), "Server::handle_in_closure");
+        assert_eq!(function_name("my_app::handle::f", "my_app"), "handle");
+        assert_eq!(function_name("other::handle::f", "my_app"), "other::handle");
+        assert_eq!(
+            function_name("my_app::main::server::handle::f", "my_app::server"),
+            "handle"
+        );
+    }
+
+    #[test]
+    fn test_with_function() {
+        assert_eq!(current_function(), None);
+        assert_eq!(with_function("handle", current_function), Some("handle"));
+        assert_eq!(current_function(), None);
+    }
+}
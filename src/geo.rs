@@ -0,0 +1,208 @@
+//! `*.geo.*` fields resolved from the IP addresses of the events
+
+use serde_json::{Map, Value};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Lookup of the geographic location of IP addresses, e.g. backed by a GeoIP database.
+///
+/// Set it with [`FormatOptions::geo_resolver`](crate::FormatOptions::geo_resolver),
+/// and the events which have `client.ip`, `source.ip`, `server.ip`, or `destination.ip`
+/// get the corresponding `client.geo.*`, `source.geo.*`, `server.geo.*`, or `destination.geo.*` fields.
+///
+/// The lookup runs where the events are encoded, so it runs on the background thread with
+/// [`Builder::deferred_formatting`](crate::Builder::deferred_formatting) instead of delaying the logging thread.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{FormatOptions, GeoLocation, GeoResolver};
+/// use std::net::IpAddr;
+///
+/// struct OfficeNetwork;
+///
+/// impl GeoResolver for OfficeNetwork {
+///     fn resolve(&self, ip: IpAddr) -> Option<GeoLocation> {
+///         ip.is_loopback().then(|| GeoLocation::new().country_iso_code("JP").city_name("Tokyo"))
+///     }
+/// }
+///
+/// let options = FormatOptions::new().geo_resolver(OfficeNetwork);
+/// ```
+pub trait GeoResolver: Send + Sync {
+    /// Returns the location of `ip`, or `None` if it is unknown.
+    fn resolve(&self, ip: IpAddr) -> Option<GeoLocation>;
+}
+
+/// Geographic location of an IP address, added as the [`geo`](https://www.elastic.co/guide/en/ecs/current/ecs-geo.html) fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeoLocation {
+    continent_name: Option<String>,
+    country_iso_code: Option<String>,
+    country_name: Option<String>,
+    region_name: Option<String>,
+    city_name: Option<String>,
+    location: Option<(f64, f64)>,
+}
+
+impl GeoLocation {
+    /// Creates an empty location.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `geo.continent_name`, e.g. `Europe`.
+    pub fn continent_name(mut self, continent_name: impl Into<String>) -> Self {
+        self.continent_name = Some(continent_name.into());
+        self
+    }
+
+    /// Sets `geo.country_iso_code`, e.g. `CA`.
+    pub fn country_iso_code(mut self, country_iso_code: impl Into<String>) -> Self {
+        self.country_iso_code = Some(country_iso_code.into());
+        self
+    }
+
+    /// Sets `geo.country_name`, e.g. `Canada`.
+    pub fn country_name(mut self, country_name: impl Into<String>) -> Self {
+        self.country_name = Some(country_name.into());
+        self
+    }
+
+    /// Sets `geo.region_name`, e.g. `Quebec`.
+    pub fn region_name(mut self, region_name: impl Into<String>) -> Self {
+        self.region_name = Some(region_name.into());
+        self
+    }
+
+    /// Sets `geo.city_name`, e.g. `Montreal`.
+    pub fn city_name(mut self, city_name: impl Into<String>) -> Self {
+        self.city_name = Some(city_name.into());
+        self
+    }
+
+    /// Sets `geo.location`, the latitude and the longitude.
+    pub fn location(mut self, lat: f64, lon: f64) -> Self {
+        self.location = Some((lat, lon));
+        self
+    }
+
+    /// Returns the fields under `<prefix>.geo` with dotted keys.
+    fn to_fields(&self, prefix: &str) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let strings = [
+            ("continent_name", &self.continent_name),
+            ("country_iso_code", &self.country_iso_code),
+            ("country_name", &self.country_name),
+            ("region_name", &self.region_name),
+            ("city_name", &self.city_name),
+        ];
+        for (name, value) in strings {
+            if let Some(value) = value {
+                fields.insert(format!("{}.geo.{}", prefix, name), value.as_str().into());
+            }
+        }
+        if let Some((lat, lon)) = self.location {
+            fields.insert(
+                format!("{}.geo.location", prefix),
+                serde_json::json!({ "lat": lat, "lon": lon }),
+            );
+        }
+        fields
+    }
+}
+
+/// [`GeoResolver`] shared by the clones of the options.
+#[derive(Clone)]
+pub(crate) struct SharedGeoResolver(pub(crate) Arc<dyn GeoResolver>);
+
+impl fmt::Debug for SharedGeoResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedGeoResolver").finish_non_exhaustive()
+    }
+}
+
+/// The fields which have the IP addresses to resolve
+const PREFIXES: [&str; 4] = ["client", "source", "server", "destination"];
+
+/// Adds the `<prefix>.geo.*` fields of the IP addresses in `event`, unless `<prefix>.geo` is set already.
+pub(crate) fn add_geo_fields(event: &mut Map<String, Value>, resolver: &dyn GeoResolver) {
+    for prefix in PREFIXES {
+        let Some(ip) = get(event, prefix, "ip")
+            .and_then(Value::as_str)
+            .and_then(|ip| ip.parse().ok())
+        else {
+            continue;
+        };
+        let has_geo = get(event, prefix, "geo").is_some()
+            || event
+                .keys()
+                .any(|key| key.starts_with(&format!("{}.geo.", prefix)));
+        if has_geo {
+            continue;
+        }
+
+        if let Some(location) = resolver.resolve(ip) {
+            event.extend(location.to_fields(prefix));
+        }
+    }
+}
+
+/// Returns `<prefix>.<name>` of `event`, whether the key is dotted or nested
+fn get<'a>(event: &'a Map<String, Value>, prefix: &str, name: &str) -> Option<&'a Value> {
+    event
+        .get(&format!("{}.{}", prefix, name))
+        .or_else(|| event.get(prefix)?.get(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Resolver;
+
+    impl GeoResolver for Resolver {
+        fn resolve(&self, ip: IpAddr) -> Option<GeoLocation> {
+            (ip == IpAddr::from([192, 0, 2, 1])).then(|| {
+                GeoLocation::new()
+                    .country_iso_code("CA")
+                    .city_name("Montreal")
+                    .location(45.5, -73.6)
+            })
+        }
+    }
+
+    #[test]
+    fn test_add_geo_fields() {
+        let mut event = json!({
+            "client.ip": "192.0.2.1",
+            "source": { "ip": "192.0.2.1" },
+            "server.ip": "192.0.2.1",
+            "server.geo.country_iso_code": "US",
+            "destination.ip": "198.51.100.7",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        add_geo_fields(&mut event, &Resolver);
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "client.ip": "192.0.2.1",
+                "client.geo.country_iso_code": "CA",
+                "client.geo.city_name": "Montreal",
+                "client.geo.location": { "lat": 45.5, "lon": -73.6 },
+                "source": { "ip": "192.0.2.1" },
+                "source.geo.country_iso_code": "CA",
+                "source.geo.city_name": "Montreal",
+                "source.geo.location": { "lat": 45.5, "lon": -73.6 },
+                "server.ip": "192.0.2.1",
+                "server.geo.country_iso_code": "US",
+                "destination.ip": "198.51.100.7",
+            })
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Metadata of the host added to the `host.*` fields
+
+use crate::ecs::{Host, Os};
+use std::borrow::Cow;
+use std::env::consts;
+use std::sync::OnceLock;
+
+static HOST: OnceLock<Host<'static>> = OnceLock::new();
+
+/// Returns the metadata of the host, collecting it on the first call.
+pub(crate) fn host() -> &'static Host<'static> {
+    HOST.get_or_init(collect)
+}
+
+fn collect() -> Host<'static> {
+    let hostname = gethostname::gethostname()
+        .into_string()
+        .unwrap_or_else(|hostname| hostname.to_string_lossy().into_owned());
+    let hostname = (!hostname.is_empty()).then_some(Cow::Owned(hostname));
+
+    Host {
+        name: hostname.clone(),
+        hostname,
+        os: Some(os()),
+        architecture: Some(Cow::Borrowed(consts::ARCH)),
+    }
+}
+
+/// Detects the operating system, reading `/etc/os-release` on Linux
+fn os() -> Os<'static> {
+    if consts::OS == "linux" {
+        if let Ok(os_release) = std::fs::read_to_string("/etc/os-release") {
+            return parse_os_release(&os_release);
+        }
+    }
+
+    let (family, name) = match consts::OS {
+        "macos" => ("macos", "macOS"),
+        "windows" => ("windows", "Windows"),
+        os => (consts::FAMILY, os),
+    };
+    Os {
+        family: Some(Cow::Borrowed(family)),
+        name: Some(Cow::Borrowed(name)),
+        version: None,
+    }
+}
+
+/// Parses the contents of [`os-release`](https://www.freedesktop.org/software/systemd/man/latest/os-release.html)
+fn parse_os_release(os_release: &str) -> Os<'static> {
+    let mut id = None;
+    let mut id_like = None;
+    let mut os = Os::default();
+
+    for line in os_release.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        if value.is_empty() {
+            continue;
+        }
+
+        match key.trim() {
+            "ID" => id = Some(value.to_string()),
+            "ID_LIKE" => id_like = value.split_whitespace().next().map(str::to_string),
+            "NAME" => os.name = Some(Cow::Owned(value.to_string())),
+            "VERSION_ID" => os.version = Some(Cow::Owned(value.to_string())),
+            _ => {}
+        }
+    }
+
+    // e.g. `debian` for Ubuntu, so that derivatives are grouped together
+    os.family = id_like.or(id).map(Cow::Owned);
+    os
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host() {
+        let host = host();
+        assert!(host.name.is_some());
+        assert_eq!(host.name, host.hostname);
+        assert_eq!(host.architecture.as_deref(), Some(consts::ARCH));
+        assert!(host.os.is_some());
+        assert!(std::ptr::eq(host, super::host()));
+    }
+
+    #[test]
+    fn test_parse_os_release() {
+        let os = parse_os_release(
+            r#"PRETTY_NAME="Ubuntu 22.04.4 LTS"
+NAME="Ubuntu"
+VERSION_ID="22.04"
+ID=ubuntu
+ID_LIKE=debian
+"#,
+        );
+        assert_eq!(os.family.as_deref(), Some("debian"));
+        assert_eq!(os.name.as_deref(), Some("Ubuntu"));
+        assert_eq!(os.version.as_deref(), Some("22.04"));
+
+        let os = parse_os_release("NAME='Alpine Linux'\nID=alpine\n# comment\n");
+        assert_eq!(os.family.as_deref(), Some("alpine"));
+        assert_eq!(os.name.as_deref(), Some("Alpine Linux"));
+        assert_eq!(os.version, None);
+    }
+}
@@ -0,0 +1,142 @@
+//! `http.*` fields of the request being handled
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// HTTP request which the log records belong to.
+///
+/// It adds `http.request.method`, `url.path`, `http.version`, `http.response.status_code`, and `user_agent.original` to the records,
+/// so all logs of a request can be filtered by the request.
+/// Web frameworks can enter it in a middleware at the start of each request,
+/// and enter [`HttpContext::response`] once the status code is known. See [`ToEcsFields`](crate::ToEcsFields) for the other ways to attach it.
+///
+/// Use [`AccessLog`](crate::AccessLog) to log the summary of a request instead.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::HttpContext;
+///
+/// ecs_logger::init();
+///
+/// let _request = HttpContext::request("GET", "/api/users")
+///     .version("1.1")
+///     .user_agent("curl/8.0.1")
+///     .enter();
+/// log::info!("Loading users");
+/// // {"log.level":"INFO","message":"Loading users","http.request.method":"GET","url.path":"/api/users","http.version":"1.1",...}
+///
+/// let _response = HttpContext::response(503).enter();
+/// log::warn!("Database is unavailable");
+/// // {"log.level":"WARN","message":"Database is unavailable",...,"http.response.status_code":503,...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpContext {
+    method: Option<String>,
+    path: Option<String>,
+    version: Option<String>,
+    status_code: Option<u16>,
+    user_agent: Option<String>,
+}
+
+impl HttpContext {
+    /// Creates a context of a request with `method` (e.g. `GET`) and `path` (e.g. `/api/users`).
+    pub fn request(method: impl Into<String>, path: impl Into<String>) -> Self {
+        HttpContext {
+            method: Some(method.into()),
+            path: Some(path.into()),
+            ..HttpContext::default()
+        }
+    }
+
+    /// Creates a context of a response with `status_code`, to be entered within the context of the request.
+    pub fn response(status_code: u16) -> Self {
+        HttpContext::default().status_code(status_code)
+    }
+
+    /// Sets `http.version`, e.g. `1.1` or `2`.
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Sets `http.response.status_code`.
+    pub fn status_code(mut self, status_code: u16) -> Self {
+        self.status_code = Some(status_code);
+        self
+    }
+
+    /// Sets the `User-Agent` header of the request, logged as `user_agent.original`.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+}
+
+impl Serialize for HttpContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(method) = &self.method {
+            map.serialize_entry("http.request.method", method)?;
+        }
+        if let Some(path) = &self.path {
+            map.serialize_entry("url.path", path)?;
+        }
+        if let Some(version) = &self.version {
+            map.serialize_entry("http.version", version)?;
+        }
+        if let Some(status_code) = self.status_code {
+            map.serialize_entry("http.response.status_code", &status_code)?;
+        }
+        if let Some(user_agent) = &self.user_agent {
+            map.serialize_entry("user_agent.original", user_agent)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(HttpContext::request("GET", "/").version("2")).unwrap(),
+            json!({ "http.request.method": "GET", "url.path": "/", "http.version": "2" })
+        );
+        assert_eq!(
+            serde_json::to_value(HttpContext::request("GET", "/").user_agent("curl/8.0.1"))
+                .unwrap(),
+            json!({ "http.request.method": "GET", "url.path": "/", "user_agent.original": "curl/8.0.1" })
+        );
+        assert_eq!(
+            serde_json::to_value(HttpContext::response(404)).unwrap(),
+            json!({ "http.response.status_code": 404 })
+        );
+    }
+
+    #[test]
+    fn test_enter() {
+        let request = HttpContext::request("POST", "/login").enter();
+        let response = HttpContext::response(401).enter();
+        assert_eq!(
+            Value::Object(scoped_fields()),
+            json!({
+                "http.request.method": "POST",
+                "url.path": "/login",
+                "http.response.status_code": 401,
+            })
+        );
+
+        response.pop();
+        assert_eq!(
+            HttpContext::response(200).in_scope(scoped_fields)["http.response.status_code"],
+            200
+        );
+
+        request.pop();
+        assert!(scoped_fields().is_empty());
+    }
+}
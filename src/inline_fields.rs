@@ -0,0 +1,183 @@
+//! Inline fields of the records logged with [`ecs_log!`](crate::ecs_log!) and the macros of each level
+
+use crate::extra_fields::with_scoped_fields;
+use serde_json::{Map, Value};
+
+/// Converts the value of an inline field into JSON. The values which fail to serialize are logged as `null`.
+pub fn field_value(value: &impl serde::Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Runs `f` with the inline `fields` added to the log records emitted by the current thread.
+pub fn with_fields<R, const N: usize>(
+    fields: [(&'static str, Value); N],
+    f: impl FnOnce() -> R,
+) -> R {
+    let fields = fields
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect::<Map<_, _>>();
+    with_scoped_fields(fields, f)
+}
+
+/// Logs a message with inline fields, its template as `message_template`, and the enclosing function as `log.origin.function`.
+///
+/// The fields are `key = value` pairs separated by commas and followed by a semicolon, like the key-values of [`log::log!`],
+/// but they do not need the `kv` feature. The keys may be dotted, e.g. `http.request.method`, and the values may be of any type
+/// which implements [`serde::Serialize`]. The fields take precedence over the extra fields and the scoped fields.
+///
+/// An error may be given first as `error: <expr>`, which adds `error.message`, `error.type`, and the chain of its
+/// [`source`](std::error::Error::source)s as `error.stack_trace`, followed by the backtrace of the current thread if backtraces are enabled
+/// with `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`. It accepts any type which implements [`std::error::Error`], `Box<dyn Error>`, `anyhow::Error` with the `anyhow` feature, and `eyre::Report` with the `eyre` feature.
+///
+/// A value of a type which implements [`ToEcsFields`](crate::ToEcsFields) may be given as `fields: <expr>`, after the error if any,
+/// and its fields are added too. The inline fields take precedence over them.
+///
+/// The message must be a string literal, followed by the format arguments.
+/// [`ecs_error!`](crate::ecs_error), [`ecs_warn!`](crate::ecs_warn), [`ecs_info!`](crate::ecs_info),
+/// [`ecs_debug!`](crate::ecs_debug), and [`ecs_trace!`](crate::ecs_trace) log at the levels of their names.
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// fn login(name: &str) {
+///     ecs_logger::ecs_log!(Level::Info, user.name = name, event.action = "login"; "user {} logged in", name);
+///     // {"log.level":"INFO","message":"user alice logged in","message_template":"user {} logged in",
+///     //  "log.origin":{"function":"login",...},"user.name":"alice","event.action":"login",...}
+///
+///     ecs_logger::ecs_info!("Login finished");
+/// }
+///
+/// login("alice");
+/// ```
+#[macro_export]
+macro_rules! ecs_log {
+    (target: $target:expr, $lvl:expr, error: $err:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(@error target: $target, $lvl, $err, $($arg)+)
+    };
+    (target: $target:expr, $lvl:expr, error: $err:expr; $($arg:tt)+) => {
+        $crate::ecs_log!(@error target: $target, $lvl, $err, $($arg)+)
+    };
+    (@error target: $target:expr, $lvl:expr, $err:expr, $($arg:tt)+) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            use $crate::__private::{AsEcsError as _, AsEcsErrorReport as _};
+            $crate::__private::with_error((&$err).as_ecs_error(), || {
+                $crate::ecs_log!(target: $target, lvl, $($arg)+)
+            });
+        }
+    }};
+    (target: $target:expr, $lvl:expr, fields: $fields:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(@fields target: $target, $lvl, $fields, $($arg)+)
+    };
+    (target: $target:expr, $lvl:expr, fields: $fields:expr; $($arg:tt)+) => {
+        $crate::ecs_log!(@fields target: $target, $lvl, $fields, $($arg)+)
+    };
+    (@fields target: $target:expr, $lvl:expr, $fields:expr, $($arg:tt)+) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            $crate::with_fields(&$fields, || {
+                $crate::ecs_log!(target: $target, lvl, $($arg)+)
+            });
+        }
+    }};
+    (target: $target:expr, $lvl:expr, $($first:ident $(. $rest:ident)* = $value:expr),+ ; $fmt:literal $($arg:tt)*) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            $crate::__private::with_fields(
+                [$((
+                    ::core::concat!(::core::stringify!($first) $(, ".", ::core::stringify!($rest))*),
+                    $crate::__private::field_value(&$value),
+                )),+],
+                || $crate::ecs_log!(@log target: $target, lvl, $fmt $($arg)*),
+            );
+        }
+    }};
+    (target: $target:expr, $lvl:expr, $fmt:literal $($arg:tt)*) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            $crate::ecs_log!(@log target: $target, lvl, $fmt $($arg)*);
+        }
+    }};
+    (@log target: $target:expr, $lvl:expr, $fmt:literal $($arg:tt)*) => {
+        $crate::__private::with_function($crate::function_name!(), || {
+            $crate::__private::with_template($fmt, || {
+                $crate::__private::log::log!(target: $target, $lvl, $fmt $($arg)*);
+            });
+        })
+    };
+    ($lvl:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: ::core::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+/// Logs a message at the error level like [`ecs_log!`](crate::ecs_log!).
+///
+/// # Example
+///
+/// ```
+/// fn load_config() -> Result<String, std::io::Error> {
+///     std::fs::read_to_string("/nonexistent/config.toml")
+/// }
+///
+/// if let Err(err) = load_config() {
+///     ecs_logger::ecs_error!(error: err, file.path = "/nonexistent/config.toml"; "Failed to load the config");
+///     // {"log.level":"ERROR","message":"Failed to load the config","error.message":"No such file or directory (os error 2)",
+///     //  "error.type":"std::io::error::Error","file.path":"/nonexistent/config.toml",...}
+/// }
+/// ```
+#[macro_export]
+macro_rules! ecs_error {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: $target, $crate::__private::log::Level::Error, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::ecs_log!($crate::__private::log::Level::Error, $($arg)+)
+    };
+}
+
+/// Logs a message at the warn level like [`ecs_log!`](crate::ecs_log!).
+#[macro_export]
+macro_rules! ecs_warn {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: $target, $crate::__private::log::Level::Warn, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::ecs_log!($crate::__private::log::Level::Warn, $($arg)+)
+    };
+}
+
+/// Logs a message at the info level like [`ecs_log!`](crate::ecs_log!).
+#[macro_export]
+macro_rules! ecs_info {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: $target, $crate::__private::log::Level::Info, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::ecs_log!($crate::__private::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Logs a message at the debug level like [`ecs_log!`](crate::ecs_log!).
+#[macro_export]
+macro_rules! ecs_debug {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: $target, $crate::__private::log::Level::Debug, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::ecs_log!($crate::__private::log::Level::Debug, $($arg)+)
+    };
+}
+
+/// Logs a message at the trace level like [`ecs_log!`](crate::ecs_log!).
+#[macro_export]
+macro_rules! ecs_trace {
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::ecs_log!(target: $target, $crate::__private::log::Level::Trace, $($arg)+)
+    };
+    ($($arg:tt)+) => {
+        $crate::ecs_log!($crate::__private::log::Level::Trace, $($arg)+)
+    };
+}
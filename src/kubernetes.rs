@@ -0,0 +1,200 @@
+//! `orchestrator.*` and `kubernetes.*` fields of the pod in which the process runs
+
+use crate::extra_fields::to_json_map;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+
+/// Kubernetes pod in which the process runs.
+///
+/// It adds the ECS `orchestrator.*` fields, and the `kubernetes.*` fields used by Elastic's Kubernetes integrations, to every record:
+///
+/// | Field | Source |
+/// |---|---|
+/// | `orchestrator.type` | always `kubernetes` |
+/// | `orchestrator.cluster.name` | [`cluster_name`](Self::cluster_name) |
+/// | `orchestrator.namespace`, `kubernetes.namespace` | [`namespace`](Self::namespace) |
+/// | `orchestrator.resource.type` | `pod` if the pod name is set |
+/// | `orchestrator.resource.name`, `kubernetes.pod.name` | [`pod_name`](Self::pod_name) |
+/// | `kubernetes.pod.uid` | [`pod_uid`](Self::pod_uid) |
+/// | `kubernetes.node.name` | [`node_name`](Self::node_name) |
+///
+/// [`from_env`](Self::from_env) reads them from the environment variables injected with the
+/// [Downward API](https://kubernetes.io/docs/concepts/workloads/pods/downward-api/):
+///
+/// ```yaml
+/// env:
+///   - name: POD_NAME
+///     valueFrom: { fieldRef: { fieldPath: metadata.name } }
+///   - name: POD_NAMESPACE
+///     valueFrom: { fieldRef: { fieldPath: metadata.namespace } }
+///   - name: POD_UID
+///     valueFrom: { fieldRef: { fieldPath: metadata.uid } }
+///   - name: NODE_NAME
+///     valueFrom: { fieldRef: { fieldPath: spec.nodeName } }
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{FormatOptions, KubernetesMetadata};
+///
+/// let options = FormatOptions::new()
+///     .kubernetes(KubernetesMetadata::from_env().cluster_name("production"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KubernetesMetadata {
+    cluster_name: Option<String>,
+    namespace: Option<String>,
+    pod_name: Option<String>,
+    pod_uid: Option<String>,
+    node_name: Option<String>,
+}
+
+impl KubernetesMetadata {
+    /// Creates empty metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the metadata from `POD_NAME`, `POD_NAMESPACE`, `POD_UID`, `NODE_NAME`, and `CLUSTER_NAME` environment variables.
+    ///
+    /// The variables which are unset or empty are skipped.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        KubernetesMetadata {
+            cluster_name: var("CLUSTER_NAME"),
+            namespace: var("POD_NAMESPACE"),
+            pod_name: var("POD_NAME"),
+            pod_uid: var("POD_UID"),
+            node_name: var("NODE_NAME"),
+        }
+    }
+
+    /// Sets the name of the cluster, logged as `orchestrator.cluster.name`.
+    pub fn cluster_name(mut self, cluster_name: impl Into<String>) -> Self {
+        self.cluster_name = Some(cluster_name.into());
+        self
+    }
+
+    /// Sets the namespace of the pod, logged as `orchestrator.namespace` and `kubernetes.namespace`.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Sets the name of the pod, logged as `orchestrator.resource.name` and `kubernetes.pod.name`.
+    pub fn pod_name(mut self, pod_name: impl Into<String>) -> Self {
+        self.pod_name = Some(pod_name.into());
+        self
+    }
+
+    /// Sets the uid of the pod, logged as `kubernetes.pod.uid`.
+    pub fn pod_uid(mut self, pod_uid: impl Into<String>) -> Self {
+        self.pod_uid = Some(pod_uid.into());
+        self
+    }
+
+    /// Sets the name of the node on which the pod is scheduled, logged as `kubernetes.node.name`.
+    pub fn node_name(mut self, node_name: impl Into<String>) -> Self {
+        self.node_name = Some(node_name.into());
+        self
+    }
+
+    /// Returns `true` if none of the metadata is set, e.g. when running outside of Kubernetes.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the fields with dotted keys.
+    pub(crate) fn to_fields(&self) -> Map<String, Value> {
+        to_json_map(self).expect("KubernetesMetadata should be converted into a JSON object")
+    }
+}
+
+impl Serialize for KubernetesMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if self.is_empty() {
+            return map.end();
+        }
+
+        map.serialize_entry("orchestrator.type", "kubernetes")?;
+        if let Some(cluster_name) = &self.cluster_name {
+            map.serialize_entry("orchestrator.cluster.name", cluster_name)?;
+        }
+        if let Some(namespace) = &self.namespace {
+            map.serialize_entry("orchestrator.namespace", namespace)?;
+        }
+        if let Some(pod_name) = &self.pod_name {
+            map.serialize_entry("orchestrator.resource.type", "pod")?;
+            map.serialize_entry("orchestrator.resource.name", pod_name)?;
+        }
+
+        let strings = [
+            ("kubernetes.namespace", &self.namespace),
+            ("kubernetes.pod.name", &self.pod_name),
+            ("kubernetes.pod.uid", &self.pod_uid),
+            ("kubernetes.node.name", &self.node_name),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_vars() {
+        let vars = [
+            ("POD_NAME", "checkout-7d9f8b6c5-x2k4q"),
+            ("POD_NAMESPACE", "shop"),
+            ("POD_UID", "0b5f8a1e-2c3d-4e5f-8a9b-0c1d2e3f4a5b"),
+            ("NODE_NAME", "ip-10-0-1-23.ec2.internal"),
+            ("CLUSTER_NAME", ""),
+        ];
+        let metadata = KubernetesMetadata::from_vars(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        });
+
+        assert_eq!(
+            Value::Object(metadata.to_fields()),
+            json!({
+                "orchestrator.type": "kubernetes",
+                "orchestrator.namespace": "shop",
+                "orchestrator.resource.type": "pod",
+                "orchestrator.resource.name": "checkout-7d9f8b6c5-x2k4q",
+                "kubernetes.namespace": "shop",
+                "kubernetes.pod.name": "checkout-7d9f8b6c5-x2k4q",
+                "kubernetes.pod.uid": "0b5f8a1e-2c3d-4e5f-8a9b-0c1d2e3f4a5b",
+                "kubernetes.node.name": "ip-10-0-1-23.ec2.internal",
+            })
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let metadata = KubernetesMetadata::from_vars(|_| None);
+        assert!(metadata.is_empty());
+        assert!(metadata.to_fields().is_empty());
+
+        let metadata = KubernetesMetadata::new().cluster_name("production");
+        assert!(!metadata.is_empty());
+        assert_eq!(
+            Value::Object(metadata.to_fields()),
+            json!({ "orchestrator.type": "kubernetes", "orchestrator.cluster.name": "production" })
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Structured key-values of the records added as top-level fields
+
+use crate::KeyStyle;
+use log::kv::{self, Key, Source, VisitSource, VisitValue};
+use log::Record;
+use serde_json::{Map, Number, Value};
+
+/// Returns the key-values of `record`, e.g. `user_id = 42` of `info!(user_id = 42; "logged in")`, as JSON fields.
+///
+/// Numbers, booleans, strings, and `None` keep their JSON types. The other values are rendered as strings,
+/// except for those captured with `:serde`, which are serialized if `kv_serde` is enabled.
+/// If `nested` is `true`, the dotted keys are expanded into nested objects.
+pub(crate) fn key_values(record: &Record, nested: bool) -> Map<String, Value> {
+    struct Visitor(Map<String, Value>);
+
+    impl<'kvs> VisitSource<'kvs> for Visitor {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+            self.0.insert(key.to_string(), to_json(&value));
+            Ok(())
+        }
+    }
+
+    let mut visitor = Visitor(Map::new());
+    // The visitor never fails
+    let _ = record.key_values().visit(&mut visitor);
+    if nested {
+        KeyStyle::Nested.apply(visitor.0)
+    } else {
+        visitor.0
+    }
+}
+
+/// Key-values captured with [`key_values`], which can be attached to a rebuilt [`Record`].
+///
+/// They are visited as the same JSON values, so the rebuilt record is rendered like the original one.
+pub(crate) struct JsonKeyValues<'a>(pub(crate) &'a [(String, Value)]);
+
+impl Source for JsonKeyValues<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in self.0 {
+            visitor.visit_pair(Key::from_str(key), from_json(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts `value` back into a key-value, which [`to_json`] converts into the same JSON
+fn from_json(value: &Value) -> kv::Value<'_> {
+    match value {
+        Value::Null => kv::Value::null(),
+        Value::Bool(value) => (*value).into(),
+        Value::Number(number) => match (number.as_u64(), number.as_i64(), number.as_f64()) {
+            (Some(value), _, _) => value.into(),
+            (_, Some(value), _) => value.into(),
+            (_, _, Some(value)) => value.into(),
+            _ => kv::Value::from_display(number),
+        },
+        Value::String(value) => value.as_str().into(),
+        // Arrays and objects are only captured from the values serialized with `:serde`
+        #[cfg(feature = "kv_serde")]
+        _ => kv::Value::from_serde(value),
+        #[cfg(not(feature = "kv_serde"))]
+        _ => kv::Value::from_display(value),
+    }
+}
+
+/// Converts `value` into JSON, keeping the structure of the values captured with `:serde` if `kv_serde` is enabled
+fn to_json(value: &kv::Value) -> Value {
+    struct Visitor(Value);
+
+    impl<'v> VisitValue<'v> for Visitor {
+        fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
+            #[cfg(feature = "kv_serde")]
+            if let Ok(value) = serde_json::to_value(&value) {
+                self.0 = value;
+                return Ok(());
+            }
+            self.0 = Value::String(value.to_string());
+            Ok(())
+        }
+
+        fn visit_null(&mut self) -> Result<(), kv::Error> {
+            self.0 = Value::Null;
+            Ok(())
+        }
+
+        fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+            self.0 = value.into();
+            Ok(())
+        }
+
+        fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+            self.0 = value.into();
+            Ok(())
+        }
+
+        fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
+            self.0 = u64::try_from(value).map_or_else(|_| value.to_string().into(), Value::from);
+            Ok(())
+        }
+
+        fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
+            self.0 = i64::try_from(value).map_or_else(|_| value.to_string().into(), Value::from);
+            Ok(())
+        }
+
+        fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+            // NaN and infinities are not valid in JSON
+            self.0 =
+                Number::from_f64(value).map_or_else(|| value.to_string().into(), Value::Number);
+            Ok(())
+        }
+
+        fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+            self.0 = value.into();
+            Ok(())
+        }
+
+        fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+            self.0 = value.into();
+            Ok(())
+        }
+    }
+
+    let mut visitor = Visitor(Value::Null);
+    let _ = value.visit(&mut visitor);
+    visitor.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_values() {
+        let kvs: &[(&str, kv::Value)] = &[
+            ("user_id", 42.into()),
+            ("delta", (-1i64).into()),
+            ("ratio", 0.5.into()),
+            ("nan", f64::NAN.into()),
+            ("admin", true.into()),
+            ("name", "alice".into()),
+            ("initial", 'a'.into()),
+            ("missing", kv::Value::null()),
+            ("small", 1u128.into()),
+            ("big", u128::MAX.into()),
+            ("path", kv::Value::from_debug(&["a", "b"])),
+        ];
+        let record = Record::builder()
+            .args(format_args!("logged in"))
+            .key_values(&kvs)
+            .build();
+
+        assert_eq!(
+            Value::Object(key_values(&record, false)),
+            json!({
+                "user_id": 42,
+                "delta": -1,
+                "ratio": 0.5,
+                "nan": "NaN",
+                "admin": true,
+                "name": "alice",
+                "initial": "a",
+                "missing": null,
+                "small": 1,
+                "big": "340282366920938463463374607431768211455",
+                "path": "[\"a\", \"b\"]",
+            })
+        );
+    }
+
+    #[cfg(feature = "kv_serde")]
+    #[test]
+    fn test_key_values_serde() {
+        #[derive(serde::Serialize)]
+        struct Request {
+            method: &'static str,
+            bytes: u64,
+        }
+
+        let tags = vec!["a", "b"];
+        let request = Request {
+            method: "GET",
+            bytes: 512,
+        };
+        let kvs: &[(&str, kv::Value)] = &[
+            ("tags", kv::Value::from_serde(&tags)),
+            ("request", kv::Value::from_serde(&request)),
+            ("count", kv::Value::from_serde(&3u8)),
+            ("path", kv::Value::from_debug(&["a", "b"])),
+        ];
+        let record = Record::builder().key_values(&kvs).build();
+
+        assert_eq!(
+            Value::Object(key_values(&record, false)),
+            json!({
+                "tags": ["a", "b"],
+                "request": { "method": "GET", "bytes": 512 },
+                "count": 3,
+                "path": "[\"a\", \"b\"]",
+            })
+        );
+    }
+
+    #[test]
+    fn test_key_values_nested() {
+        let kvs: &[(&str, kv::Value)] = &[
+            ("http.request.method", "GET".into()),
+            ("http.response.status_code", 200.into()),
+            ("user_id", 42.into()),
+        ];
+        let record = Record::builder().key_values(&kvs).build();
+
+        assert_eq!(
+            Value::Object(key_values(&record, true)),
+            json!({
+                "http": { "request": { "method": "GET" }, "response": { "status_code": 200 } },
+                "user_id": 42,
+            })
+        );
+        assert_eq!(
+            key_values(&record, false)["http.request.method"],
+            json!("GET")
+        );
+    }
+}
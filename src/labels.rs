@@ -0,0 +1,232 @@
+//! `labels` field
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Flat map of string key/values added as the [`labels`](https://www.elastic.co/guide/en/ecs/current/ecs-base.html#field-labels) field.
+///
+/// ECS defines `labels` as keyword values, so numbers and booleans are converted into strings,
+/// and dots in the keys are replaced with underscores.
+/// The `labels` set by other means, e.g. with extra fields, are coerced in the same way when the event is formatted,
+/// and the values which are neither strings, numbers, nor booleans are dropped.
+///
+/// Set them for every record with [`FormatOptions::labels`](crate::FormatOptions::labels),
+/// or for the records of a block with [`in_scope`](Self::in_scope), [`enter`](Self::enter), or the other ways of [`ToEcsFields`](crate::ToEcsFields).
+/// The labels of a block are merged with those of the outer blocks and of the options.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::Labels;
+///
+/// ecs_logger::init();
+///
+/// Labels::new()
+///     .label("tenant", "acme")
+///     .label("shard", 3)
+///     .in_scope(|| log::info!("Rebalanced"));
+/// // {"log.level":"INFO","message":"Rebalanced","labels":{"tenant":"acme","shard":"3"},...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels(Map<String, Value>);
+
+/// Error returned by [`Labels::try_from_fields`].
+#[derive(Error, Debug)]
+pub enum LabelsError {
+    /// Failed to convert the fields into JSON.
+    #[error("failed to convert labels into JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+
+    /// The fields were not converted into a JSON object.
+    #[error("labels must be converted into a JSON object")]
+    NotObject,
+
+    /// The value of the key is neither a string, a number, nor a boolean.
+    #[error("label `{0}` must be a string, a number, or a boolean")]
+    NotScalar(String),
+}
+
+impl Labels {
+    /// Creates an empty set of labels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates labels from `fields`, which must be serialized into a flat JSON object.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LabelsError`] if `fields` cannot be converted into a JSON object,
+    /// or one of its values is neither a string, a number, nor a boolean.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{Labels, LabelsError};
+    /// use serde_json::json;
+    ///
+    /// assert!(Labels::try_from_fields(json!({ "tenant": "acme", "shard": 3 })).is_ok());
+    /// assert!(matches!(
+    ///     Labels::try_from_fields(json!({ "owner": { "team": "payments" } })),
+    ///     Err(LabelsError::NotScalar(_))
+    /// ));
+    /// ```
+    pub fn try_from_fields(fields: impl Serialize) -> Result<Self, LabelsError> {
+        let Value::Object(fields) = serde_json::to_value(fields)? else {
+            return Err(LabelsError::NotObject);
+        };
+
+        fields
+            .into_iter()
+            .try_fold(Labels::new(), |labels, (key, value)| match coerce(&value) {
+                Some(value) => Ok(labels.label(key, value)),
+                None => Err(LabelsError::NotScalar(key)),
+            })
+    }
+
+    /// Adds a label. `value` is converted into a string.
+    pub fn label(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.0
+            .insert(sanitize_key(key.into()), value.to_string().into());
+        self
+    }
+
+    /// Returns `true` if there are no labels.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the `labels` field as a JSON object.
+    pub(crate) fn to_fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        fields.insert("labels".to_string(), Value::Object(self.0.clone()));
+        fields
+    }
+}
+
+impl Serialize for Labels {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("labels", &self.0)?;
+        map.end()
+    }
+}
+
+/// Coerces the `labels` field of `event` and its `labels.*` fields into string values.
+pub(crate) fn coerce_labels(event: &mut Map<String, Value>) {
+    match event.get_mut("labels") {
+        Some(Value::Object(labels)) => {
+            *labels = std::mem::take(labels)
+                .into_iter()
+                .filter_map(|(key, value)| Some((sanitize_key(key), coerce(&value)?.into())))
+                .collect();
+        }
+        Some(_) => {
+            event.remove("labels");
+        }
+        None => {}
+    }
+
+    event.retain(|key, value| {
+        if !key.starts_with("labels.") {
+            return true;
+        }
+        match coerce(value) {
+            Some(coerced) => {
+                *value = coerced.into();
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+/// Converts a scalar into a string, or returns `None` for null, arrays, and objects
+fn coerce(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// Replaces dots, which ECS forbids in label keys, with underscores
+fn sanitize_key(key: String) -> String {
+    if key.contains('.') {
+        key.replace('.', "_")
+    } else {
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::json;
+
+    #[test]
+    fn test_label() {
+        let labels = Labels::new()
+            .label("tenant", "acme")
+            .label("shard", 3)
+            .label("canary", true)
+            .label("app.tier", "web");
+        assert_eq!(
+            serde_json::to_value(&labels).unwrap(),
+            json!({ "labels": { "tenant": "acme", "shard": "3", "canary": "true", "app_tier": "web" } })
+        );
+
+        let fields = labels.in_scope(scoped_fields);
+        assert_eq!(
+            Value::Object(fields),
+            serde_json::to_value(&labels).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_try_from_fields() {
+        assert_eq!(
+            Labels::try_from_fields(json!({ "tenant": "acme", "ratio": 0.5 })).unwrap(),
+            Labels::new().label("tenant", "acme").label("ratio", "0.5")
+        );
+        assert!(matches!(
+            Labels::try_from_fields(json!(["acme"])),
+            Err(LabelsError::NotObject)
+        ));
+        assert!(matches!(
+            Labels::try_from_fields(json!({ "owners": ["a", "b"] })),
+            Err(LabelsError::NotScalar(key)) if key == "owners"
+        ));
+    }
+
+    #[test]
+    fn test_coerce_labels() {
+        let mut event = json!({
+            "labels": { "shard": 3, "canary": false, "owner": { "team": "payments" }, "note": null, "app.tier": "web" },
+            "labels.region": "eu",
+            "labels.zone": 2,
+            "labels.hosts": ["a"],
+            "message": 1,
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        coerce_labels(&mut event);
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "labels": { "shard": "3", "canary": "false", "app_tier": "web" },
+                "labels.region": "eu",
+                "labels.zone": "2",
+                "message": 1,
+            })
+        );
+
+        let mut event = json!({ "labels": "invalid" }).as_object().unwrap().clone();
+        coerce_labels(&mut event);
+        assert!(event.is_empty());
+    }
+}
@@ -3,7 +3,7 @@
 //! ## Features
 //!
 //! - Configurable via the `RUST_LOG` environment variable.
-//!   - Uses [env_logger] under the hood.
+//!   - Filters use the same syntax as [env_logger](https://docs.rs/env_logger).
 //!   - **All logging is disabled except for the `error` level by default.**
 //! - Logs are written to stderr by default.
 //!
@@ -17,6 +17,38 @@
 //! ecs-logger = "1"
 //! ```
 //!
+//! ## Cargo features
+//!
+//! - `std` (enabled by default): Enables the logger itself. Without this feature, only the [`ecs`] module is available
+//!   and the crate is `no_std` (requires `alloc`), so embedded targets can still produce ECS-shaped records.
+//! - `env_logger` (enabled by default): Enables `format_with`, which returns a format function for `env_logger`.
+//!   Disable the default features and enable `std` for a minimal build with the standalone [`Builder`] and the format functions only.
+//! - `anyhow`: Enables building the `error.*` fields of an `anyhow::Error`, including its chain and its captured backtrace,
+//!   with `EcsError::from` and the `error:` argument of `ecs_log!` and the macros of each level.
+//! - `cloud`: Enables adding `cloud.*` fields queried from the instance metadata service of AWS, Google Cloud, or Azure with `FormatOptions::cloud_metadata`, or derived from the environment variables with `FormatOptions::cloud_metadata_from_env`.
+//! - `etw`: Enables the Event Tracing for Windows sink in the `etw` module.
+//! - `eyre`: Like `anyhow`, enables building the `error.*` fields of an `eyre::Report`, whichever handler is installed, e.g. `color-eyre`.
+//! - `ffi`: Exposes a C API in the `ffi` module.
+//! - `host`: Adds `host.*` fields collected from the system to every event. Disable them with `FormatOptions::host_metadata(false)` for privacy-sensitive deployments.
+//! - `http`: Enables converting an `http::Uri` into `UrlFields`.
+//! - `kv`: Enables the [`log`] crate's key-value support, adding the key-values of the records as top-level fields
+//!   (e.g. `info!(user_id = 42; "logged in")` adds `"user_id":42`), and forwarding events to other loggers with `Builder::forward`.
+//! - `kv_serde`: Enables `kv` and the [`log`] crate's `kv_serde` feature, so the key-values captured with `:serde`
+//!   (e.g. `info!(tags:serde = vec!["a", "b"]; "tagged")`) are added as JSON arrays and objects instead of strings.
+//! - `mdc`: Adds the entries of the [`log-mdc`](https://docs.rs/log-mdc) map to each event. See [`extra_fields`].
+//! - `metrics`: Enables `MetricsBridge`, which logs the counters and gauges of the [`metrics`](https://docs.rs/metrics) crate periodically.
+//! - `opentelemetry`: Enables `kv` and `OpenTelemetryAppender`, which emits the events as OpenTelemetry log records
+//!   when passed to `Builder::forward`, and converting an `opentelemetry::trace::SpanContext` into `TraceContext`.
+//! - `regex`: Enables filtering targets with regular expressions in [`Builder`].
+//! - `slog`: Enables `EcsDrain`, a [`slog`](https://docs.rs/slog) drain which writes the records as ECS log lines, including their key-values.
+//! - `tracing`: Enables `EcsLayer`, a [`tracing-subscriber`](https://docs.rs/tracing-subscriber) layer which writes the events of
+//!   the [`tracing`](https://docs.rs/tracing) crate as ECS log lines.
+//! - `max_level_*` and `release_max_level_*`: Forwarded to the [`log`] crate's features of the same names.
+//!   Log statements above the configured level are compiled out, even though the ECS formatter is installed.
+//!
+//! For real-time environments which forbid heap growth after initialization, the [`fixed`] module encodes events into preallocated buffers,
+//! and [`Builder::fixed_buffer`] configures a logger which writes them.
+//!
 //! ## Example
 //!
 //! In the following examples we assume the binary is `./example`.
@@ -37,16 +69,16 @@
 //!
 //! ```bash
 //! $ ./example
-//! {"@timestamp":"2021-11-26T15:25:22.321002600Z","log.level":"ERROR","message":"this is printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}}}
+//! {"@timestamp":"2021-11-26T15:25:22.321002600Z","log.level":"ERROR","message":"this is printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}},"process":{"thread":{"id":1,"name":"main"}}}
 //! ```
 //!
 //! ```bash
 //! $ RUST_LOG=debug ./example
-//! {"@timestamp":"2021-11-26T15:26:13.524069Z","log.level":"DEBUG","message":"this is a debug message, which is NOT printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":9,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}}}
-//! {"@timestamp":"2021-11-26T15:26:13.524193100Z","log.level":"ERROR","message":"this is printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}}}
+//! {"@timestamp":"2021-11-26T15:26:13.524069Z","log.level":"DEBUG","message":"this is a debug message, which is NOT printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":9,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}},"process":{"thread":{"id":1,"name":"main"}}}
+//! {"@timestamp":"2021-11-26T15:26:13.524193100Z","log.level":"ERROR","message":"this is printed by default","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"example.rs"},"rust":{"target":"example::tests","module_path":"example::tests","file_path":"tests/example.rs"}},"process":{"thread":{"id":1,"name":"main"}}}
 //! ```
 //!
-//! More filtering config examples are available at [`env_logger`]'s documentation.
+//! More filtering config examples are available at [`env_logger`'s documentation](https://docs.rs/env_logger).
 //!
 //! ### Extra Fields
 //!
@@ -73,9 +105,64 @@
 //! extra_fields::clear_extra_fields();
 //! ```
 //!
+//! Types of the application can describe their ECS fields by implementing [`ToEcsFields`],
+//! and be attached to the log records of a scope with [`with_fields`].
+//!
+//! ### Standalone logger
+//!
+//! [`Builder`] configures the filters, the output, and the format of the logger without depending on [`env_logger`](https://docs.rs/env_logger).
+//! Glob patterns can be used to drop logs from noisy targets.
+//!
+//! ```
+//! use log::info;
+//!
+//! ecs_logger::Builder::from_env() // Read filters from RUST_LOG
+//!     .deny_target("h2::*") // Drop logs from noisy dependencies
+//!     .target(ecs_logger::Target::Stdout) // Write to stdout
+//!     .init();
+//!
+//! info!("Hello {}!", "world");
+//! ```
+//!
+//! ### Presets
+//!
+//! [`init_production`], [`init_development`], and [`init_test`] install loggers with sensible defaults for each environment.
+//! See [`Builder::production`], [`Builder::development`], and [`Builder::test`] for the details, or to customize them further.
+//!
+//! ```
+//! ecs_logger::init_production();
+//!
+//! log::info!("Hello {}!", "world");
+//! ```
+//!
+//! ### Rate-limited logging
+//!
+//! [`log_once!`], [`log_every_n!`], and [`log_every!`] emit a log only the first time, every Nth time, or at most once per duration per call site.
+//! The number of skipped occurrences is attached as `log.skipped`.
+//!
+//! ```
+//! use log::Level;
+//! use std::time::Duration;
+//!
+//! for i in 0..1000 {
+//!     ecs_logger::log_every!(Duration::from_secs(10), Level::Warn, "queue is full, dropping item {}", i);
+//! }
+//! ```
+//!
+//! ### Testing
+//!
+//! [`test::with_capture`] captures the events logged on the current thread, so tests can assert on them.
+//!
+//! ```
+//! ecs_logger::test::with_capture(|events| {
+//!     log::info!("Hello {}!", "world");
+//!     assert_eq!(events.messages(), vec!["Hello world!"]);
+//! });
+//! ```
+//!
 //! ### Custom logging
 //!
-//! You need to add [`env_logger`] to your `Cargo.toml` for the following examples.
+//! You need to add [`env_logger`](https://docs.rs/env_logger) to your `Cargo.toml` for the following examples.
 //!
 //! ```toml
 //! [dependencies]
@@ -112,6 +199,24 @@
 //! info!("Hello {}!", "world");
 //! ```
 //!
+//! #### Configure output format
+//!
+//! Use [`format_with`] to customize the layout of the log lines.
+//!
+//! ```
+//! use ecs_logger::{FormatOptions, KeyStyle};
+//! use log::info;
+//!
+//! // Initialize custom logger
+//! env_logger::builder()
+//!     .format(ecs_logger::format_with(
+//!         FormatOptions::new().key_style(KeyStyle::Nested), // Expand dotted keys into nested objects
+//!     ))
+//!     .init();
+//!
+//! info!("Hello {}!", "world");
+//! ```
+//!
 //! ## Default log fields
 //!
 //! ```json
@@ -130,19 +235,251 @@
 //!             "module_path": "example::tests",
 //!             "file_path": "tests/example.rs"
 //!         }
+//!     },
+//!     "process": {
+//!         "thread": {
+//!             "id": 1,
+//!             "name": "main"
+//!         }
 //!     }
 //! }
 //! ```
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod access_log;
+#[cfg(feature = "std")]
+mod alias;
+#[cfg(feature = "std")]
+mod background;
+#[cfg(feature = "std")]
+mod broadcast;
+#[cfg(feature = "std")]
+mod categorization;
+#[cfg(feature = "cloud")]
+mod cloud;
+#[cfg(feature = "std")]
+mod connection;
+#[cfg(feature = "std")]
+mod container;
+#[cfg(feature = "std")]
+pub mod context;
+#[cfg(feature = "std")]
+mod data_stream;
+#[cfg(feature = "std")]
+mod dataset;
 pub mod ecs;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "etw")]
+pub mod etw;
+#[cfg(feature = "std")]
+mod exit;
+#[cfg(feature = "std")]
 pub mod extra_fields;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod fields;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+mod filter;
+pub mod fixed;
+#[cfg(feature = "kv")]
+mod forward;
+#[cfg(feature = "std")]
+mod function;
+#[cfg(feature = "std")]
+mod geo;
+#[cfg(feature = "host")]
+mod host;
+#[cfg(feature = "std")]
+mod http;
+#[cfg(feature = "std")]
+mod inline_fields;
+#[cfg(feature = "std")]
+mod kubernetes;
+#[cfg(feature = "kv")]
+mod kv;
+#[cfg(feature = "std")]
+mod labels;
+#[cfg(feature = "std")]
+mod logger;
+#[cfg(feature = "std")]
+mod metric;
+#[cfg(feature = "metrics")]
+mod metrics_bridge;
+#[cfg(feature = "std")]
+mod network;
+#[cfg(feature = "std")]
+mod options;
+#[cfg(feature = "std")]
+mod origin;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "std")]
+mod outcome;
+#[cfg(feature = "std")]
+mod owned;
+#[cfg(feature = "std")]
+mod panic_hook;
+#[cfg(feature = "std")]
+mod presets;
+#[cfg(feature = "std")]
+mod process;
+#[cfg(feature = "std")]
+mod rate_limit;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+mod related;
+#[cfg(feature = "std")]
+mod sampling;
+#[cfg(feature = "std")]
+mod security;
+#[cfg(feature = "std")]
+mod sink;
+#[cfg(feature = "std")]
+mod siphash;
+#[cfg(feature = "slog")]
+mod slog_drain;
+#[cfg(feature = "std")]
+mod syslog;
+#[cfg(feature = "std")]
+mod tags;
+#[cfg(feature = "std")]
+mod template;
+#[cfg(feature = "std")]
+pub mod test;
+#[cfg(all(test, feature = "std"))]
+mod test_util;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(feature = "std")]
 mod timestamp;
+#[cfg(feature = "std")]
+mod tls;
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "tracing")]
+mod tracing_layer;
+#[cfg(feature = "std")]
+mod transaction;
+#[cfg(feature = "std")]
+mod url;
+#[cfg(feature = "std")]
+mod user;
 
-use ecs::Event;
+#[cfg(feature = "std")]
+pub use access_log::AccessLog;
+#[cfg(feature = "std")]
+pub use alias::AliasGroup;
+#[cfg(feature = "std")]
+pub use broadcast::{Broadcast, BroadcastReceiver, Lagged};
+#[cfg(feature = "std")]
+pub use categorization::Categorization;
+#[cfg(feature = "std")]
+pub use connection::ConnectionContext;
+#[cfg(feature = "std")]
+pub use data_stream::DataStream;
+#[cfg(feature = "std")]
+pub use dataset::EventDataset;
+#[cfg(feature = "std")]
+pub use error::{log_error, EcsError, ErrorExt, ResultExt};
+#[cfg(feature = "std")]
+pub use fields::{with_fields, ToEcsFields};
+#[cfg(feature = "std")]
+pub use file::FileFields;
+#[cfg(feature = "std")]
+pub use filter::suppressed_count;
+#[cfg(feature = "std")]
+pub use geo::{GeoLocation, GeoResolver};
+#[cfg(feature = "std")]
+pub use http::HttpContext;
+#[cfg(feature = "std")]
+pub use kubernetes::KubernetesMetadata;
+#[cfg(feature = "std")]
+pub use labels::{Labels, LabelsError};
+#[cfg(feature = "std")]
+pub use logger::{Builder, Logger, Target};
+#[cfg(feature = "std")]
+pub use metric::MetricEvent;
+#[cfg(feature = "metrics")]
+pub use metrics_bridge::MetricsBridge;
+#[cfg(feature = "std")]
+pub use network::NetworkEvent;
+#[cfg(feature = "std")]
+pub use options::{FormatOptions, KeyStyle};
+#[cfg(feature = "opentelemetry")]
+pub use otel::OpenTelemetryAppender;
+#[cfg(feature = "std")]
+pub use outcome::{with_outcome, Outcome};
+#[cfg(feature = "std")]
+pub use owned::OwnedRecord;
+#[cfg(feature = "std")]
+pub use panic_hook::install_panic_hook;
+#[cfg(feature = "std")]
+pub use presets::{init_development, init_production, init_test};
+#[cfg(feature = "std")]
+pub use sampling::AdaptiveSampling;
+#[cfg(feature = "std")]
+pub use security::{Detection, SecurityEvent};
+#[cfg(feature = "slog")]
+pub use slog_drain::EcsDrain;
+#[cfg(feature = "std")]
+pub use syslog::SyslogFacility;
+#[cfg(feature = "std")]
+pub use tags::{add_tag, clear_tags, set_tags};
+#[cfg(feature = "std")]
+pub use timer::{span_timer, SpanTimer};
+#[cfg(feature = "std")]
+pub use tls::TlsContext;
+#[cfg(feature = "std")]
+pub use trace::{TraceContext, TraceparentError};
+#[cfg(feature = "tracing")]
+pub use tracing_layer::EcsLayer;
+#[cfg(feature = "std")]
+pub use transaction::Transaction;
+#[cfg(feature = "std")]
+pub use url::UrlFields;
+#[cfg(feature = "std")]
+pub use user::UserContext;
+
+/// Items used by the exported macros. Not public API.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::error::{with_error, AsEcsError, AsEcsErrorReport};
+    pub use crate::function::{function_name, with_function};
+    pub use crate::inline_fields::{field_value, with_fields};
+    pub use crate::rate_limit::{with_skipped, Every, EveryN, Once};
+    pub use crate::template::with_template;
+    pub use crate::timer::{ResultOutcome, ValueOutcome};
+    pub use log;
+    pub use serde_json;
+}
+
+#[cfg(feature = "std")]
+use ecs::{EcsVersion, Event};
+#[cfg(feature = "std")]
 use extra_fields::merge_extra_fields;
-use std::borrow::BorrowMut;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
 
-/// Initializes the global logger with an instance of [`env_logger::Logger`] with ECS-Logging formatting.
+/// Sequence number of the events emitted without `@timestamp`.
+#[cfg(feature = "std")]
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "std")]
+/// Initializes the global logger with ECS-Logging formatting, configured by the `RUST_LOG` environment variable.
+///
+/// With the `env_logger` feature, which is enabled by default, the logger is an instance of
+/// [`env_logger::Logger`](https://docs.rs/env_logger/latest/env_logger/struct.Logger.html).
+/// Otherwise it is a [`Logger`] built by [`Builder::from_env`].
 ///
 /// This should be called early in the execution of a Rust program. Any log events that occur before initialization will be ignored.
 ///
@@ -165,7 +502,10 @@ pub fn init() {
     try_init().expect("ecs_logger::init should not be called after logger initialized");
 }
 
-/// Attempts to initialize the global logger with an instance of [`env_logger::Logger`] with ECS-Logging formatting.
+#[cfg(feature = "std")]
+/// Attempts to initialize the global logger with ECS-Logging formatting, configured by the `RUST_LOG` environment variable.
+///
+/// The logger is the same as that of [`init`].
 ///
 /// This should be called early in the execution of a Rust program. Any log events that occur before initialization will be ignored.
 ///
@@ -188,12 +528,21 @@ pub fn init() {
 /// assert!(ecs_logger::try_init().is_err());
 /// ```
 pub fn try_init() -> Result<(), log::SetLoggerError> {
-    env_logger::builder().format(format).try_init()
+    #[cfg(feature = "env_logger")]
+    {
+        env_logger::builder().format(format).try_init()
+    }
+    #[cfg(not(feature = "env_logger"))]
+    {
+        Builder::from_env().try_init()
+    }
 }
 
+#[cfg(feature = "std")]
 /// Writes an ECS log line to the `buf`.
 ///
-/// You may pass this format function to [`env_logger::Builder::format`] when building a custom logger.
+/// You may pass this format function to [`env_logger::Builder::format`](https://docs.rs/env_logger/latest/env_logger/struct.Builder.html#method.format)
+/// when building a custom logger.
 ///
 /// # Example
 ///
@@ -210,28 +559,315 @@ pub fn try_init() -> Result<(), log::SetLoggerError> {
 /// info!("Hello {}!", "world");
 /// ```
 pub fn format(buf: &mut impl std::io::Write, record: &log::Record) -> std::io::Result<()> {
-    let event = Event::new(timestamp::get_timestamp(), record);
+    write_event(buf, record, &FormatOptions::default())
+}
+
+#[cfg(feature = "std")]
+/// Writes an ECS log line to the `buf` through a trait object.
+///
+/// This is the non-generic counterpart of [`format()`], which can be stored as a function pointer or called through FFI shims and dynamically loaded plugins.
+///
+/// # Example
+///
+/// ```
+/// let formatter: fn(&mut dyn std::io::Write, &log::Record) -> std::io::Result<()> =
+///     ecs_logger::format_dyn;
+///
+/// let mut buf: Box<dyn std::io::Write> = Box::new(std::io::stdout());
+/// let record = log::Record::builder()
+///     .args(format_args!("Hello {}!", "world"))
+///     .level(log::Level::Info)
+///     .build();
+///
+/// formatter(&mut buf, &record).unwrap();
+/// ```
+pub fn format_dyn(buf: &mut dyn std::io::Write, record: &log::Record) -> std::io::Result<()> {
+    write_event(buf, record, &FormatOptions::default())
+}
+
+#[cfg(feature = "std")]
+/// Writes an ECS log line rendered according to `options` to the `buf`, with `timestamp` as `@timestamp`.
+///
+/// Use this to encode a record later than it was logged, e.g. in a custom queue, passing the time captured when it was logged.
+/// With [`FormatOptions::event_created`] enabled, the time of the encoding is written as `event.created`.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{FormatOptions, OwnedRecord};
+///
+/// let record = log::Record::builder()
+///     .args(format_args!("Hello {}!", "world"))
+///     .level(log::Level::Info)
+///     .build();
+/// let queued = (chrono::Utc::now(), OwnedRecord::from(&record));
+///
+/// // Encode it later
+/// let (timestamp, record) = queued;
+/// let options = FormatOptions::new().event_created(true);
+/// let mut buf = Vec::new();
+/// record
+///     .with_record(|record| ecs_logger::format_at(&mut buf, timestamp, record, &options))
+///     .unwrap();
+/// ```
+pub fn format_at(
+    buf: &mut dyn std::io::Write,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    record: &log::Record,
+    options: &FormatOptions,
+) -> std::io::Result<()> {
+    write_event_at(buf, timestamp, record, options)
+}
+
+#[cfg(feature = "env_logger")]
+/// Returns a format function configured by `options`.
+///
+/// The returned closure may be passed to [`env_logger::Builder::format`] in place of [`format()`].
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{FormatOptions, KeyStyle};
+/// use log::info;
+///
+/// // Initialize custom logger
+/// env_logger::builder()
+///     .format(ecs_logger::format_with(
+///         FormatOptions::new()
+///             .key_style(KeyStyle::Nested) // Expand dotted keys into nested objects
+///             .timestamp_format(chrono::SecondsFormat::Millis), // Use millisecond precision
+///     ))
+///     .init();
+///
+/// info!("Hello {}!", "world");
+/// ```
+pub fn format_with(
+    options: FormatOptions,
+) -> impl Fn(&mut env_logger::fmt::Formatter, &log::Record) -> std::io::Result<()> + Sync + Send + 'static
+{
+    move |buf, record| write_event(buf, record, &options)
+}
+
+#[cfg(feature = "std")]
+/// Logs a record with the target `ecs_logger` whose origin is `location`, for events emitted by this crate on behalf of the caller.
+fn log_at(level: log::Level, args: std::fmt::Arguments, location: &std::panic::Location) {
+    let metadata = log::Metadata::builder()
+        .level(level)
+        .target("ecs_logger")
+        .build();
+    if level > log::max_level() || !log::logger().enabled(&metadata) {
+        return;
+    }
+
+    log::logger().log(
+        &log::Record::builder()
+            .metadata(metadata)
+            .args(args)
+            .file(Some(location.file()))
+            .line(Some(location.line()))
+            .build(),
+    );
+}
+
+#[cfg(feature = "std")]
+/// Writes an ECS log line rendered according to `options` to the `buf`.
+fn write_event(
+    buf: &mut dyn std::io::Write,
+    record: &log::Record,
+    options: &FormatOptions,
+) -> std::io::Result<()> {
+    write_event_at(buf, timestamp::get_timestamp(), record, options)
+}
+
+#[cfg(feature = "std")]
+/// Writes an ECS log line like [`write_event`], stamped with `timestamp` instead of the current time.
+fn write_event_at(
+    buf: &mut dyn std::io::Write,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    record: &log::Record,
+    options: &FormatOptions,
+) -> std::io::Result<()> {
+    let event_json_map = event_json_map(timestamp, record, options);
+
+    serde_json::to_writer(&mut *buf, &event_json_map)?;
+    writeln!(buf)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Builds the JSON object of an ECS event rendered according to `options`, including the extra fields.
+fn event_json_map(
+    timestamp: chrono::DateTime<chrono::Utc>,
+    record: &log::Record,
+    options: &FormatOptions,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut event = Event::new(timestamp, record);
+    event.ecs_version = options.ecs_version.as_str().into();
+    if let Some(hook) = &options.message_hook {
+        event.message = hook.apply(&event.message);
+    }
 
     let event_json_value =
-        serde_json::to_value(event).expect("Event should be converted into JSON");
-    let event_json_map = match event_json_value {
+        serde_json::to_value(&event).expect("Event should be converted into JSON");
+    let mut event_json_map = match event_json_value {
         serde_json::Value::Object(m) => m,
         _ => unreachable!("Event should be converted into a JSON object"),
     };
 
-    let merged_json_map = merge_extra_fields(event_json_map);
+    if let Some(function) = function::current_function() {
+        if let Some(serde_json::Value::Object(log_origin)) = event_json_map.get_mut("log.origin") {
+            log_origin.insert("function".to_string(), function.into());
+        }
+    }
+    let mut process = options.process.map(json_object).unwrap_or_default();
+    process.insert(
+        "thread".to_string(),
+        json_object(process::current_thread()).into(),
+    );
+    let mut service = options.service.as_ref().map(json_object);
+    if let Some(name) = &options.service_node_name {
+        service
+            .get_or_insert_with(Default::default)
+            .insert("node".to_string(), serde_json::json!({ "name": name }));
+    }
+    let field_sets = [
+        ("service", service),
+        ("host", options.host.map(json_object)),
+        ("process", Some(process)),
+        ("cloud", options.cloud.map(json_object)),
+        ("container", options.container.map(json_object)),
+    ];
+    for (key, fields) in field_sets {
+        if let Some(fields) = fields {
+            event_json_map.insert(key.to_string(), fields.into());
+        }
+    }
+    if options.agent {
+        event_json_map.insert(
+            "agent".to_string(),
+            serde_json::json!({
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            }),
+        );
+    }
 
-    serde_json::to_writer(buf.borrow_mut(), &merged_json_map)?;
-    writeln!(buf)?;
+    if !options.timestamp {
+        event_json_map.remove("@timestamp");
+        event_json_map.insert(
+            "event.sequence".to_string(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed).into(),
+        );
+    } else {
+        if options.timestamp_format != chrono::SecondsFormat::AutoSi {
+            event_json_map.insert(
+                "@timestamp".to_string(),
+                timestamp::format_timestamp(&event.timestamp, options.timestamp_format).into(),
+            );
+        }
+        if options.event_created {
+            event_json_map.insert(
+                "event.created".to_string(),
+                timestamp::format_timestamp(&timestamp::get_timestamp(), options.timestamp_format)
+                    .into(),
+            );
+        }
+    }
+    if !options.origin {
+        event_json_map.remove("log.origin");
+    } else if let Some(log_origin) = event_json_map.get_mut("log.origin") {
+        origin::rewrite_log_origin(log_origin, options);
+    }
 
-    Ok(())
+    if options.log_logger {
+        event_json_map.insert(
+            "log.logger".to_string(),
+            origin::log_logger(record, options).into(),
+        );
+    }
+    if let Some(facility) = options.syslog_facility {
+        event_json_map.extend(syslog::syslog_fields(facility, record.level()));
+    }
+    if let Some(template) = template::current_template() {
+        event_json_map.insert("message_template".to_string(), template.into());
+    }
+    if options.event_original {
+        if let Some(template) = template::current_template().or(record.args().as_str()) {
+            event_json_map.insert("event.original".to_string(), template.into());
+        }
+    }
+
+    if let Some(labels) = &options.labels {
+        event_json_map.extend(labels.to_fields());
+    }
+    if let Some(kubernetes) = &options.kubernetes {
+        event_json_map.extend(kubernetes.to_fields());
+    }
+    if let Some(trace_context) = &options.trace_context {
+        trace_context.insert_into(&mut event_json_map);
+    }
+
+    let mut event_json_map = merge_extra_fields(event_json_map);
+    #[cfg(feature = "kv")]
+    extra_fields::extend_json_map(
+        &mut event_json_map,
+        &kv::key_values(record, options.nest_kv_keys),
+    );
+    labels::coerce_labels(&mut event_json_map);
+    tags::merge_tags(&mut event_json_map);
+    if options.ecs_version == EcsVersion::V8 {
+        alias::rename_to_v8(&mut event_json_map);
+    }
+    alias::add_aliases(&mut event_json_map, &options.field_aliases);
+    if let Some(resolver) = &options.geo_resolver {
+        geo::add_geo_fields(&mut event_json_map, resolver.0.as_ref());
+    }
+    if options.related_fields {
+        related::add_related(&mut event_json_map);
+    }
+    if options.event_outcome {
+        outcome::set_failure_on_error(&mut event_json_map, record.level());
+    }
+    if let Some(categorization) = &options.event_categorization {
+        categorization.insert_into(&mut event_json_map);
+    }
+    dataset::set_event_dataset(&mut event_json_map, record.target(), options);
+    if let Some(data_stream) = &options.data_stream {
+        data_stream.insert_into(&mut event_json_map);
+    }
+
+    options.key_style.apply(event_json_map)
+}
+
+/// Converts a field set of [`ecs`], e.g. [`ecs::Service`], into a JSON object
+#[cfg(feature = "std")]
+fn json_object(fields: impl serde::Serialize) -> serde_json::Map<String, serde_json::Value> {
+    extra_fields::to_json_map(fields).expect("ECS field set should be converted into a JSON object")
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
+    use ecs::Service;
     use serde_json::json;
 
+    /// Adds the `host` field, which is added by default when the `host` feature is enabled, to the expected event
+    fn with_default_host(expected: serde_json::Value) -> serde_json::Value {
+        #[cfg(feature = "host")]
+        if let serde_json::Value::Object(fields) = expected {
+            let mut with_host = serde_json::Map::new();
+            for (k, v) in fields {
+                if k == "process" {
+                    with_host.insert("host".to_string(), json!(host::host()));
+                }
+                with_host.insert(k, v);
+            }
+            return with_host.into();
+        }
+        expected
+    }
+
     #[test]
     fn test_init() {
         init();
@@ -249,7 +885,7 @@ mod tests {
         let log_line = String::from_utf8(buf).unwrap();
         assert_eq!(
             log_line,
-            json!({
+            with_default_host(json!({
                 "@timestamp": timestamp::MOCK_TIMESTAMP,
                 "log.level": "ERROR",
                 "message": "hello world",
@@ -264,8 +900,11 @@ mod tests {
                         "module_path": "example::tests",
                         "file_path": "tests/example.rs"
                     }
+                },
+                "process": {
+                    "thread": process::current_thread(),
                 }
-            })
+            }))
             .to_string()
                 + "\n"
         );
@@ -288,7 +927,7 @@ mod tests {
         let log_line = String::from_utf8(buf).unwrap();
         assert_eq!(
             log_line,
-            json!({
+            with_default_host(json!({
                 "@timestamp": timestamp::MOCK_TIMESTAMP,
                 "log.level": "ERROR",
                 "message": "hello world",
@@ -304,16 +943,445 @@ mod tests {
                         "file_path": "tests/example.rs"
                     }
                 },
+                "process": {
+                    "thread": process::current_thread(),
+                },
                 "a": 1,
                 "b": {
                     "c": 2,
                 },
+            }))
+            .to_string()
+                + "\n"
+        );
+    }
+
+    #[test]
+    fn test_format_with() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new()
+            .key_style(KeyStyle::Nested)
+            .timestamp_format(chrono::SecondsFormat::Millis)
+            .origin(false);
+        #[cfg(feature = "host")]
+        let options = options.host_metadata(false);
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let log_line = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            log_line,
+            json!({
+                "@timestamp": "2000-01-23T01:23:45.678Z",
+                "log": {
+                    "level": "ERROR",
+                },
+                "message": "hello world",
+                "ecs": {
+                    "version": "1.12.1",
+                },
+                "process": {
+                    "thread": process::current_thread(),
+                },
             })
             .to_string()
                 + "\n"
         );
     }
 
+    #[test]
+    fn test_format_with_message_hook() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new()
+            .origin(false)
+            .message_hook(|message| format!("[tenant-a] {}", message));
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["message"], "[tenant-a] hello world");
+    }
+
+    #[test]
+    fn test_format_with_service() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new().origin(false).service(Service {
+            name: Some("my-app".into()),
+            version: Some("1.2.3".into()),
+            environment: None,
+            node: None,
+        });
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            v["service"],
+            json!({ "name": "my-app", "version": "1.2.3" })
+        );
+
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &options.service_node_name("my-app-0")).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            v["service"],
+            json!({ "name": "my-app", "version": "1.2.3", "node": { "name": "my-app-0" } })
+        );
+
+        // The node name is kept when the service is set afterwards
+        let options = FormatOptions::new()
+            .origin(false)
+            .service_node_name("my-app-0")
+            .service(Service {
+                name: Some("my-app".into()),
+                ..Service::default()
+            });
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &options).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            v["service"],
+            json!({ "name": "my-app", "node": { "name": "my-app-0" } })
+        );
+    }
+
+    #[test]
+    fn test_format_with_event_categorization() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new()
+            .origin(false)
+            .event_outcome(true)
+            .event_categorization(
+                Categorization::new()
+                    .kind("event")
+                    .category("web")
+                    .outcome(Outcome::Success),
+            );
+        Categorization::new()
+            .event_type("access")
+            .in_scope(|| write_event(&mut buf, &record, &options))
+            .unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["event.kind"], "event");
+        assert_eq!(v["event.category"], json!(["web"]));
+        assert_eq!(v["event.type"], json!(["access"]));
+        assert_eq!(v["event.outcome"], "failure");
+    }
+
+    #[test]
+    fn test_format_with_event_original() {
+        extra_fields::clear_extra_fields();
+
+        let options = FormatOptions::new().origin(false).event_original(true);
+        let format = |record: &log::Record| {
+            let mut buf = Vec::new();
+            write_event(&mut buf, record, &options).unwrap();
+            serde_json::from_slice::<serde_json::Value>(&buf).unwrap()
+        };
+
+        let name = "alice";
+        let v = template::with_template("user {} logged in", || {
+            format(
+                &log::Record::builder()
+                    .args(format_args!("user {} logged in", name))
+                    .build(),
+            )
+        });
+        assert_eq!(v["message"], "user alice logged in");
+        assert_eq!(v["message_template"], "user {} logged in");
+        assert_eq!(v["event.original"], "user {} logged in");
+
+        let v = format(&log::Record::builder().args(format_args!("started")).build());
+        assert!(v.get("message_template").is_none());
+        assert_eq!(v["event.original"], "started");
+
+        let v = format(
+            &log::Record::builder()
+                .args(format_args!("user {} logged in", name))
+                .build(),
+        );
+        assert!(v.get("event.original").is_none());
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn test_format_with_key_values() {
+        extra_fields::clear_extra_fields();
+        extra_fields::set_extra_fields(
+            json!({ "user_id": "anonymous", "service": { "version": "1.0" } }),
+        )
+        .unwrap();
+
+        let mut buf = Vec::new();
+        let kvs: &[(&str, log::kv::Value)] = &[("user_id", 42.into()), ("retry", false.into())];
+        let record = log::Record::builder()
+            .args(format_args!("logged in"))
+            .key_values(&kvs)
+            .build();
+        write_event(&mut buf, &record, &FormatOptions::new().origin(false)).unwrap();
+        extra_fields::clear_extra_fields();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["message"], "logged in");
+        assert_eq!(v["user_id"], 42);
+        assert_eq!(v["retry"], false);
+        assert_eq!(v["service"]["version"], "1.0");
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn test_format_with_nested_key_values() {
+        extra_fields::clear_extra_fields();
+
+        let kvs: &[(&str, log::kv::Value)] = &[("http.request.method", "GET".into())];
+        let record = log::Record::builder()
+            .args(format_args!("Request"))
+            .key_values(&kvs)
+            .build();
+        let format = |options: FormatOptions| {
+            let mut buf = Vec::new();
+            extra_fields::with_scoped_fields(
+                json!({ "http": { "version": "1.1" } })
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+                || write_event(&mut buf, &record, &options.origin(false)),
+            )
+            .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&buf).unwrap()
+        };
+
+        let v = format(FormatOptions::new());
+        assert_eq!(v["http.request.method"], "GET");
+        assert_eq!(v["http"], json!({ "version": "1.1" }));
+
+        let v = format(FormatOptions::new().nest_kv_keys(true));
+        assert_eq!(
+            v["http"],
+            json!({ "version": "1.1", "request": { "method": "GET" } })
+        );
+
+        let v = format(
+            FormatOptions::new()
+                .nest_kv_keys(true)
+                .key_style(KeyStyle::Flat),
+        );
+        assert_eq!(v["http.request.method"], "GET");
+        assert_eq!(v["http.version"], "1.1");
+    }
+
+    #[test]
+    fn test_format_with_labels() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new()
+            .origin(false)
+            .labels(Labels::new().label("region", "eu").label("build", 41));
+        let _context =
+            context::push(json!({ "labels": { "build": 42, "owner": { "team": "payments" } } }))
+                .unwrap();
+        Labels::new()
+            .label("tenant", "acme")
+            .in_scope(|| write_event(&mut buf, &record, &options))
+            .unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            v["labels"],
+            json!({ "region": "eu", "build": "42", "tenant": "acme" })
+        );
+    }
+
+    #[test]
+    fn test_format_with_kubernetes() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new().origin(false).kubernetes(
+            KubernetesMetadata::new()
+                .namespace("shop")
+                .pod_name("checkout-0"),
+        );
+        let _context = context::push(json!({ "orchestrator.namespace": "override" })).unwrap();
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["orchestrator.type"], "kubernetes");
+        assert_eq!(v["orchestrator.namespace"], "override");
+        assert_eq!(v["orchestrator.resource.name"], "checkout-0");
+        assert_eq!(v["kubernetes.namespace"], "shop");
+    }
+
+    #[test]
+    fn test_format_with_log_logger() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new().log_logger(true);
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["log.logger"], "example");
+        assert_eq!(v["log.origin"]["rust"]["target"], "example");
+    }
+
+    #[test]
+    fn test_format_with_ecs_version() {
+        extra_fields::clear_extra_fields();
+
+        let record = create_example_record();
+        let options = FormatOptions::new().ecs_version(EcsVersion::V8);
+        let mut buf = Vec::new();
+        let _context = context::push(json!({ "log.original": "raw line" })).unwrap();
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["ecs.version"], "8.11.0");
+        assert_eq!(v["event.original"], "raw line");
+        assert!(v.get("log.original").is_none());
+    }
+
+    #[test]
+    fn test_format_with_agent() {
+        extra_fields::clear_extra_fields();
+
+        let record = create_example_record();
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &FormatOptions::new().agent(true)).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            v["agent"],
+            json!({ "name": "ecs-logger", "version": env!("CARGO_PKG_VERSION") })
+        );
+
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &FormatOptions::new()).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(v.get("agent").is_none());
+    }
+
+    #[test]
+    fn test_format_with_function() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        function::with_function("Server::handle", || {
+            write_event(&mut buf, &record, &FormatOptions::new())
+        })
+        .unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["log.origin"]["function"], "Server::handle");
+    }
+
+    #[test]
+    fn test_format_with_process_metadata() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let options = FormatOptions::new().origin(false).process_metadata(true);
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["process"]["pid"], std::process::id());
+        assert!(v["process"]["name"].is_string());
+        assert!(v["process"]["executable"].is_string());
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn test_format_with_host_metadata() {
+        extra_fields::clear_extra_fields();
+
+        let record = create_example_record();
+        // Enabled by default
+        let options = FormatOptions::new().origin(false);
+
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &options).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(v["host"]["name"].is_string());
+        assert_eq!(v["host"]["name"], v["host"]["hostname"]);
+
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &options.host_metadata(false)).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(v.get("host").is_none());
+    }
+
+    #[test]
+    fn test_format_dyn() {
+        extra_fields::clear_extra_fields();
+
+        let record = create_example_record();
+
+        let mut buf = Vec::new();
+        format(&mut buf, &record).unwrap();
+
+        let mut dyn_buf = Vec::new();
+        format_dyn(&mut dyn_buf as &mut dyn std::io::Write, &record).unwrap();
+
+        assert_eq!(dyn_buf, buf);
+    }
+
+    #[test]
+    fn test_format_at_with_event_created() {
+        extra_fields::clear_extra_fields();
+
+        let mut buf = Vec::new();
+        let record = create_example_record();
+        let logged_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let options = FormatOptions::new()
+            .origin(false)
+            .timestamp_format(chrono::SecondsFormat::Millis)
+            .event_created(true);
+        format_at(&mut buf, logged_at, &record, &options).unwrap();
+
+        let v: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(v["@timestamp"], "2023-11-14T22:13:20.000Z");
+        assert_eq!(v["event.created"], "2000-01-23T01:23:45.678Z");
+    }
+
+    #[test]
+    fn test_format_without_timestamp() {
+        extra_fields::clear_extra_fields();
+
+        let record = create_example_record();
+        let options = FormatOptions::new().timestamp(false).origin(false);
+
+        let mut buf = Vec::new();
+        write_event(&mut buf, &record, &options).unwrap();
+        write_event(&mut buf, &record, &options).unwrap();
+
+        let log_lines = String::from_utf8(buf).unwrap();
+        let sequences = log_lines
+            .lines()
+            .map(|line| {
+                let v: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert!(v.get("@timestamp").is_none());
+                v["event.sequence"].as_u64().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(sequences[0] < sequences[1]);
+    }
+
     fn create_example_record<'a>() -> log::Record<'a> {
         log::Record::builder()
             .args(format_args!("hello world"))
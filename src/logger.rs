@@ -0,0 +1,961 @@
+//! Standalone logger configured with a [`Builder`]
+
+use crate::background::Background;
+use crate::broadcast::Broadcast;
+use crate::categorization::Categorization;
+use crate::data_stream::DataStream;
+use crate::dataset::EventDataset;
+use crate::ecs::{EcsVersion, Service};
+use crate::filter::{Directives, DirectivesBuilder, MessageFilter, Pattern, TargetFilter};
+#[cfg(feature = "kv")]
+use crate::forward::Forwarder;
+use crate::kubernetes::KubernetesMetadata;
+use crate::sampling::{AdaptiveSampling, Sampler};
+use crate::sink::{Sink, Subscriber};
+use crate::FormatOptions;
+use chrono::Utc;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, TryLockError};
+use std::time::Duration;
+
+/// How long a flush from an exit handler waits for the background thread.
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Builder for [`Logger`].
+///
+/// Filters are configured in the same way as [`env_logger`](https://docs.rs/env_logger), and additional filters can be layered on top of them.
+/// With the `env_logger` feature, the filters are parsed by `env_logger` itself. The builder is also available without it,
+/// parsing the filters with a built-in parser of the same syntax.
+///
+/// # Example
+///
+/// ```
+/// use log::info;
+///
+/// ecs_logger::Builder::from_env()
+///     .deny_target("h2::*") // Drop logs from noisy dependencies
+///     .deny_target("rustls::*")
+///     .target(ecs_logger::Target::Stdout)
+///     .init();
+///
+/// info!("Hello {}!", "world");
+/// ```
+pub struct Builder {
+    filter: DirectivesBuilder,
+    target_filter: TargetFilter,
+    message_filter: MessageFilter,
+    sampling: Option<AdaptiveSampling>,
+    deferred_capacity: Option<usize>,
+    fixed_capacity: Option<usize>,
+    flush_on_exit: bool,
+    error_stack_trace: bool,
+    target: Target,
+    subscribers: Vec<Subscriber>,
+    #[cfg(feature = "kv")]
+    forwards: Vec<Box<dyn Log>>,
+    options: FormatOptions,
+    /// Changes made with the methods of the builder, applied on top of `options` in the order they are made
+    option_edits: Vec<OptionEdit>,
+}
+
+type OptionEdit = Box<dyn FnOnce(FormatOptions) -> FormatOptions + Send>;
+
+/// Destination of the log lines.
+#[derive(Default)]
+pub enum Target {
+    /// Writes to stderr.
+    #[default]
+    Stderr,
+
+    /// Writes to stdout.
+    Stdout,
+
+    /// Writes to a custom writer.
+    Pipe(Box<dyn Write + Send + 'static>),
+}
+
+/// Logger which writes ECS log lines to a [`Target`].
+///
+/// Use [`Builder`] to construct it.
+pub struct Logger {
+    filter: Directives,
+    target_filter: TargetFilter,
+    message_filter: MessageFilter,
+    sampler: Option<Sampler>,
+    error_stack_trace: bool,
+    output: Output,
+    #[cfg(feature = "kv")]
+    forwarder: Option<Forwarder>,
+}
+
+/// How a [`Logger`] encodes and writes the events.
+enum Output {
+    /// Encodes and writes on the logging thread.
+    Direct {
+        sink: Mutex<Sink>,
+        options: Box<FormatOptions>,
+    },
+
+    /// Encodes and writes on a background thread.
+    Deferred(Background),
+
+    /// Encodes into a preallocated buffer and writes on the logging thread.
+    Fixed {
+        sink: Mutex<Sink>,
+        buf: Mutex<Box<[u8]>>,
+        dropped: AtomicU64,
+    },
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    /// Creates a builder without any filters.
+    ///
+    /// Only `error` level logs are enabled unless filters are configured.
+    pub fn new() -> Self {
+        Builder {
+            filter: DirectivesBuilder::default(),
+            target_filter: TargetFilter::default(),
+            message_filter: MessageFilter::default(),
+            sampling: None,
+            deferred_capacity: None,
+            fixed_capacity: None,
+            flush_on_exit: false,
+            error_stack_trace: false,
+            target: Target::default(),
+            subscribers: Vec::new(),
+            #[cfg(feature = "kv")]
+            forwards: Vec::new(),
+            options: FormatOptions::default(),
+            option_edits: Vec::new(),
+        }
+    }
+
+    /// Creates a builder with filters parsed from the `RUST_LOG` environment variable.
+    pub fn from_env() -> Self {
+        let mut builder = Self::new();
+        if let Ok(filters) = std::env::var("RUST_LOG") {
+            builder.parse_filters(&filters);
+        }
+        builder
+    }
+
+    /// Adds filters in the same syntax as the `RUST_LOG` environment variable.
+    ///
+    /// `filters` is a comma-separated list of directives, each of which is `level`, `target`, or `target=level`,
+    /// optionally followed by `/` and a substring which the messages must contain (e.g. `warn,my_app=debug/timeout`).
+    /// A directive applies to the targets starting with `target`, and the most specific one wins.
+    pub fn parse_filters(&mut self, filters: &str) -> &mut Self {
+        self.filter.parse(filters);
+        self
+    }
+
+    /// Adds a directive to the filter for all modules.
+    pub fn filter_level(&mut self, level: LevelFilter) -> &mut Self {
+        self.filter.filter(None, level);
+        self
+    }
+
+    /// Adds a directive to the filter for a specific module.
+    pub fn filter_module(&mut self, module: &str, level: LevelFilter) -> &mut Self {
+        self.filter.filter(Some(module), level);
+        self
+    }
+
+    /// Only allows targets matching one of the allow patterns.
+    ///
+    /// Patterns are globs where `*` matches any sequence of characters (including `::`) and `?` matches any single character.
+    /// If no allow pattern is added, all targets are allowed.
+    pub fn allow_target(&mut self, pattern: &str) -> &mut Self {
+        self.target_filter.allow(Pattern::Glob(pattern.to_string()));
+        self
+    }
+
+    /// Drops targets matching the glob `pattern`, even if they are allowed by other filters.
+    ///
+    /// See [`allow_target`](Self::allow_target) for the pattern syntax.
+    pub fn deny_target(&mut self, pattern: &str) -> &mut Self {
+        self.target_filter.deny(Pattern::Glob(pattern.to_string()));
+        self
+    }
+
+    /// Only allows targets matching one of the allow patterns, like [`allow_target`](Self::allow_target) but with a regular expression.
+    ///
+    /// The regular expression is not anchored, so use `^` and `$` to match whole targets.
+    /// This method is available when the `regex` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use regex::Regex;
+    ///
+    /// ecs_logger::Builder::from_env()
+    ///     .allow_target_regex(Regex::new(r"^my_app(::|$)").unwrap())
+    ///     .init();
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn allow_target_regex(&mut self, regex: regex::Regex) -> &mut Self {
+        self.target_filter.allow(Pattern::Regex(regex));
+        self
+    }
+
+    /// Drops targets matching the regular expression, like [`deny_target`](Self::deny_target).
+    ///
+    /// This method is available when the `regex` feature is enabled.
+    #[cfg(feature = "regex")]
+    pub fn deny_target_regex(&mut self, regex: regex::Regex) -> &mut Self {
+        self.target_filter.deny(Pattern::Regex(regex));
+        self
+    }
+
+    /// Drops records whose rendered message matches the glob `pattern`.
+    ///
+    /// This is meant for third-party warnings which cannot be fixed but must not be indexed millions of times.
+    /// The number of dropped records is available from [`suppressed_count`](crate::suppressed_count).
+    /// See [`allow_target`](Self::allow_target) for the pattern syntax.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env()
+    ///     .suppress_message("*connection reset by peer*")
+    ///     .init();
+    /// ```
+    pub fn suppress_message(&mut self, pattern: &str) -> &mut Self {
+        self.message_filter
+            .suppress(Pattern::Glob(pattern.to_string()));
+        self
+    }
+
+    /// Drops records whose rendered message matches the regular expression, like [`suppress_message`](Self::suppress_message).
+    ///
+    /// This method is available when the `regex` feature is enabled.
+    #[cfg(feature = "regex")]
+    pub fn suppress_message_regex(&mut self, regex: regex::Regex) -> &mut Self {
+        self.message_filter.suppress(Pattern::Regex(regex));
+        self
+    }
+
+    /// Enables adaptive sampling of low-severity events under load.
+    ///
+    /// See [`AdaptiveSampling`] for details.
+    pub fn adaptive_sampling(&mut self, sampling: AdaptiveSampling) -> &mut Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    /// Moves encoding and writing of the events to a background thread.
+    ///
+    /// The logging thread only captures an owned snapshot of the record, and queues it.
+    /// Up to `capacity` events are queued; logging blocks while the queue is full.
+    /// [`log::logger().flush()`](log::Log::flush) waits until all queued events are written.
+    /// This replaces [`fixed_buffer`](Self::fixed_buffer).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env()
+    ///     .deferred_formatting(1024)
+    ///     .init();
+    ///
+    /// log::info!("Hello {}!", "world");
+    /// log::logger().flush();
+    /// ```
+    pub fn deferred_formatting(&mut self, capacity: usize) -> &mut Self {
+        self.deferred_capacity = Some(capacity);
+        self.fixed_capacity = None;
+        self
+    }
+
+    /// Encodes the events with the [`fixed`](crate::fixed) encoder into a buffer of `capacity` bytes, which is allocated when the logger is built,
+    /// so that logging does not grow the heap, e.g. in real-time environments.
+    ///
+    /// The events which do not fit in the buffer are dropped, and counted by [`Logger::dropped_events`].
+    /// Like the `fixed` module, the encoder writes the fields of [`Event`](crate::ecs::Event) only,
+    /// so [`format_options`](Self::format_options), the extra fields, and the context fields are ignored.
+    /// The filters, the sampling, and the target still apply.
+    /// This replaces [`deferred_formatting`](Self::deferred_formatting).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env()
+    ///     .fixed_buffer(512)
+    ///     .init();
+    ///
+    /// log::info!("Hello {}!", "world");
+    /// ```
+    pub fn fixed_buffer(&mut self, capacity: usize) -> &mut Self {
+        self.fixed_capacity = Some(capacity);
+        self.deferred_capacity = None;
+        self
+    }
+
+    /// Sets whether the global logger is flushed when the process exits or panics.
+    ///
+    /// When enabled, [`init`](Self::init) registers an `atexit` handler and a panic hook, which run after the previous hook.
+    /// They flush on a best-effort basis: the flush gives up if the writer is busy on the same thread, and waits at most
+    /// 5 seconds for [deferred formatting](Self::deferred_formatting), so that the event which describes a crash is not lost.
+    /// Fatal signals such as `SIGSEGV` are not handled.
+    ///
+    /// Defaults to `false`.
+    pub fn flush_on_exit(&mut self, enabled: bool) -> &mut Self {
+        self.flush_on_exit = enabled;
+        self
+    }
+
+    /// Sets whether a backtrace is captured for `ERROR` records and written to `error.stack_trace`.
+    ///
+    /// The backtrace is captured on the logging thread regardless of the `RUST_BACKTRACE` environment variable,
+    /// and appended to the cause chain written by [`ErrorExt`](crate::ErrorExt) if any.
+    /// Capturing a backtrace is slow, so this is meant for services which log errors rarely.
+    ///
+    /// Defaults to `false`.
+    pub fn error_stack_trace(&mut self, enabled: bool) -> &mut Self {
+        self.error_stack_trace = enabled;
+        self
+    }
+
+    /// Sets where the log lines are written to.
+    ///
+    /// Defaults to [`Target::Stderr`].
+    pub fn target(&mut self, target: Target) -> &mut Self {
+        self.target = target;
+        self
+    }
+
+    /// Registers a callback which receives each rendered event in addition to the target.
+    ///
+    /// The callback receives the JSON document without the trailing newline.
+    /// It is called on the thread which writes the event, in the order the events are written,
+    /// so it should return quickly, e.g. by forwarding the event to a channel.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    /// ecs_logger::Builder::new()
+    ///     .filter_level(log::LevelFilter::Info)
+    ///     .subscribe(move |event| {
+    ///         let _ = sender.send(event.to_string());
+    ///     })
+    ///     .init();
+    ///
+    /// log::info!("Hello {}!", "world");
+    /// assert!(receiver.recv().unwrap().contains("Hello world!"));
+    /// ```
+    pub fn subscribe(&mut self, subscriber: impl Fn(&str) + Send + 'static) -> &mut Self {
+        self.subscribers.push(Box::new(subscriber));
+        self
+    }
+
+    /// Sends each rendered event to `broadcast`, whose receivers can consume the events independently.
+    ///
+    /// See [`Broadcast`] for details.
+    pub fn broadcast(&mut self, broadcast: &Broadcast) -> &mut Self {
+        let broadcast = broadcast.clone();
+        self.subscribe(move |event| broadcast.send(event))
+    }
+
+    /// Forwards each record to `logger` as well, with the ECS fields attached as key-values with dotted names.
+    ///
+    /// The record passed to `logger` has the original message, level, and origin, and key-values such as
+    /// `log.level`, `log.origin.file.line`, and the extra fields. This lets another [`log`] backend,
+    /// e.g. the bridge of `opentelemetry-appender-log`, ship the same events with ECS attribute names.
+    /// With the `opentelemetry` feature, `OpenTelemetryAppender` also maps them to the native
+    /// body, severity, timestamp, and trace context of the OpenTelemetry log records.
+    /// This method is available when the `kv` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use opentelemetry_appender_log::OpenTelemetryLogBridge;
+    ///
+    /// let bridge = OpenTelemetryLogBridge::new(&logger_provider);
+    ///
+    /// ecs_logger::Builder::from_env()
+    ///     .forward(bridge) // Ship to OpenTelemetry as well as writing to stderr
+    ///     .init();
+    /// ```
+    #[cfg(feature = "kv")]
+    pub fn forward(&mut self, logger: impl Log + 'static) -> &mut Self {
+        self.forwards.push(Box::new(logger));
+        self
+    }
+
+    /// Adds the `service.name`, `service.version`, and `service.environment` fields to every event.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env()
+    ///     .service("my-app", env!("CARGO_PKG_VERSION"), "production")
+    ///     .init();
+    /// ```
+    pub fn service(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        version: impl Into<Cow<'static, str>>,
+        environment: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        let service = Service {
+            name: Some(name.into()),
+            version: Some(version.into()),
+            environment: Some(environment.into()),
+            node: None,
+        };
+        self.edit_options(move |options| options.service(service))
+    }
+
+    /// Adds the `service.node.name` field to every event, e.g. the name of the pod or the id of the instance.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env()
+    ///     .service("my-app", env!("CARGO_PKG_VERSION"), "production")
+    ///     .service_node_name(std::env::var("POD_NAME").unwrap_or_default())
+    ///     .init();
+    /// ```
+    pub fn service_node_name(&mut self, name: impl Into<Cow<'static, str>>) -> &mut Self {
+        let name = name.into();
+        self.edit_options(move |options| options.service_node_name(name))
+    }
+
+    /// Sets how `event.dataset` is set, e.g. [`EventDataset::Auto`] to use the name given to [`service`](Self::service).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::EventDataset;
+    ///
+    /// ecs_logger::Builder::from_env()
+    ///     .service("my-app", env!("CARGO_PKG_VERSION"), "production")
+    ///     .event_dataset(EventDataset::Auto) // "event.dataset":"my-app"
+    ///     .init();
+    /// ```
+    pub fn event_dataset(&mut self, dataset: EventDataset) -> &mut Self {
+        self.edit_options(move |options| options.event_dataset(dataset))
+    }
+
+    /// Sets the `event.kind`, `event.category`, `event.type`, and `event.outcome` fields of every record.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::Categorization;
+    ///
+    /// ecs_logger::Builder::from_env()
+    ///     .event_categorization(Categorization::new().kind("event").category("iam"))
+    ///     .init();
+    /// ```
+    pub fn event_categorization(&mut self, categorization: Categorization) -> &mut Self {
+        self.edit_options(move |options| options.event_categorization(categorization))
+    }
+
+    /// Adds the `orchestrator.*` and `kubernetes.*` fields of the pod to every record, reading them with [`KubernetesMetadata::from_env`].
+    ///
+    /// Nothing is added outside of Kubernetes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ecs_logger::Builder::from_env().kubernetes_from_env().init();
+    /// ```
+    pub fn kubernetes_from_env(&mut self) -> &mut Self {
+        let metadata = KubernetesMetadata::from_env();
+        self.edit_options(move |options| options.kubernetes(metadata))
+    }
+
+    /// Sets the `data_stream.*` fields which route the records to an Elastic data stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::DataStream;
+    ///
+    /// ecs_logger::Builder::from_env()
+    ///     .data_stream(DataStream::new().dataset("my_app").namespace("production"))
+    ///     .init();
+    /// ```
+    pub fn data_stream(&mut self, data_stream: DataStream) -> &mut Self {
+        self.edit_options(move |options| options.data_stream(data_stream))
+    }
+
+    /// Sets the version of the ECS schema which the events conform to.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::ecs::EcsVersion;
+    ///
+    /// ecs_logger::Builder::from_env().ecs_version(EcsVersion::V8).init();
+    /// ```
+    pub fn ecs_version(&mut self, version: EcsVersion) -> &mut Self {
+        self.edit_options(move |options| options.ecs_version(version))
+    }
+
+    /// Sets whether the `host.*` fields are added to every event.
+    ///
+    /// Disable this for privacy-sensitive deployments.
+    /// This method is available when the `host` feature is enabled.
+    #[cfg(feature = "host")]
+    pub fn host_metadata(&mut self, enabled: bool) -> &mut Self {
+        self.edit_options(move |options| options.host_metadata(enabled))
+    }
+
+    /// Sets the options which control how the log lines are rendered.
+    ///
+    /// The options set with the other methods of the builder, e.g. [`service`](Self::service), are applied on top of `options`,
+    /// whether they are set before or after this is called.
+    pub fn format_options(&mut self, options: FormatOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    fn edit_options(
+        &mut self,
+        edit: impl FnOnce(FormatOptions) -> FormatOptions + Send + 'static,
+    ) -> &mut Self {
+        self.option_edits.push(Box::new(edit));
+        self
+    }
+
+    /// Builds a [`Logger`], leaving the builder with the default configuration.
+    pub fn build(&mut self) -> Logger {
+        let Builder {
+            mut filter,
+            target_filter,
+            message_filter,
+            sampling,
+            deferred_capacity,
+            fixed_capacity,
+            flush_on_exit: _,
+            error_stack_trace,
+            target,
+            subscribers,
+            #[cfg(feature = "kv")]
+            forwards,
+            options,
+            option_edits,
+        } = std::mem::take(self);
+        let options = option_edits
+            .into_iter()
+            .fold(options, |options, edit| edit(options));
+
+        let writer: Box<dyn Write + Send> = match target {
+            Target::Stderr => Box::new(std::io::stderr()),
+            Target::Stdout => Box::new(std::io::stdout()),
+            Target::Pipe(pipe) => pipe,
+        };
+        let sink = Sink::new(writer, subscribers);
+        #[cfg(feature = "kv")]
+        let forwarder = (!forwards.is_empty()).then(|| Forwarder::new(forwards, options.clone()));
+
+        let output = match (deferred_capacity, fixed_capacity) {
+            (Some(capacity), _) => Output::Deferred(Background::spawn(sink, options, capacity)),
+            (None, Some(capacity)) => Output::Fixed {
+                sink: Mutex::new(sink),
+                buf: Mutex::new(vec![0; capacity].into_boxed_slice()),
+                dropped: AtomicU64::new(0),
+            },
+            (None, None) => Output::Direct {
+                sink: Mutex::new(sink),
+                options: Box::new(options),
+            },
+        };
+        let sampler = sampling.map(|config| {
+            let queue = match &output {
+                Output::Deferred(background) => Some(background.queue_depth()),
+                _ => None,
+            };
+            Sampler::new(config, queue)
+        });
+
+        Logger {
+            filter: filter.build(),
+            target_filter,
+            message_filter,
+            sampler,
+            error_stack_trace,
+            output,
+            #[cfg(feature = "kv")]
+            forwarder,
+        }
+    }
+
+    /// Initializes the global logger with the built [`Logger`].
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`log::SetLoggerError`] if the global logger has already been initialized.
+    pub fn try_init(&mut self) -> Result<(), SetLoggerError> {
+        let flush_on_exit = self.flush_on_exit;
+        let logger = self.build();
+
+        let max_level = logger.filter();
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(max_level);
+
+        if flush_on_exit {
+            crate::exit::install();
+        }
+
+        Ok(())
+    }
+
+    /// Initializes the global logger with the built [`Logger`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the global logger has already been initialized.
+    pub fn init(&mut self) {
+        self.try_init()
+            .expect("Builder::init should not be called after logger initialized");
+    }
+}
+
+impl Logger {
+    /// Returns the maximum level this logger is enabled for.
+    pub fn filter(&self) -> LevelFilter {
+        self.filter.filter()
+    }
+
+    /// Returns `true` if `record` passes all the filters.
+    pub fn matches(&self, record: &Record) -> bool {
+        self.filter.matches(record)
+            && self.target_filter.matches(record.target())
+            && self.message_filter.matches(record)
+    }
+
+    /// Returns the number of events dropped because they did not fit in the buffer of [`Builder::fixed_buffer`].
+    pub fn dropped_events(&self) -> u64 {
+        match &self.output {
+            Output::Fixed { dropped, .. } => dropped.load(Ordering::Relaxed),
+            _ => 0,
+        }
+    }
+
+    /// Writes `record`, and forwards it to the other loggers.
+    fn emit(&self, record: &Record) {
+        self.write(record);
+
+        #[cfg(feature = "kv")]
+        if let Some(forwarder) = &self.forwarder {
+            forwarder.log(record);
+        }
+    }
+
+    fn write(&self, record: &Record) {
+        match &self.output {
+            Output::Direct { sink, options } => {
+                // Render the line first so that it is written with a single call.
+                let mut buf = Vec::new();
+                if crate::write_event(&mut buf, record, options).is_ok() {
+                    sink.lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .write_line(&buf);
+                }
+            }
+            Output::Deferred(background) => background.send(record),
+            Output::Fixed { sink, buf, dropped } => {
+                let mut buf = buf.lock().unwrap_or_else(PoisonError::into_inner);
+                match crate::fixed::encode(&mut buf, Utc::now(), record) {
+                    Ok(len) => sink
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .write_line(&buf[..len]),
+                    Err(_) => {
+                        dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata) && self.target_filter.matches(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.matches(record) {
+            return;
+        }
+
+        if let Some(sampler) = &self.sampler {
+            let (keep, change) = sampler.sample(record.level());
+            if let Some(change) = change {
+                self.write(
+                    &Record::builder()
+                        .args(format_args!("{}", change.message()))
+                        .level(log::Level::Warn)
+                        .target("ecs_logger")
+                        .module_path_static(Some(module_path!()))
+                        .file_static(Some(file!()))
+                        .line(Some(line!()))
+                        .build(),
+                );
+            }
+            if !keep {
+                return;
+            }
+        }
+
+        if self.error_stack_trace && record.level() == log::Level::Error {
+            crate::error::with_stack_trace(|| self.emit(record));
+        } else {
+            self.emit(record);
+        }
+    }
+
+    fn flush(&self) {
+        match &self.output {
+            // The exiting thread may hold the lock already, e.g. if a subscriber panicked.
+            Output::Direct { sink, .. } | Output::Fixed { sink, .. }
+                if crate::exit::is_exiting() =>
+            {
+                match sink.try_lock() {
+                    Ok(mut sink) => {
+                        let _ = sink.flush();
+                    }
+                    Err(TryLockError::Poisoned(e)) => {
+                        let _ = e.into_inner().flush();
+                    }
+                    Err(TryLockError::WouldBlock) => {}
+                }
+            }
+            Output::Direct { sink, .. } | Output::Fixed { sink, .. } => {
+                let _ = sink.lock().unwrap_or_else(PoisonError::into_inner).flush();
+            }
+            Output::Deferred(background) if crate::exit::is_exiting() => {
+                background.flush_timeout(EXIT_FLUSH_TIMEOUT)
+            }
+            Output::Deferred(background) => background.flush(),
+        }
+
+        #[cfg(feature = "kv")]
+        if let Some(forwarder) = &self.forwarder {
+            forwarder.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SharedBuf;
+
+    fn log(logger: &Logger, level: log::Level, target: &str) {
+        logger.log(
+            &Record::builder()
+                .args(format_args!("hello"))
+                .level(level)
+                .target(target)
+                .build(),
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .parse_filters("info,my_app=debug")
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        assert_eq!(logger.filter(), LevelFilter::Debug);
+
+        log(&logger, log::Level::Info, "other");
+        log(&logger, log::Level::Debug, "other");
+        log(&logger, log::Level::Debug, "my_app");
+
+        let targets = buf
+            .lines()
+            .iter()
+            .map(|v| {
+                v["log.origin"]["rust"]["target"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(targets, vec!["other", "my_app"]);
+    }
+
+    #[test]
+    fn test_target_globs() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Trace)
+            .deny_target("h2::*")
+            .deny_target("rustls::*")
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Info, "h2::proto");
+        log(&logger, log::Level::Info, "rustls::conn");
+        log(&logger, log::Level::Info, "my_app");
+
+        assert!(!logger.enabled(&Metadata::builder().target("h2::codec").build()));
+        assert_eq!(buf.lines().len(), 1);
+        assert_eq!(buf.lines()[0]["log.origin"]["rust"]["target"], "my_app");
+    }
+
+    #[test]
+    fn test_error_stack_trace() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Info)
+            .error_stack_trace(true)
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Info, "my_app");
+        log(&logger, log::Level::Error, "my_app");
+        let chain = serde_json::json!({ "error.stack_trace": "outer\nCaused by: inner" });
+        crate::extra_fields::with_scoped_fields(chain.as_object().unwrap().clone(), || {
+            log(&logger, log::Level::Error, "my_app")
+        });
+
+        let lines = buf.lines();
+        assert!(lines[0].get("error.stack_trace").is_none());
+        assert!(lines[1]["error.stack_trace"].is_string());
+        assert!(lines[2]["error.stack_trace"]
+            .as_str()
+            .unwrap()
+            .starts_with("outer\nCaused by: inner\n\n"));
+    }
+
+    #[test]
+    fn test_deferred_formatting() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Info)
+            .deferred_formatting(1)
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        let fields = serde_json::json!({ "scoped": true });
+        crate::extra_fields::with_scoped_fields(fields.as_object().unwrap().clone(), || {
+            for _ in 0..3 {
+                log(&logger, log::Level::Info, "my_app");
+            }
+        });
+        logger.flush();
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["@timestamp"], crate::timestamp::MOCK_TIMESTAMP);
+        assert_eq!(lines[0]["message"], "hello");
+        assert_eq!(lines[2]["scoped"], true);
+        assert_eq!(
+            lines[2]["process"]["thread"]["name"],
+            std::thread::current().name().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_service() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Info)
+            .service("my-app", "1.2.3", "production")
+            .format_options(FormatOptions::new().process_metadata(true))
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Info, "my_app");
+
+        let lines = buf.lines();
+        assert_eq!(
+            lines[0]["service"],
+            serde_json::json!({ "name": "my-app", "version": "1.2.3", "environment": "production" })
+        );
+        assert!(lines[0]["process"]["pid"].is_number());
+    }
+
+    #[test]
+    fn test_fixed_buffer() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Info)
+            .fixed_buffer(256)
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Info, "my_app");
+        let long = "a".repeat(256);
+        log(&logger, log::Level::Info, &long);
+        log(&logger, log::Level::Debug, "my_app");
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["message"], "hello");
+        assert_eq!(lines[0]["log.origin"]["rust"]["target"], "my_app");
+        assert_eq!(logger.dropped_events(), 1);
+    }
+
+    #[test]
+    fn test_deferred_trace_context() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .filter_level(LevelFilter::Info)
+            .deferred_formatting(1)
+            .format_options(FormatOptions::new().trace_context(|| {
+                let thread = std::thread::current();
+                Some(crate::TraceContext::new(thread.name()?))
+            }))
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Info, "my_app");
+        logger.flush();
+
+        // Called on the logging thread, not on the writer thread
+        assert_eq!(
+            buf.lines()[0]["trace.id"],
+            std::thread::current().name().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .subscribe(move |event| sender.send(event.to_string()).unwrap())
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Error, "my_app");
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(format!("{}\n", event), buf.content());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_suppress_message() {
+        let buf = SharedBuf::default();
+        let logger = Builder::new()
+            .suppress_message("hel*")
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        log(&logger, log::Level::Error, "my_app");
+
+        assert!(buf.lines().is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! Metric events with numeric fields named by the application
+
+use crate::extra_fields::with_scoped_fields;
+use log::Level;
+use serde_json::{Map, Value};
+use std::panic::Location;
+
+/// Event with `event.kind: "metric"` and numeric fields named by the application, for shipping a few counters and gauges
+/// through the logger without a separate metrics stack.
+///
+/// The values are fields named after the metrics, and the labels are in `labels`, like the events of `MetricsBridge`
+/// with the `metrics` feature. The message is `Metrics` unless set with [`message`](Self::message).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::MetricEvent;
+///
+/// ecs_logger::init();
+///
+/// MetricEvent::new()
+///     .counter("http.requests", 1024)
+///     .gauge("queue.length", 12.0)
+///     .label("region", "eu-west-1")
+///     .log();
+/// // {"log.level":"INFO","message":"Metrics","event.kind":"metric","http.requests":1024,"queue.length":12.0,
+/// //  "labels":{"region":"eu-west-1"},...}
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricEvent {
+    values: Map<String, Value>,
+    labels: Map<String, Value>,
+    message: Option<String>,
+}
+
+impl MetricEvent {
+    /// Creates an event without any metric.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the field `name` to the value of a counter.
+    pub fn counter(mut self, name: impl Into<String>, value: u64) -> Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the field `name` to the value of a gauge.
+    ///
+    /// NaN and infinities are logged as `null`, because they are not valid in JSON.
+    pub fn gauge(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds a label to `labels`.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into().into());
+        self
+    }
+
+    /// Sets the message of the log record.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Returns the ECS fields of the event.
+    pub fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        fields.insert("event.kind".to_string(), "metric".into());
+        if !self.labels.is_empty() {
+            fields.insert("labels".to_string(), Value::Object(self.labels.clone()));
+        }
+        fields.extend(self.values.clone());
+        fields
+    }
+
+    /// Logs the event at `INFO` level.
+    ///
+    /// Like [`ResultExt`](crate::ResultExt), the event has the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn log(self) {
+        let location = Location::caller();
+        let message = self.message.as_deref().unwrap_or("Metrics");
+
+        with_scoped_fields(self.fields(), || {
+            crate::log_at(Level::Info, format_args!("{}", message), location)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields() {
+        let event = MetricEvent::new()
+            .counter("http.requests", 3)
+            .gauge("queue.length", 1.5)
+            .gauge("ratio", f64::NAN)
+            .label("region", "eu-west-1");
+
+        assert_eq!(
+            Value::Object(event.fields()),
+            json!({
+                "event.kind": "metric",
+                "labels": { "region": "eu-west-1" },
+                "http.requests": 3,
+                "queue.length": 1.5,
+                "ratio": null,
+            })
+        );
+        assert_eq!(
+            Value::Object(MetricEvent::new().fields()),
+            json!({ "event.kind": "metric" })
+        );
+    }
+}
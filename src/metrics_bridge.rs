@@ -0,0 +1,212 @@
+//! Bridge from the `metrics` crate to ECS metric events
+
+use crate::extra_fields::with_scoped_fields;
+use log::Level;
+use metrics::{
+    Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SetRecorderError, SharedString,
+    Unit,
+};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Recorder of the [`metrics`](https://docs.rs/metrics) crate which logs the current values of the counters and gauges as
+/// ECS documents with `event.kind: "metric"`.
+///
+/// Each [`report`](Self::report) logs one event per distinct set of labels. The event has the values of the metrics as fields
+/// named after the metrics, and the labels in `labels`. Histograms are not supported and their samples are discarded.
+///
+/// Available with the `metrics` feature.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// ecs_logger::init();
+///
+/// ecs_logger::MetricsBridge::new()
+///     .install(Duration::from_secs(60))
+///     .unwrap();
+///
+/// metrics::counter!("http.requests", "method" => "GET").increment(1);
+/// metrics::gauge!("queue.length").set(12.0);
+/// // Every minute:
+/// // {"log.level":"INFO","message":"Metrics snapshot","event.kind":"metric","queue.length":12.0,...}
+/// // {"log.level":"INFO","message":"Metrics snapshot","event.kind":"metric","labels":{"method":"GET"},"http.requests":1,...}
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricsBridge {
+    metrics: Arc<Mutex<HashMap<Key, Metric>>>,
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Arc<AtomicU64>),
+    /// Bits of the `f64` value
+    Gauge(Arc<AtomicU64>),
+}
+
+impl MetricsBridge {
+    /// Creates a bridge without any metric.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs the bridge as the global recorder of the `metrics` crate, and spawns a thread which calls [`report`](Self::report)
+    /// every `interval`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SetRecorderError`] if a global recorder has already been installed.
+    #[track_caller]
+    pub fn install(self, interval: Duration) -> Result<(), SetRecorderError<Self>> {
+        let location = Location::caller();
+        let bridge = self.clone();
+
+        metrics::set_global_recorder(self)?;
+        std::thread::Builder::new()
+            .name("ecs-logger-metrics".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                bridge.report_at(location);
+            })
+            .expect("failed to spawn the metrics thread");
+
+        Ok(())
+    }
+
+    /// Logs the current values of the metrics at `INFO` level.
+    ///
+    /// Like [`ResultExt`](crate::ResultExt), the events have the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn report(&self) {
+        self.report_at(Location::caller());
+    }
+
+    fn report_at(&self, location: &Location) {
+        for fields in self.snapshot() {
+            with_scoped_fields(fields, || {
+                crate::log_at(Level::Info, format_args!("Metrics snapshot"), location)
+            });
+        }
+    }
+
+    /// Returns the fields of the metric events, grouped by the labels
+    fn snapshot(&self) -> Vec<Map<String, Value>> {
+        let metrics = self.metrics.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut groups = BTreeMap::<Vec<(&str, &str)>, BTreeMap<&str, Value>>::new();
+        for (key, metric) in &*metrics {
+            let mut labels = key
+                .labels()
+                .map(|l| (l.key(), l.value()))
+                .collect::<Vec<_>>();
+            labels.sort();
+
+            let value = match metric {
+                Metric::Counter(v) => v.load(Ordering::Relaxed).into(),
+                Metric::Gauge(v) => f64::from_bits(v.load(Ordering::Relaxed)).into(),
+            };
+            groups.entry(labels).or_default().insert(key.name(), value);
+        }
+
+        groups
+            .into_iter()
+            .map(|(labels, values)| {
+                let mut fields = Map::new();
+                fields.insert("event.kind".to_string(), "metric".into());
+                if !labels.is_empty() {
+                    let labels = labels
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.into()))
+                        .collect();
+                    fields.insert("labels".to_string(), Value::Object(labels));
+                }
+                for (name, value) in values {
+                    fields.insert(name.to_string(), value);
+                }
+                fields
+            })
+            .collect()
+    }
+
+    fn register(&self, key: &Key, new: impl FnOnce() -> Metric) -> Metric {
+        self.metrics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.clone())
+            .or_insert_with(new)
+            .clone()
+    }
+}
+
+impl Recorder for MetricsBridge {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        match self.register(key, || Metric::Counter(Arc::default())) {
+            Metric::Counter(v) => Counter::from_arc(v),
+            Metric::Gauge(_) => Counter::noop(),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        match self.register(key, || {
+            Metric::Gauge(Arc::new(AtomicU64::new(0f64.to_bits())))
+        }) {
+            Metric::Gauge(v) => Gauge::from_arc(v),
+            Metric::Counter(_) => Gauge::noop(),
+        }
+    }
+
+    fn register_histogram(&self, _key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_snapshot() {
+        let bridge = MetricsBridge::new();
+        metrics::with_local_recorder(&bridge, || {
+            metrics::counter!("http.requests", "method" => "GET", "status" => "200").increment(2);
+            metrics::counter!("http.requests", "status" => "200", "method" => "GET").increment(1);
+            metrics::counter!("http.bytes", "method" => "GET", "status" => "200").increment(512);
+            metrics::gauge!("queue.length").set(12.0);
+            metrics::histogram!("latency").record(1.0);
+        });
+
+        let snapshot = bridge
+            .snapshot()
+            .into_iter()
+            .map(Value::Object)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            snapshot,
+            vec![
+                json!({
+                    "event.kind": "metric",
+                    "queue.length": 12.0,
+                }),
+                json!({
+                    "event.kind": "metric",
+                    "labels": { "method": "GET", "status": "200" },
+                    "http.bytes": 512,
+                    "http.requests": 3,
+                }),
+            ]
+        );
+    }
+}
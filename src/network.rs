@@ -0,0 +1,158 @@
+//! Network flow events
+
+use crate::connection::Endpoint;
+use crate::extra_fields::{to_json_map, with_scoped_fields};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::net::SocketAddr;
+use std::panic::Location;
+
+/// Summary of a network flow, logged as an ECS network event.
+///
+/// The event has the `source.*`, `destination.*`, and `network.*` fields,
+/// and is categorized with `event.category: ["network"]` and `event.type: ["connection"]`.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::NetworkEvent;
+///
+/// ecs_logger::init();
+///
+/// NetworkEvent::new(
+///     "192.0.2.1:51234".parse().unwrap(),
+///     "198.51.100.7:443".parse().unwrap(),
+/// )
+/// .transport("tcp")
+/// .protocol("http")
+/// .bytes(5120)
+/// .log();
+/// // {"log.level":"INFO","message":"tcp 192.0.2.1:51234 -> 198.51.100.7:443","source.ip":"192.0.2.1","source.port":51234,...}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkEvent {
+    source: SocketAddr,
+    destination: SocketAddr,
+    transport: Option<String>,
+    protocol: Option<String>,
+    bytes: Option<u64>,
+}
+
+impl NetworkEvent {
+    /// Creates a summary of a flow from `source` to `destination`.
+    pub fn new(source: SocketAddr, destination: SocketAddr) -> Self {
+        NetworkEvent {
+            source,
+            destination,
+            transport: None,
+            protocol: None,
+            bytes: None,
+        }
+    }
+
+    /// Sets the transport layer protocol in lowercase, e.g. `tcp` or `udp`, logged as `network.transport`.
+    pub fn transport(mut self, transport: impl Into<String>) -> Self {
+        self.transport = Some(transport.into());
+        self
+    }
+
+    /// Sets the application layer protocol in lowercase, e.g. `http` or `dns`, logged as `network.protocol`.
+    pub fn protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.protocol = Some(protocol.into());
+        self
+    }
+
+    /// Sets the total number of bytes transferred in both directions, logged as `network.bytes`.
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// Returns the ECS fields of the event with dotted keys.
+    pub fn fields(&self) -> Map<String, Value> {
+        to_json_map(self).expect("NetworkEvent should be converted into a JSON object")
+    }
+
+    /// Logs the event at the `INFO` level.
+    ///
+    /// Like [`AccessLog::log`](crate::AccessLog::log), the event has the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn log(self) {
+        let location = Location::caller();
+        let message = match &self.transport {
+            Some(transport) => format!("{} {} -> {}", transport, self.source, self.destination),
+            None => format!("{} -> {}", self.source, self.destination),
+        };
+
+        with_scoped_fields(self.fields(), || {
+            crate::log_at(log::Level::Info, format_args!("{}", message), location)
+        });
+    }
+}
+
+impl Serialize for NetworkEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("event.kind", "event")?;
+        map.serialize_entry("event.category", &["network"])?;
+        map.serialize_entry("event.type", &["connection"])?;
+        Endpoint::from(self.source).serialize_entries(&mut map, "source")?;
+        Endpoint::from(self.destination).serialize_entries(&mut map, "destination")?;
+        if let Some(transport) = &self.transport {
+            map.serialize_entry("network.transport", transport)?;
+        }
+        if let Some(protocol) = &self.protocol {
+            map.serialize_entry("network.protocol", protocol)?;
+        }
+        if let Some(bytes) = self.bytes {
+            map.serialize_entry("network.bytes", &bytes)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields() {
+        let fields = NetworkEvent::new(
+            "192.0.2.1:51234".parse().unwrap(),
+            "[2001:db8::1]:53".parse().unwrap(),
+        )
+        .transport("udp")
+        .protocol("dns")
+        .bytes(512)
+        .fields();
+
+        assert_eq!(
+            Value::Object(fields),
+            json!({
+                "event.kind": "event",
+                "event.category": ["network"],
+                "event.type": ["connection"],
+                "source.ip": "192.0.2.1",
+                "source.port": 51234,
+                "destination.ip": "2001:db8::1",
+                "destination.port": 53,
+                "network.transport": "udp",
+                "network.protocol": "dns",
+                "network.bytes": 512,
+            })
+        );
+    }
+
+    #[test]
+    fn test_fields_minimal() {
+        let fields = NetworkEvent::new(
+            "192.0.2.1:51234".parse().unwrap(),
+            "198.51.100.7:443".parse().unwrap(),
+        )
+        .fields();
+
+        assert_eq!(fields.len(), 7);
+        assert!(!fields.contains_key("network.transport"));
+    }
+}
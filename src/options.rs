@@ -0,0 +1,816 @@
+//! Options which control how log events are rendered
+
+use crate::alias::AliasGroup;
+use crate::categorization::Categorization;
+use crate::data_stream::DataStream;
+use crate::dataset::EventDataset;
+use crate::ecs::{Cloud, Container, EcsVersion, Host, Process, Service};
+use crate::geo::{GeoResolver, SharedGeoResolver};
+use crate::kubernetes::KubernetesMetadata;
+use crate::labels::Labels;
+use crate::origin::SourcePathHasher;
+use crate::syslog::SyslogFacility;
+use crate::trace::{TraceContext, TraceContextProvider};
+use chrono::SecondsFormat;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+type JsonMap = Map<String, Value>;
+
+/// Options which control how [`format_with`](crate::format_with) renders log events.
+///
+/// The default options produce exactly the same output as [`format`](crate::format()).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{FormatOptions, KeyStyle};
+///
+/// let options = FormatOptions::new()
+///     .key_style(KeyStyle::Nested)
+///     .timestamp_format(chrono::SecondsFormat::Millis)
+///     .origin(false);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub(crate) key_style: KeyStyle,
+    pub(crate) ecs_version: EcsVersion,
+    pub(crate) timestamp_format: SecondsFormat,
+    pub(crate) origin: bool,
+    pub(crate) log_logger: bool,
+    pub(crate) syslog_facility: Option<SyslogFacility>,
+    pub(crate) timestamp: bool,
+    pub(crate) normalize_path_separators: bool,
+    pub(crate) source_root: Option<PathBuf>,
+    pub(crate) source_path_hasher: SourcePathHasher,
+    pub(crate) event_outcome: bool,
+    pub(crate) event_created: bool,
+    pub(crate) event_original: bool,
+    pub(crate) field_aliases: Vec<AliasGroup>,
+    pub(crate) message_hook: Option<MessageHook>,
+    pub(crate) service: Option<Service<'static>>,
+    pub(crate) service_node_name: Option<Cow<'static, str>>,
+    pub(crate) host: Option<&'static Host<'static>>,
+    pub(crate) process: Option<&'static Process<'static>>,
+    pub(crate) cloud: Option<&'static Cloud<'static>>,
+    pub(crate) container: Option<&'static Container<'static>>,
+    pub(crate) agent: bool,
+    pub(crate) trace_context: Option<TraceContextProvider>,
+    pub(crate) event_dataset: EventDataset,
+    pub(crate) event_dataset_targets: Vec<(String, String)>,
+    pub(crate) event_categorization: Option<Categorization>,
+    pub(crate) labels: Option<Labels>,
+    pub(crate) kubernetes: Option<KubernetesMetadata>,
+    pub(crate) data_stream: Option<DataStream>,
+    pub(crate) geo_resolver: Option<SharedGeoResolver>,
+    pub(crate) related_fields: bool,
+    #[cfg(feature = "kv")]
+    pub(crate) nest_kv_keys: bool,
+}
+
+/// Function which transforms the rendered message.
+#[derive(Clone)]
+pub(crate) struct MessageHook(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl fmt::Debug for MessageHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageHook").finish_non_exhaustive()
+    }
+}
+
+impl MessageHook {
+    pub(crate) fn apply(&self, message: &str) -> String {
+        (self.0)(message)
+    }
+}
+
+/// How the keys of the JSON document are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyStyle {
+    /// Top-level ECS logging fields are dotted (e.g. `"log.level"`) and everything else is kept as is.
+    ///
+    /// This is the layout recommended by the [ECS Logging spec](https://github.com/elastic/ecs-logging/tree/master/spec).
+    #[default]
+    Dotted,
+
+    /// Every dotted key is expanded into nested objects (e.g. `{"log":{"level":"ERROR"}}`).
+    ///
+    /// A key which conflicts with a non-object value, e.g. `a.b` with `a`, is kept dotted next to it (e.g. `{"a":1,"a.b":2}`).
+    Nested,
+
+    /// Every nested object is collapsed into dotted keys (e.g. `"log.origin.file.line"`).
+    Flat,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            key_style: KeyStyle::default(),
+            ecs_version: EcsVersion::default(),
+            timestamp_format: SecondsFormat::AutoSi,
+            origin: true,
+            log_logger: false,
+            syslog_facility: None,
+            timestamp: true,
+            normalize_path_separators: false,
+            source_root: None,
+            source_path_hasher: SourcePathHasher::default(),
+            event_outcome: false,
+            event_created: false,
+            event_original: false,
+            field_aliases: Vec::new(),
+            message_hook: None,
+            service: None,
+            service_node_name: None,
+            #[cfg(feature = "host")]
+            host: Some(crate::host::host()),
+            #[cfg(not(feature = "host"))]
+            host: None,
+            process: None,
+            cloud: None,
+            container: None,
+            agent: false,
+            trace_context: None,
+            event_dataset: EventDataset::default(),
+            event_dataset_targets: Vec::new(),
+            event_categorization: None,
+            labels: None,
+            kubernetes: None,
+            data_stream: None,
+            geo_resolver: None,
+            related_fields: false,
+            #[cfg(feature = "kv")]
+            nest_kv_keys: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Creates options which produce the default ECS log output.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how the keys of the JSON document are laid out.
+    ///
+    /// Defaults to [`KeyStyle::Dotted`].
+    pub fn key_style(mut self, key_style: KeyStyle) -> Self {
+        self.key_style = key_style;
+        self
+    }
+
+    /// Sets the version of the ECS schema which the events conform to, and which is written to `ecs.version`.
+    ///
+    /// With [`EcsVersion::V8`], the fields renamed in ECS 8.0 are moved to their new names, even if they are set with extra fields,
+    /// e.g. `log.original` to `event.original`. Use [`alias_fields`](Self::alias_fields) to emit both names during a migration.
+    ///
+    /// Defaults to [`EcsVersion::V1`], i.e. `1.12.1`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{ecs::EcsVersion, FormatOptions};
+    ///
+    /// let options = FormatOptions::new().ecs_version(EcsVersion::V8);
+    /// ```
+    pub fn ecs_version(mut self, version: EcsVersion) -> Self {
+        self.ecs_version = version;
+        self
+    }
+
+    /// Sets the precision of the `@timestamp` field.
+    ///
+    /// Defaults to [`SecondsFormat::AutoSi`].
+    pub fn timestamp_format(mut self, timestamp_format: SecondsFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Sets whether the `@timestamp` field is included.
+    ///
+    /// When disabled, a process-wide sequence number is emitted as `event.sequence` instead,
+    /// so that a forwarder with a reliable clock can stamp the events later with [`stamp_timestamp`](crate::ecs::stamp_timestamp).
+    ///
+    /// Defaults to `true`.
+    pub fn timestamp(mut self, enabled: bool) -> Self {
+        self.timestamp = enabled;
+        self
+    }
+
+    /// Sets whether the time the log line is encoded is included as `event.created`.
+    ///
+    /// `@timestamp` is the time the record was logged, so the two differ when the line is encoded later,
+    /// e.g. with [`Builder::deferred_formatting`](crate::Builder::deferred_formatting) or [`format_at`](crate::format_at).
+    /// Ignored when [`timestamp`](Self::timestamp) is disabled.
+    ///
+    /// Defaults to `false`.
+    pub fn event_created(mut self, enabled: bool) -> Self {
+        self.event_created = enabled;
+        self
+    }
+
+    /// Sets whether the target of the record is included as `log.logger`, the name of the logger defined by ECS.
+    ///
+    /// The target is also included as `log.origin.rust.target` regardless of this option.
+    ///
+    /// Defaults to `false`.
+    pub fn log_logger(mut self, enabled: bool) -> Self {
+        self.log_logger = enabled;
+        self
+    }
+
+    /// Adds the `log.syslog.*` fields of the records sent with the syslog `facility`, e.g. [`SyslogFacility::Local0`],
+    /// so the JSON document agrees with the syslog header when the output is shipped through syslog.
+    ///
+    /// The severity is derived from the level of the record, and the priority is `facility * 8 + severity`.
+    /// A numeric facility code, e.g. from a configuration file, is converted with [`SyslogFacility::from_code`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{FormatOptions, SyslogFacility};
+    ///
+    /// let options = FormatOptions::new().syslog_facility(SyslogFacility::Local0);
+    /// // For a warning:
+    /// // "log.syslog.facility.code":16,"log.syslog.facility.name":"local0","log.syslog.priority":132,
+    /// // "log.syslog.severity.code":4,"log.syslog.severity.name":"Warning"
+    /// ```
+    pub fn syslog_facility(mut self, facility: SyslogFacility) -> Self {
+        self.syslog_facility = Some(facility);
+        self
+    }
+
+    /// Sets whether the unformatted template of the message is included as `event.original`.
+    ///
+    /// The template is known for the messages logged with [`log_templated!`](crate::log_templated),
+    /// and for the messages without arguments, whose template is the message itself.
+    ///
+    /// Defaults to `false`.
+    pub fn event_original(mut self, enabled: bool) -> Self {
+        self.event_original = enabled;
+        self
+    }
+
+    /// Sets whether the `log.origin` field is included.
+    ///
+    /// Defaults to `true`.
+    pub fn origin(mut self, enabled: bool) -> Self {
+        self.origin = enabled;
+        self
+    }
+
+    /// Sets whether backslashes in `log.origin.rust.file_path` are replaced with forward slashes.
+    ///
+    /// Enable this to get the same values from Windows and Unix builds.
+    ///
+    /// Defaults to `false`.
+    pub fn normalize_path_separators(mut self, enabled: bool) -> Self {
+        self.normalize_path_separators = enabled;
+        self
+    }
+
+    /// Sets the directory which `log.origin.rust.file_path` is made relative to.
+    ///
+    /// Paths outside of `root` are left untouched.
+    /// This gives stable values regardless of where the binary was built.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::FormatOptions;
+    ///
+    /// let options = FormatOptions::new().source_root(env!("CARGO_MANIFEST_DIR"));
+    /// ```
+    pub fn source_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.source_root = Some(root.into());
+        self
+    }
+
+    /// Replaces the file name, file path, module path, and function name in `log.origin` with stable hashes.
+    ///
+    /// `log.origin.rust.target` and `log.logger` are hashed as well when the target equals the module path, which is the default target of the [`log`] macros.
+    /// The hashes are SipHash-2-4 keyed with the 128-bit secret `key`, in 16 hexadecimal digits.
+    /// Without the key, they cannot be computed for guessed paths, nor inverted.
+    /// Use [`source_path_mapping`](Self::source_path_mapping) to keep the mapping from hashes to original values.
+    pub fn hash_source_paths(mut self, key: [u8; 16]) -> Self {
+        self.source_path_hasher.set_key(key);
+        self
+    }
+
+    /// Sets the side channel which receives a `<hash>\t<original value>` line the first time each hash is emitted.
+    ///
+    /// This can be called before or after [`hash_source_paths`](Self::hash_source_paths), and has no effect without it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ecs_logger::FormatOptions;
+    ///
+    /// let key: [u8; 16] = std::fs::read("source-path.key").unwrap().try_into().unwrap();
+    /// let options = FormatOptions::new()
+    ///     .hash_source_paths(key)
+    ///     .source_path_mapping(std::fs::File::create("source-paths.tsv").unwrap());
+    /// ```
+    pub fn source_path_mapping(mut self, writer: impl std::io::Write + Send + 'static) -> Self {
+        self.source_path_hasher.set_mapping(Box::new(writer));
+        self
+    }
+
+    /// Sets whether `event.outcome` is set to `failure` for `ERROR` records automatically.
+    ///
+    /// An outcome set explicitly, e.g. with [`with_outcome`](crate::with_outcome) or extra fields, is kept as is.
+    ///
+    /// Defaults to `false`.
+    pub fn event_outcome(mut self, enabled: bool) -> Self {
+        self.event_outcome = enabled;
+        self
+    }
+
+    /// Sets how `event.dataset` is set.
+    ///
+    /// A dataset set explicitly, e.g. with extra fields, is kept as is.
+    ///
+    /// Defaults to [`EventDataset::None`].
+    pub fn event_dataset(mut self, dataset: EventDataset) -> Self {
+        self.event_dataset = dataset;
+        self
+    }
+
+    /// Sets `event.dataset` of the records whose target is `target` or in it, e.g. `my_app::http::client` for `my_app::http`.
+    ///
+    /// This takes precedence over [`event_dataset`](Self::event_dataset), and the most specific target wins.
+    pub fn event_dataset_for(
+        mut self,
+        target: impl Into<String>,
+        dataset: impl Into<String>,
+    ) -> Self {
+        self.event_dataset_targets
+            .push((target.into(), dataset.into()));
+        self
+    }
+
+    /// Sets the `event.kind`, `event.category`, `event.type`, and `event.outcome` fields of every record.
+    ///
+    /// Fields set explicitly, e.g. with [`Categorization::in_scope`] or extra fields, are kept as is,
+    /// and so is the `failure` outcome set by [`event_outcome`](Self::event_outcome).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{Categorization, FormatOptions};
+    ///
+    /// let options = FormatOptions::new()
+    ///     .event_categorization(Categorization::new().kind("event").category("web"));
+    /// ```
+    pub fn event_categorization(mut self, categorization: Categorization) -> Self {
+        self.event_categorization = Some(categorization);
+        self
+    }
+
+    /// Sets the `labels` of every record.
+    ///
+    /// The labels set for the current scope with [`Labels::in_scope`], or with extra fields, are merged into them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{FormatOptions, Labels};
+    ///
+    /// let options = FormatOptions::new().labels(Labels::new().label("region", "eu-west-1"));
+    /// ```
+    pub fn labels(mut self, labels: Labels) -> Self {
+        self.labels = (!labels.is_empty()).then_some(labels);
+        self
+    }
+
+    /// Sets the Kubernetes pod whose `orchestrator.*` and `kubernetes.*` fields are added to every record.
+    ///
+    /// Nothing is added if `metadata` is empty, e.g. when [`KubernetesMetadata::from_env`] runs outside of Kubernetes.
+    /// Fields set explicitly, e.g. with extra fields, take precedence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{FormatOptions, KubernetesMetadata};
+    ///
+    /// let options = FormatOptions::new().kubernetes(KubernetesMetadata::from_env());
+    /// ```
+    pub fn kubernetes(mut self, metadata: KubernetesMetadata) -> Self {
+        self.kubernetes = (!metadata.is_empty()).then_some(metadata);
+        self
+    }
+
+    /// Sets the `data_stream.type`, `data_stream.dataset`, and `data_stream.namespace` fields of every record.
+    ///
+    /// Fields set explicitly, e.g. with extra fields, take precedence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{DataStream, FormatOptions};
+    ///
+    /// let options = FormatOptions::new().data_stream(DataStream::new().dataset("my_app"));
+    /// ```
+    pub fn data_stream(mut self, data_stream: DataStream) -> Self {
+        self.data_stream = Some(data_stream);
+        self
+    }
+
+    /// Sets a lookup which adds the `*.geo.*` fields of `client.ip`, `source.ip`, `server.ip`, and `destination.ip`.
+    ///
+    /// See [`GeoResolver`] for the details. Calling this again replaces the previous resolver.
+    pub fn geo_resolver(mut self, resolver: impl GeoResolver + 'static) -> Self {
+        self.geo_resolver = Some(SharedGeoResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Sets whether the IP addresses, the users, and the hashes found in the fields of every event are aggregated into
+    /// the `related.ip`, `related.user`, and `related.hash` arrays, as ECS recommends for pivoting between events.
+    ///
+    /// They are collected from the fields named e.g. `source.ip`, `user.name`, `user.target.id`, and `file.hash.sha256`,
+    /// after the extra fields are merged. The values set in `related.*` explicitly are kept.
+    ///
+    /// Defaults to `false`.
+    pub fn related_fields(mut self, enabled: bool) -> Self {
+        self.related_fields = enabled;
+        self
+    }
+
+    /// Sets whether the dotted keys of the records' key-values, e.g. `http.request.method`, are expanded into nested objects
+    /// before they are merged into the event, so they merge with the nested extra fields and scoped fields of the same objects.
+    ///
+    /// When disabled, the keys are added as they are given. In both cases the [`KeyStyle`] applies to the whole event afterwards,
+    /// e.g. [`KeyStyle::Flat`] turns the expanded keys back into dotted ones.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::FormatOptions;
+    ///
+    /// let options = FormatOptions::new().nest_kv_keys(true);
+    /// // log::info!(http.request.method = "GET"; "Request")
+    /// // {"log.level":"INFO","message":"Request","http":{"request":{"method":"GET"}},...}
+    /// ```
+    #[cfg(feature = "kv")]
+    pub fn nest_kv_keys(mut self, enabled: bool) -> Self {
+        self.nest_kv_keys = enabled;
+        self
+    }
+
+    /// Emits both the ECS 1.x and 8.x names of the fields of `group`. May be called once per group.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{AliasGroup, FormatOptions};
+    ///
+    /// let options = FormatOptions::new()
+    ///     .alias_fields(AliasGroup::LogOriginal)
+    ///     .alias_fields(AliasGroup::ProcessParentPid);
+    /// ```
+    pub fn alias_fields(mut self, group: AliasGroup) -> Self {
+        if !self.field_aliases.contains(&group) {
+            self.field_aliases.push(group);
+        }
+        self
+    }
+
+    /// Sets a function which transforms the rendered message before it is written to the `message` field.
+    ///
+    /// This is useful to rewrite messages produced by third-party crates. Calling this again replaces the previous hook.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::FormatOptions;
+    ///
+    /// // Strip ANSI color codes
+    /// let options = FormatOptions::new().message_hook(|message| {
+    ///     let mut stripped = String::with_capacity(message.len());
+    ///     let mut chars = message.chars();
+    ///     while let Some(c) = chars.next() {
+    ///         if c == '\u{1b}' {
+    ///             chars.by_ref().find(|c| c.is_ascii_alphabetic());
+    ///         } else {
+    ///             stripped.push(c);
+    ///         }
+    ///     }
+    ///     stripped
+    /// });
+    /// ```
+    pub fn message_hook(mut self, hook: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.message_hook = Some(MessageHook(Arc::new(hook)));
+        self
+    }
+
+    /// Sets the `service.*` fields added to every event.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{ecs::Service, FormatOptions};
+    ///
+    /// let options = FormatOptions::new().service(Service {
+    ///     name: Some("my-app".into()),
+    ///     version: Some(env!("CARGO_PKG_VERSION").into()),
+    ///     environment: None,
+    ///     node: None,
+    /// });
+    /// ```
+    pub fn service(mut self, service: Service<'static>) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    /// Sets the `service.node.name` field added to every event, e.g. the name of the pod or the id of the instance,
+    /// so that the replicas of a horizontally scaled service can be told apart.
+    ///
+    /// It is added on top of the other `service.*` fields set with [`service`](Self::service), and replaces `service.node` set there.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::FormatOptions;
+    ///
+    /// let options = FormatOptions::new().service_node_name("checkout-7d9f8b6c5-x2k4q");
+    /// ```
+    pub fn service_node_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.service_node_name = Some(name.into());
+        self
+    }
+
+    /// Sets whether the `host.*` fields are added to every event. Disable this for privacy-sensitive deployments.
+    ///
+    /// They are `host.name`, `host.hostname`, `host.architecture`, `host.os.family`, `host.os.name`, and `host.os.version`,
+    /// where the OS is read from `/etc/os-release` on Linux.
+    /// The metadata is collected the first time the options are created, and cached for the lifetime of the process.
+    /// This method is available when the `host` feature is enabled.
+    ///
+    /// Defaults to `true`.
+    #[cfg(feature = "host")]
+    pub fn host_metadata(mut self, enabled: bool) -> Self {
+        self.host = enabled.then(crate::host::host);
+        self
+    }
+
+    /// Sets whether `process.pid`, `process.name`, and `process.executable` are added to every event.
+    ///
+    /// The metadata is collected the first time this is enabled, and cached for the lifetime of the process.
+    ///
+    /// Defaults to `false`.
+    pub fn process_metadata(mut self, enabled: bool) -> Self {
+        self.process = enabled.then(crate::process::process);
+        self
+    }
+
+    /// Sets whether `agent.name` (`ecs-logger`) and `agent.version` (the version of this crate) are added to every event,
+    /// so that the documents produced by this crate can be told apart from those of other shippers.
+    ///
+    /// Defaults to `false`.
+    pub fn agent(mut self, enabled: bool) -> Self {
+        self.agent = enabled;
+        self
+    }
+
+    /// Sets whether `container.id` and `container.runtime` are added to every event.
+    ///
+    /// They are read from `/proc/self/cgroup`, or from `/proc/self/mountinfo` under cgroup v2, the first time this is enabled,
+    /// and cached for the lifetime of the process. No fields are added outside of a container, or on platforms other than Linux.
+    ///
+    /// Defaults to `false`.
+    pub fn container_metadata(mut self, enabled: bool) -> Self {
+        self.container = if enabled {
+            crate::container::container()
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets whether `cloud.provider`, `cloud.region`, `cloud.availability_zone`, and `cloud.instance.id` are added to every event.
+    ///
+    /// The metadata is queried from the instance metadata service of AWS, Google Cloud, or Azure the first time this is enabled,
+    /// with a short timeout, and cached for the lifetime of the process. No fields are added outside of these clouds.
+    /// This replaces the fields set by [`cloud_metadata_from_env`](Self::cloud_metadata_from_env).
+    /// This method is available when the `cloud` feature is enabled.
+    ///
+    /// Defaults to `false`.
+    #[cfg(feature = "cloud")]
+    pub fn cloud_metadata(mut self, enabled: bool) -> Self {
+        self.cloud = if enabled { crate::cloud::cloud() } else { None };
+        self
+    }
+
+    /// Sets whether the `cloud.*` fields derived from the environment variables set by the cloud platforms are added to every event.
+    ///
+    /// This is a cheaper alternative to [`cloud_metadata`](Self::cloud_metadata) for environments where the metadata service is blocked, e.g. serverless platforms.
+    /// `cloud.provider` is detected from e.g. `AWS_REGION`, `ECS_CONTAINER_METADATA_URI`, `GOOGLE_CLOUD_PROJECT`, or `WEBSITE_SITE_NAME`,
+    /// and `cloud.region` and `cloud.project.id` are added where the variables tell them.
+    /// The variables are read the first time this is enabled. This replaces the fields set by [`cloud_metadata`](Self::cloud_metadata).
+    /// This method is available when the `cloud` feature is enabled.
+    ///
+    /// Defaults to `false`.
+    #[cfg(feature = "cloud")]
+    pub fn cloud_metadata_from_env(mut self, enabled: bool) -> Self {
+        self.cloud = if enabled {
+            crate::cloud::cloud_from_env()
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets a function which returns the trace context of the current thread, which is added as `trace.id`, `span.id`, and `transaction.id`.
+    ///
+    /// The function is called on the logging thread for each event, e.g. to read the current span of a tracing library.
+    /// Fields set explicitly, e.g. with [`TraceContext::in_scope`], take precedence. Calling this again replaces the previous function.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ecs_logger::{FormatOptions, TraceContext};
+    /// use std::cell::RefCell;
+    ///
+    /// thread_local! {
+    ///     static CURRENT_TRACE: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+    /// }
+    ///
+    /// let options = FormatOptions::new()
+    ///     .trace_context(|| CURRENT_TRACE.with(|trace| trace.borrow().clone()));
+    /// ```
+    pub fn trace_context(
+        mut self,
+        provider: impl Fn() -> Option<TraceContext> + Send + Sync + 'static,
+    ) -> Self {
+        self.trace_context = Some(TraceContextProvider::new(provider));
+        self
+    }
+}
+
+impl KeyStyle {
+    /// Rearranges the keys of `json_map` according to the style.
+    pub(crate) fn apply(self, json_map: JsonMap) -> JsonMap {
+        match self {
+            KeyStyle::Dotted => json_map,
+            KeyStyle::Nested => nest_json_map(json_map),
+            KeyStyle::Flat => {
+                let mut flat = JsonMap::new();
+                flatten_json_map(&mut flat, None, json_map);
+                flat
+            }
+        }
+    }
+}
+
+/// Expand dotted keys of `json_map` into nested objects
+///
+/// A key which conflicts with a non-object value, e.g. `a.b` with `a`, cannot be nested.
+/// The non-object value keeps the plain key, and the other values are kept under dotted keys next to it, e.g. `{"a":1,"a.b":2}`.
+fn nest_json_map(json_map: JsonMap) -> JsonMap {
+    let mut nested = JsonMap::new();
+
+    for (k, v) in json_map {
+        let v = match v {
+            Value::Object(m) => Value::Object(nest_json_map(m)),
+            v => v,
+        };
+
+        let mut target = &mut nested;
+        let mut rest = k.as_str();
+        while let Some((segment, tail)) = rest.split_once('.') {
+            if target.get(segment).is_some_and(|v| !v.is_object()) {
+                break;
+            }
+            target = target
+                .entry(segment)
+                .or_insert_with(|| Value::Object(JsonMap::new()))
+                .as_object_mut()
+                .unwrap();
+            rest = tail;
+        }
+
+        match (target.get_mut(rest), v) {
+            (Some(Value::Object(a)), Value::Object(b)) => {
+                crate::extra_fields::extend_json_map(a, &b);
+            }
+            (Some(existing @ Value::Object(_)), v) => {
+                let Value::Object(fields) = std::mem::replace(existing, v) else {
+                    unreachable!();
+                };
+                flatten_json_map(target, Some(rest), fields);
+            }
+            (_, v) => {
+                target.insert(rest.to_string(), v);
+            }
+        }
+    }
+
+    nested
+}
+
+/// Collapse nested objects of `json_map` into dotted keys of `flat`
+fn flatten_json_map(flat: &mut JsonMap, prefix: Option<&str>, json_map: JsonMap) {
+    for (k, v) in json_map {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, k),
+            None => k,
+        };
+
+        match v {
+            Value::Object(m) if !m.is_empty() => flatten_json_map(flat, Some(&key), m),
+            v => {
+                flat.insert(key, v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn example_map() -> JsonMap {
+        json!({
+            "@timestamp": "2021-11-24T17:38:21.000098765Z",
+            "log.level": "TRACE",
+            "log.origin": {
+                "file": {
+                    "line": 1234,
+                },
+            },
+            "b": {
+                "c.d": 2,
+            },
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn test_key_style_dotted() {
+        assert_eq!(KeyStyle::Dotted.apply(example_map()), example_map());
+    }
+
+    #[test]
+    fn test_key_style_nested() {
+        assert_eq!(
+            Value::Object(KeyStyle::Nested.apply(example_map())).to_string(),
+            json!({
+                "@timestamp": "2021-11-24T17:38:21.000098765Z",
+                "log": {
+                    "level": "TRACE",
+                    "origin": {
+                        "file": {
+                            "line": 1234,
+                        },
+                    },
+                },
+                "b": {
+                    "c": {
+                        "d": 2,
+                    },
+                },
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_key_style_nested_conflict() {
+        let nested = |value: Value| {
+            Value::Object(KeyStyle::Nested.apply(value.as_object().unwrap().clone())).to_string()
+        };
+
+        let expected = json!({ "a": 1, "a.b": 2, "a.c.d": 3 }).to_string();
+        assert_eq!(nested(json!({ "a": 1, "a.b": 2, "a.c.d": 3 })), expected);
+        assert_eq!(
+            nested(json!({ "a.b": 2, "a.c": { "d": 3 }, "a": 1 })),
+            expected
+        );
+        assert_eq!(
+            nested(json!({ "x": { "a.b": 2 }, "x.a": 1 })),
+            json!({ "x": { "a": 1, "a.b": 2 } }).to_string()
+        );
+    }
+
+    #[test]
+    fn test_key_style_flat() {
+        assert_eq!(
+            Value::Object(KeyStyle::Flat.apply(example_map())).to_string(),
+            json!({
+                "@timestamp": "2021-11-24T17:38:21.000098765Z",
+                "log.level": "TRACE",
+                "log.origin.file.line": 1234,
+                "b.c.d": 2,
+            })
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Rewriting of the source paths in `log.origin`
+
+use crate::siphash::siphash24;
+use crate::FormatOptions;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Replaces source paths with stable hashes, optionally recording the original values.
+///
+/// The paths are hashed only if the key is set; the mapping may be set before or after it.
+#[derive(Clone, Default)]
+pub(crate) struct SourcePathHasher {
+    key: Option<[u8; 16]>,
+    mapping: Option<Arc<Mutex<Mapping>>>,
+}
+
+/// Side channel which receives each distinct hash and its original value once
+struct Mapping {
+    writer: Box<dyn Write + Send>,
+    recorded: HashSet<String>,
+}
+
+impl fmt::Debug for SourcePathHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourcePathHasher")
+            .field("enabled", &self.key.is_some())
+            .field("mapping", &self.mapping.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SourcePathHasher {
+    pub(crate) fn set_key(&mut self, key: [u8; 16]) {
+        self.key = Some(key);
+    }
+
+    pub(crate) fn set_mapping(&mut self, writer: Box<dyn Write + Send>) {
+        self.mapping = Some(Arc::new(Mutex::new(Mapping {
+            writer,
+            recorded: HashSet::new(),
+        })));
+    }
+
+    /// Returns the hash of `value` keyed with `key`, recording it to the mapping if it is seen for the first time.
+    fn hash(&self, key: &[u8; 16], value: &str) -> String {
+        let hash = format!("{:016x}", siphash24(key, value.as_bytes()));
+
+        if let Some(mapping) = &self.mapping {
+            let mut mapping = mapping.lock().unwrap_or_else(PoisonError::into_inner);
+            if !mapping.recorded.contains(&hash) {
+                // The mapping is best-effort; failing to record it must not break logging.
+                if writeln!(mapping.writer, "{}\t{}", hash, value).is_ok() {
+                    let _ = mapping.writer.flush();
+                    mapping.recorded.insert(hash.clone());
+                }
+            }
+        }
+
+        hash
+    }
+
+    fn hash_in_place(&self, key: &[u8; 16], value: &mut String) {
+        *value = self.hash(key, value);
+    }
+}
+
+/// Rewrites the source paths in the serialized `log.origin` field according to `options`.
+pub(crate) fn rewrite_log_origin(log_origin: &mut Value, options: &FormatOptions) {
+    if let Some(Value::String(file_path)) = log_origin.pointer_mut("/rust/file_path") {
+        if let Some(root) = &options.source_root {
+            if let Some(relative) = relative_path(file_path, root) {
+                *file_path = relative;
+            }
+        }
+        if options.normalize_path_separators {
+            *file_path = normalize_path_separators(file_path);
+        }
+    }
+
+    let hasher = &options.source_path_hasher;
+    if let Some(key) = &hasher.key {
+        let target_is_module_path = matches!(
+            (log_origin.pointer("/rust/target"), log_origin.pointer("/rust/module_path")),
+            (Some(target), Some(module_path)) if target == module_path
+        );
+
+        for pointer in [
+            "/file/name",
+            "/function",
+            "/rust/module_path",
+            "/rust/file_path",
+        ] {
+            if let Some(Value::String(value)) = log_origin.pointer_mut(pointer) {
+                hasher.hash_in_place(key, value);
+            }
+        }
+        if target_is_module_path {
+            if let Some(Value::String(target)) = log_origin.pointer_mut("/rust/target") {
+                hasher.hash_in_place(key, target);
+            }
+        }
+    }
+}
+
+/// Returns the value of the `log.logger` field, which is hashed like `log.origin.rust.target` according to `options`.
+pub(crate) fn log_logger(record: &log::Record, options: &FormatOptions) -> String {
+    let target = record.target();
+    match &options.source_path_hasher.key {
+        Some(key) if record.module_path() == Some(target) => {
+            options.source_path_hasher.hash(key, target)
+        }
+        _ => target.to_string(),
+    }
+}
+
+/// Returns `path` relative to `root`, or `None` if `path` is not inside `root`.
+fn relative_path(path: &str, root: &Path) -> Option<String> {
+    Path::new(path)
+        .strip_prefix(root)
+        .ok()
+        .and_then(Path::to_str)
+        .map(str::to_string)
+}
+
+/// Replaces Windows path separators with forward slashes.
+fn normalize_path_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SharedBuf;
+    use serde_json::json;
+
+    #[test]
+    fn test_rewrite_log_origin() {
+        let mut log_origin = json!({
+            "file": { "line": 13, "name": "example.rs" },
+            "rust": { "target": "example", "file_path": "tests\\example.rs" },
+        });
+
+        rewrite_log_origin(&mut log_origin, &FormatOptions::new());
+        assert_eq!(log_origin["rust"]["file_path"], "tests\\example.rs");
+
+        rewrite_log_origin(
+            &mut log_origin,
+            &FormatOptions::new().normalize_path_separators(true),
+        );
+        assert_eq!(log_origin["rust"]["file_path"], "tests/example.rs");
+    }
+
+    #[test]
+    fn test_rewrite_log_origin_hash_source_paths() {
+        let key = *b"0123456789abcdef";
+        let mapping = SharedBuf::default();
+        // The mapping takes effect even if it is set first
+        let options = FormatOptions::new()
+            .source_path_mapping(mapping.clone())
+            .hash_source_paths(key);
+
+        let original = json!({
+            "file": { "line": 13, "name": "example.rs" },
+            "rust": {
+                "target": "example::tests",
+                "module_path": "example::tests",
+                "file_path": "tests/example.rs",
+            },
+        });
+
+        let mut log_origin = original.clone();
+        rewrite_log_origin(&mut log_origin, &options);
+        rewrite_log_origin(&mut original.clone(), &options);
+
+        let module_path_hash = format!("{:016x}", siphash24(&key, b"example::tests"));
+        assert_eq!(log_origin["file"]["line"], 13);
+        assert_eq!(log_origin["rust"]["module_path"], module_path_hash);
+        assert_eq!(log_origin["rust"]["target"], module_path_hash);
+        assert_ne!(log_origin["rust"]["file_path"], "tests/example.rs");
+
+        let mapping = mapping.content();
+        assert_eq!(mapping.lines().count(), 3);
+        assert!(mapping.contains(&format!("{}\texample::tests\n", module_path_hash)));
+    }
+
+    #[test]
+    fn test_rewrite_log_origin_hash_function() {
+        let key = *b"0123456789abcdef";
+        let mut log_origin = json!({ "function": "example::tests::handle_request" });
+
+        rewrite_log_origin(&mut log_origin, &FormatOptions::new());
+        assert_eq!(log_origin["function"], "example::tests::handle_request");
+
+        rewrite_log_origin(
+            &mut log_origin,
+            &FormatOptions::new().hash_source_paths(key),
+        );
+        assert_eq!(
+            log_origin["function"],
+            format!(
+                "{:016x}",
+                siphash24(&key, b"example::tests::handle_request")
+            )
+        );
+    }
+
+    #[test]
+    fn test_log_logger_hash_source_paths() {
+        let key = *b"0123456789abcdef";
+        let options = FormatOptions::new().hash_source_paths(key);
+
+        let record = log::Record::builder()
+            .target("example::tests")
+            .module_path(Some("example::tests"))
+            .build();
+        assert_eq!(
+            log_logger(&record, &options),
+            format!("{:016x}", siphash24(&key, b"example::tests"))
+        );
+        assert_eq!(log_logger(&record, &FormatOptions::new()), "example::tests");
+
+        let record = log::Record::builder()
+            .target("audit")
+            .module_path(Some("example::tests"))
+            .build();
+        assert_eq!(log_logger(&record, &options), "audit");
+    }
+
+    #[test]
+    fn test_rewrite_log_origin_source_root() {
+        let options = FormatOptions::new().source_root("/build/my_app");
+
+        let mut log_origin = json!({ "rust": { "file_path": "/build/my_app/src/main.rs" } });
+        rewrite_log_origin(&mut log_origin, &options);
+        assert_eq!(log_origin["rust"]["file_path"], "src/main.rs");
+
+        let mut log_origin = json!({ "rust": { "file_path": "/registry/dep/src/lib.rs" } });
+        rewrite_log_origin(&mut log_origin, &options);
+        assert_eq!(log_origin["rust"]["file_path"], "/registry/dep/src/lib.rs");
+
+        let mut log_origin = json!({ "rust": { "file_path": "/build/my_app_2/src/main.rs" } });
+        rewrite_log_origin(&mut log_origin, &options);
+        assert_eq!(
+            log_origin["rust"]["file_path"],
+            "/build/my_app_2/src/main.rs"
+        );
+    }
+}
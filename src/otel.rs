@@ -0,0 +1,289 @@
+//! Appender of the ECS events to an OpenTelemetry logger
+
+use log::kv::{self, Key, VisitSource};
+use log::{Level, Log, Metadata, Record};
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity};
+use opentelemetry::trace::{SpanId, TraceId};
+use std::time::SystemTime;
+
+/// [`log`] backend which emits the ECS events as OpenTelemetry [`LogRecord`]s, so that one logger can ship the same events
+/// to an OTLP collector and to Elasticsearch.
+///
+/// Pass it to [`Builder::forward`](crate::Builder::forward), which attaches the ECS fields to the records:
+///
+/// | `LogRecord` | Source |
+/// |---|---|
+/// | body | `message` |
+/// | severity | `log.level` |
+/// | timestamp | `@timestamp` |
+/// | target | `log.origin.rust.target` |
+/// | trace context | `trace.id` and `span.id`, if they are W3C ids. The flags are unset, since the sampled flag is not logged |
+/// | attributes | the other ECS fields with dotted names, e.g. `service.name` and the extra fields |
+///
+/// Available with the `opentelemetry` feature, which enables `kv`.
+///
+/// # Example
+///
+/// ```ignore
+/// use ecs_logger::OpenTelemetryAppender;
+/// use opentelemetry_sdk::logs::SdkLoggerProvider;
+///
+/// let provider = SdkLoggerProvider::builder()
+///     .with_batch_exporter(opentelemetry_otlp::LogExporter::builder().with_tonic().build()?)
+///     .build();
+///
+/// ecs_logger::Builder::from_env()
+///     .forward(OpenTelemetryAppender::new(&provider)) // Ship to OpenTelemetry as well as writing to stderr
+///     .init();
+/// ```
+#[derive(Debug)]
+pub struct OpenTelemetryAppender<L> {
+    logger: L,
+}
+
+impl<L: Logger> OpenTelemetryAppender<L> {
+    /// Creates an appender which emits to a logger of `provider` named `ecs-logger`.
+    pub fn new<P: LoggerProvider<Logger = L>>(provider: &P) -> Self {
+        Self::from_logger(provider.logger("ecs-logger"))
+    }
+
+    /// Creates an appender which emits to `logger`.
+    pub fn from_logger(logger: L) -> Self {
+        OpenTelemetryAppender { logger }
+    }
+}
+
+impl<L: Logger + Send + Sync> Log for OpenTelemetryAppender<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.logger
+            .event_enabled(severity(metadata.level()), metadata.target(), None)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut log_record = self.logger.create_log_record();
+        log_record.set_body(record.args().to_string().into());
+        log_record.set_severity_number(severity(record.level()));
+        log_record.set_severity_text(record.level().as_str());
+        log_record.set_target(record.target().to_string());
+        log_record.set_observed_timestamp(SystemTime::now());
+
+        let mut visitor = Visitor {
+            record: &mut log_record,
+            trace_id: None,
+            span_id: None,
+        };
+        // The visitor never fails
+        let _ = record.key_values().visit(&mut visitor);
+        if let (Some(trace_id), Some(span_id)) = (visitor.trace_id, visitor.span_id) {
+            log_record.set_trace_context(trace_id, span_id, None);
+        }
+
+        self.logger.emit(log_record);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Visitor which adds the ECS fields to a [`LogRecord`]
+struct Visitor<'a, R> {
+    record: &'a mut R,
+    trace_id: Option<TraceId>,
+    span_id: Option<SpanId>,
+}
+
+impl<'kvs, R: LogRecord> VisitSource<'kvs> for Visitor<'_, R> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        match key.as_str() {
+            // Set natively
+            "message" | "log.level" => {}
+            "@timestamp" => {
+                if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&value.to_string()) {
+                    self.record.set_timestamp(timestamp.into());
+                }
+            }
+            key => {
+                match key {
+                    "trace.id" => self.trace_id = TraceId::from_hex(&value.to_string()).ok(),
+                    "span.id" => self.span_id = SpanId::from_hex(&value.to_string()).ok(),
+                    _ => {}
+                }
+                self.record
+                    .add_attribute(key.to_string(), any_value(&value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a key-value of the forwarded record, which is a scalar, into an attribute value
+fn any_value(value: &kv::Value) -> AnyValue {
+    if let Some(b) = value.to_bool() {
+        b.into()
+    } else if let Some(n) = value.to_i64() {
+        n.into()
+    } else if let Some(n) = value.to_f64() {
+        n.into()
+    } else {
+        // Strings, `null`, and the integers out of the range of `i64`
+        value.to_string().into()
+    }
+}
+
+/// Returns the severity of the OpenTelemetry log data model of `level`
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::Error => Severity::Error,
+        Level::Warn => Severity::Warn,
+        Level::Info => Severity::Info,
+        Level::Debug => Severity::Debug,
+        Level::Trace => Severity::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forward::Forwarder;
+    use crate::FormatOptions;
+    use opentelemetry::trace::TraceFlags;
+    use opentelemetry::Key;
+    use std::borrow::Cow;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct TestRecord {
+        body: Option<AnyValue>,
+        severity: Option<Severity>,
+        target: Option<String>,
+        timestamp: Option<SystemTime>,
+        attributes: Vec<(Key, AnyValue)>,
+        trace_context: Option<(TraceId, SpanId, Option<TraceFlags>)>,
+    }
+
+    impl LogRecord for TestRecord {
+        fn set_event_name(&mut self, _name: &'static str) {}
+
+        fn set_target<T: Into<Cow<'static, str>>>(&mut self, target: T) {
+            self.target = Some(target.into().into_owned());
+        }
+
+        fn set_timestamp(&mut self, timestamp: SystemTime) {
+            self.timestamp = Some(timestamp);
+        }
+
+        fn set_observed_timestamp(&mut self, _timestamp: SystemTime) {}
+
+        fn set_severity_text(&mut self, _text: &'static str) {}
+
+        fn set_severity_number(&mut self, number: Severity) {
+            self.severity = Some(number);
+        }
+
+        fn set_body(&mut self, body: AnyValue) {
+            self.body = Some(body);
+        }
+
+        fn add_attributes<I, K, V>(&mut self, attributes: I)
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: Into<Key>,
+            V: Into<AnyValue>,
+        {
+            for (k, v) in attributes {
+                self.add_attribute(k, v);
+            }
+        }
+
+        fn add_attribute<K: Into<Key>, V: Into<AnyValue>>(&mut self, key: K, value: V) {
+            self.attributes.push((key.into(), value.into()));
+        }
+
+        fn set_trace_context(
+            &mut self,
+            trace_id: TraceId,
+            span_id: SpanId,
+            trace_flags: Option<TraceFlags>,
+        ) {
+            self.trace_context = Some((trace_id, span_id, trace_flags));
+        }
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct TestLogger(Arc<Mutex<Vec<TestRecord>>>);
+
+    impl Logger for TestLogger {
+        type LogRecord = TestRecord;
+
+        fn create_log_record(&self) -> TestRecord {
+            TestRecord::default()
+        }
+
+        fn emit(&self, record: TestRecord) {
+            self.0.lock().unwrap().push(record);
+        }
+
+        fn event_enabled(&self, level: Severity, _target: &str, _name: Option<&str>) -> bool {
+            level >= Severity::Info
+        }
+    }
+
+    #[test]
+    fn test_appender() {
+        let logger = TestLogger::default();
+        let forwarder = Forwarder::new(
+            vec![Box::new(OpenTelemetryAppender::from_logger(logger.clone()))],
+            FormatOptions::default(),
+        );
+
+        crate::TraceContext::new("0af7651916cd43dd8448eb211c80319c")
+            .span_id("b7ad6b7169203331")
+            .in_scope(|| {
+                forwarder.log(
+                    &Record::builder()
+                        .args(format_args!("Hello {}!", "world"))
+                        .level(Level::Warn)
+                        .target("my_app")
+                        .line(Some(12))
+                        .build(),
+                );
+            });
+        forwarder.log(&Record::builder().level(Level::Debug).build());
+
+        let records = logger.0.lock().unwrap().clone();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.body, Some("Hello world!".into()));
+        assert_eq!(record.severity, Some(Severity::Warn));
+        assert_eq!(record.target.as_deref(), Some("my_app"));
+        assert!(record.timestamp.is_some());
+        assert_eq!(
+            record.trace_context,
+            Some((
+                TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+                SpanId::from_hex("b7ad6b7169203331").unwrap(),
+                None
+            ))
+        );
+
+        let attribute = |key: &str| {
+            record
+                .attributes
+                .iter()
+                .find(|(k, _)| k.as_str() == key)
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(attribute("log.origin.file.line"), Some(12.into()));
+        assert_eq!(attribute("log.origin.rust.target"), Some("my_app".into()));
+        assert_eq!(
+            attribute("trace.id"),
+            Some("0af7651916cd43dd8448eb211c80319c".into())
+        );
+        assert_eq!(attribute("message"), None);
+        assert_eq!(attribute("log.level"), None);
+        assert_eq!(attribute("@timestamp"), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! `event.outcome` field
+
+use crate::extra_fields::with_scoped_fields;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Value of the [`event.outcome`](https://www.elastic.co/guide/en/ecs/current/ecs-allowed-values-event-outcome.html) field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The operation succeeded.
+    Success,
+
+    /// The operation failed.
+    Failure,
+
+    /// Whether the operation succeeded cannot be determined.
+    Unknown,
+}
+
+impl Outcome {
+    /// Returns the value of the field.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Unknown => "unknown",
+        }
+    }
+}
+
+/// Runs `f` with `event.outcome` set to `outcome` for the log records emitted by the current thread.
+///
+/// This takes precedence over the outcome set automatically by [`FormatOptions::event_outcome`](crate::FormatOptions::event_outcome).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::{with_outcome, Outcome};
+///
+/// ecs_logger::init();
+///
+/// with_outcome(Outcome::Success, || log::info!("Payment captured"));
+/// ```
+pub fn with_outcome<R>(outcome: Outcome, f: impl FnOnce() -> R) -> R {
+    let mut fields = Map::new();
+    fields.insert("event.outcome".to_string(), outcome.as_str().into());
+    with_scoped_fields(fields, f)
+}
+
+/// Sets `event.outcome` to `failure` if `level` is `ERROR` and the outcome is not set yet.
+pub(crate) fn set_failure_on_error(event: &mut Map<String, Value>, level: log::Level) {
+    if level != log::Level::Error {
+        return;
+    }
+
+    let nested = event.get("event").and_then(|e| e.get("outcome")).is_some();
+    if !nested && !event.contains_key("event.outcome") {
+        event.insert(
+            "event.outcome".to_string(),
+            Outcome::Failure.as_str().into(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn outcome(event: Value, level: log::Level) -> Value {
+        let mut event = event.as_object().unwrap().clone();
+        set_failure_on_error(&mut event, level);
+        Value::Object(event)
+    }
+
+    #[test]
+    fn test_set_failure_on_error() {
+        assert_eq!(
+            outcome(json!({}), log::Level::Error),
+            json!({ "event.outcome": "failure" })
+        );
+        assert_eq!(outcome(json!({}), log::Level::Warn), json!({}));
+        assert_eq!(
+            outcome(json!({ "event.outcome": "success" }), log::Level::Error),
+            json!({ "event.outcome": "success" })
+        );
+        assert_eq!(
+            outcome(
+                json!({ "event": { "outcome": "unknown" } }),
+                log::Level::Error
+            ),
+            json!({ "event": { "outcome": "unknown" } })
+        );
+    }
+
+    #[test]
+    fn test_outcome_serialize() {
+        assert_eq!(
+            serde_json::to_value(Outcome::Failure).unwrap(),
+            Outcome::Failure.as_str()
+        );
+    }
+}
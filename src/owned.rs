@@ -0,0 +1,170 @@
+//! Owned snapshot of a log record
+
+use log::{Level, Record};
+
+/// Snapshot of a [`log::Record`] which owns its data.
+///
+/// The message is rendered when the snapshot is taken, so it can be stored beyond the borrow of the original record,
+/// e.g. in a custom sink or a test harness.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::OwnedRecord;
+///
+/// let owned = OwnedRecord::from(
+///     &log::Record::builder()
+///         .args(format_args!("Hello {}!", "world"))
+///         .level(log::Level::Info)
+///         .target("my_app")
+///         .build(),
+/// );
+/// assert_eq!(owned.message(), "Hello world!");
+///
+/// // Encode it later
+/// let mut buf = Vec::new();
+/// owned.with_record(|record| ecs_logger::format(&mut buf, record)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    #[cfg(feature = "kv")]
+    key_values: Vec<(String, serde_json::Value)>,
+}
+
+impl OwnedRecord {
+    /// Returns the verbosity level of the record.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the target of the record.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the rendered message of the record.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the module path of the record.
+    pub fn module_path(&self) -> Option<&str> {
+        self.module_path.as_deref()
+    }
+
+    /// Returns the source file of the record.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line of the record.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Returns the key-values of the record as JSON, e.g. `("user_id", 42)` of `info!(user_id = 42; "logged in")`.
+    ///
+    /// This method is available when the `kv` feature is enabled.
+    #[cfg(feature = "kv")]
+    pub fn key_values(&self) -> &[(String, serde_json::Value)] {
+        &self.key_values
+    }
+
+    /// Calls `f` with a [`log::Record`] borrowing from the snapshot.
+    ///
+    /// The record has the key-values of the original one if the `kv` feature is enabled.
+    pub fn with_record<R>(&self, f: impl FnOnce(&Record) -> R) -> R {
+        let mut builder = Record::builder();
+        builder
+            .level(self.level)
+            .target(&self.target)
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line);
+        #[cfg(feature = "kv")]
+        let key_values = crate::kv::JsonKeyValues(&self.key_values);
+        #[cfg(feature = "kv")]
+        builder.key_values(&key_values);
+
+        f(&builder.args(format_args!("{}", self.message)).build())
+    }
+}
+
+impl From<&Record<'_>> for OwnedRecord {
+    fn from(record: &Record) -> Self {
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: match record.args().as_str() {
+                Some(message) => message.to_string(),
+                None => record.args().to_string(),
+            },
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            #[cfg(feature = "kv")]
+            key_values: crate::kv::key_values(record, false).into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owned_record() {
+        let owned = OwnedRecord::from(
+            &Record::builder()
+                .args(format_args!("Hello {}!", "world"))
+                .level(Level::Warn)
+                .target("my_target")
+                .module_path(Some("my_app::module"))
+                .file(Some("src/module.rs"))
+                .line(Some(42))
+                .build(),
+        );
+
+        owned.with_record(|record| {
+            assert_eq!(record.args().to_string(), "Hello world!");
+            assert_eq!(record.level(), Level::Warn);
+            assert_eq!(record.target(), "my_target");
+            assert_eq!(record.module_path(), Some("my_app::module"));
+            assert_eq!(record.file(), Some("src/module.rs"));
+            assert_eq!(record.line(), Some(42));
+        });
+    }
+
+    #[cfg(feature = "kv")]
+    #[test]
+    fn test_owned_record_key_values() {
+        use log::kv;
+        use serde_json::json;
+
+        let kvs: &[(&str, kv::Value)] = &[
+            ("user_id", 42.into()),
+            ("delta", (-1i64).into()),
+            ("ratio", 0.5.into()),
+            ("http.request.method", "GET".into()),
+            ("missing", kv::Value::null()),
+            ("path", kv::Value::from_debug(&["a", "b"])),
+        ];
+        let record = Record::builder()
+            .args(format_args!("logged in"))
+            .key_values(&kvs)
+            .build();
+        let owned = OwnedRecord::from(&record);
+
+        let expected = crate::kv::key_values(&record, true);
+        assert_eq!(owned.key_values()[0], ("user_id".to_string(), json!(42)));
+        owned.with_record(|record| {
+            assert_eq!(crate::kv::key_values(record, true), expected);
+        });
+    }
+}
@@ -0,0 +1,87 @@
+//! Panic hook which logs panics as ECS events
+
+use crate::extra_fields::with_scoped_fields;
+use serde_json::{Map, Value};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::{self, Location};
+
+/// Replaces the panic hook with one which logs the panics as `FATAL` events and flushes the global logger.
+///
+/// The event is logged at the `ERROR` level, and `log.level` is overwritten with `FATAL`. It has:
+///
+/// - `error.message`: the panic message, e.g. `index out of bounds: the len is 3 but the index is 5`,
+/// - `error.type`: `panic`,
+/// - `error.stack_trace`: the backtrace of the panicking thread, captured regardless of `RUST_BACKTRACE`,
+/// - `log.origin.file.name` and `log.origin.file.line`: the location of the panic.
+///
+/// The message of the event is `thread '<name>' panicked`. Then the global logger is flushed like with
+/// [`Builder::flush_on_exit`](crate::Builder::flush_on_exit), so that the event is written before the process aborts or exits.
+/// The previous hook, which prints the panic to stderr by default, is not called, so the output stays one JSON document per line.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+/// ecs_logger::install_panic_hook();
+///
+/// let result = std::panic::catch_unwind(|| panic!("invalid state"));
+/// // {"log.level":"FATAL","message":"thread 'main' panicked","error.message":"invalid state","error.type":"panic",...}
+/// assert!(result.is_err());
+/// ```
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().unwrap_or_else(|| Location::caller());
+        log_panic(payload_message(info.payload()), location);
+        crate::exit::flush();
+    }));
+}
+
+/// Returns the message of a panic payload, which is a string unless the panic was started with [`std::panic::panic_any`]
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message,
+            None => "Box<dyn Any>",
+        },
+    }
+}
+
+fn log_panic(message: &str, location: &Location) {
+    let mut fields = Map::new();
+    fields.insert("log.level".to_string(), "FATAL".into());
+    fields.insert("error.message".to_string(), message.into());
+    fields.insert("error.type".to_string(), "panic".into());
+    fields.insert(
+        "error.stack_trace".to_string(),
+        Value::String(Backtrace::force_capture().to_string()),
+    );
+
+    let thread = std::thread::current();
+    let name = thread.name().unwrap_or("<unnamed>");
+    with_scoped_fields(fields, || {
+        crate::log_at(
+            log::Level::Error,
+            format_args!("thread '{}' panicked", name),
+            location,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_message() {
+        let payload: Box<dyn Any + Send> = Box::new("static");
+        assert_eq!(payload_message(payload.as_ref()), "static");
+
+        let payload: Box<dyn Any + Send> = Box::new(format!("formatted {}", 1));
+        assert_eq!(payload_message(payload.as_ref()), "formatted 1");
+
+        let payload: Box<dyn Any + Send> = Box::new(42);
+        assert_eq!(payload_message(payload.as_ref()), "Box<dyn Any>");
+    }
+}
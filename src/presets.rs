@@ -0,0 +1,131 @@
+//! Preset configurations of [`Builder`] for common environments
+
+use crate::{Builder, FormatOptions, Target};
+use chrono::SecondsFormat;
+use log::LevelFilter;
+use std::io::{self, Write};
+
+/// Capacity of the queue of [`Builder::production`].
+const PRODUCTION_QUEUE_CAPACITY: usize = 8192;
+
+impl Builder {
+    /// Creates a builder for production services.
+    ///
+    /// - Filters are read from `RUST_LOG`, and `info` level logs are enabled if it is not set.
+    /// - Logs are written to stdout, where container runtimes collect them.
+    /// - Events are encoded on a background thread, and flushed when the process exits or panics.
+    /// - `event.outcome` is set to `failure` for `ERROR` records.
+    /// - `process.pid`, `process.name`, and `process.executable` are added.
+    /// - `host.*` fields are added when the `host` feature is enabled.
+    pub fn production() -> Self {
+        let mut builder = Self::from_env_or(LevelFilter::Info);
+        builder
+            .target(Target::Stdout)
+            .deferred_formatting(PRODUCTION_QUEUE_CAPACITY)
+            .flush_on_exit(true)
+            .format_options(
+                FormatOptions::new()
+                    .event_outcome(true)
+                    .process_metadata(true),
+            );
+        builder
+    }
+
+    /// Creates a builder for local development.
+    ///
+    /// - Filters are read from `RUST_LOG`, and `debug` level logs are enabled if it is not set.
+    /// - Logs are written to stderr as soon as they are logged, with millisecond timestamps.
+    /// - Backslashes in file paths are replaced with forward slashes.
+    pub fn development() -> Self {
+        let mut builder = Self::from_env_or(LevelFilter::Debug);
+        builder.target(Target::Stderr).format_options(
+            FormatOptions::new()
+                .timestamp_format(SecondsFormat::Millis)
+                .normalize_path_separators(true),
+        );
+        builder
+    }
+
+    /// Creates a builder for tests.
+    ///
+    /// - Filters are read from `RUST_LOG`, and `trace` level logs are enabled if it is not set.
+    /// - Logs are written with [`eprint!`], so the test harness captures them and shows them only for failed tests.
+    pub fn test() -> Self {
+        let mut builder = Self::from_env_or(LevelFilter::Trace);
+        builder.target(Target::Pipe(Box::new(TestWriter)));
+        builder
+    }
+
+    /// Creates a builder with filters parsed from `RUST_LOG`, or enabling `level` for all targets if it is not set
+    fn from_env_or(level: LevelFilter) -> Self {
+        let mut builder = Self::new();
+        match std::env::var("RUST_LOG") {
+            Ok(filters) => builder.parse_filters(&filters),
+            Err(_) => builder.filter_level(level),
+        };
+        builder
+    }
+}
+
+/// Writer which goes through [`eprint!`], so that the output is captured by the test harness.
+struct TestWriter;
+
+impl Write for TestWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        eprint!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Initializes the global logger with [`Builder::production`].
+///
+/// # Panics
+///
+/// This function will panic if the global logger has already been initialized.
+pub fn init_production() {
+    Builder::production().init();
+}
+
+/// Initializes the global logger with [`Builder::development`].
+///
+/// # Panics
+///
+/// This function will panic if the global logger has already been initialized.
+pub fn init_development() {
+    Builder::development().init();
+}
+
+/// Initializes the global logger with [`Builder::test`], unless it has already been initialized.
+///
+/// Unlike the other presets, this may be called at the start of every test.
+///
+/// # Example
+///
+/// ```
+/// // At the start of each test
+/// ecs_logger::init_test();
+/// log::debug!("Shown if the test fails");
+/// ```
+pub fn init_test() {
+    let _ = Builder::test().try_init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_levels() {
+        if std::env::var_os("RUST_LOG").is_some() {
+            return;
+        }
+
+        assert_eq!(Builder::production().build().filter(), LevelFilter::Info);
+        assert_eq!(Builder::development().build().filter(), LevelFilter::Debug);
+        assert_eq!(Builder::test().build().filter(), LevelFilter::Trace);
+    }
+}
@@ -0,0 +1,121 @@
+//! Metadata of the process added to the `process.*` fields
+
+use crate::ecs::{Process, ProcessThread};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::sync::OnceLock;
+use std::thread;
+
+static PROCESS: OnceLock<Process<'static>> = OnceLock::new();
+
+thread_local! {
+    /// The current thread, resolved once per thread.
+    static CURRENT_THREAD: ProcessThread<'static> = resolve_current_thread();
+
+    /// The thread reported instead of the current thread, e.g. by the writer thread of deferred formatting.
+    static THREAD_OVERRIDE: RefCell<Option<ProcessThread<'static>>> = const { RefCell::new(None) };
+}
+
+/// Returns the metadata of the current process, collecting it on the first call.
+pub(crate) fn process() -> &'static Process<'static> {
+    PROCESS.get_or_init(collect)
+}
+
+fn collect() -> Process<'static> {
+    let executable = std::env::current_exe().ok();
+    let name = executable
+        .as_deref()
+        .and_then(|executable| executable.file_name())
+        .map(|name| Cow::Owned(name.to_string_lossy().into_owned()));
+
+    Process {
+        pid: Some(std::process::id()),
+        name,
+        executable: executable
+            .map(|executable| Cow::Owned(executable.to_string_lossy().into_owned())),
+        thread: None,
+    }
+}
+
+/// Returns the thread which logs the current record.
+///
+/// This is empty while the thread-local storage of the current thread is being destroyed.
+pub(crate) fn current_thread() -> ProcessThread<'static> {
+    THREAD_OVERRIDE
+        .try_with(|o| o.borrow().clone())
+        .ok()
+        .flatten()
+        .or_else(|| CURRENT_THREAD.try_with(ProcessThread::clone).ok())
+        .unwrap_or_default()
+}
+
+/// Run `f` with `thread` reported as the current thread.
+pub(crate) fn with_thread<R>(thread: ProcessThread<'static>, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous thread even if `f` panics
+    struct RestoreGuard(Option<ProcessThread<'static>>);
+
+    impl Drop for RestoreGuard {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            THREAD_OVERRIDE.with(|o| *o.borrow_mut() = previous);
+        }
+    }
+
+    let _guard = RestoreGuard(THREAD_OVERRIDE.with(|o| o.borrow_mut().replace(thread)));
+
+    f()
+}
+
+fn resolve_current_thread() -> ProcessThread<'static> {
+    let current = thread::current();
+
+    ProcessThread {
+        id: thread_id(current.id()),
+        name: current.name().map(|name| Cow::Owned(name.to_string())),
+    }
+}
+
+/// Extracts the number of a [`thread::ThreadId`], whose `as_u64` is unstable, from its `Debug` representation
+fn thread_id(id: thread::ThreadId) -> Option<u64> {
+    format!("{:?}", id)
+        .strip_prefix("ThreadId(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process() {
+        let process = process();
+        assert_eq!(process.pid, Some(std::process::id()));
+        assert!(process
+            .executable
+            .as_deref()
+            .unwrap()
+            .ends_with(process.name.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn test_current_thread() {
+        let main = current_thread();
+        assert!(main.id.is_some());
+        assert_eq!(main.name.as_deref(), thread::current().name());
+
+        let spawned = thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(current_thread)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(spawned.name.as_deref(), Some("worker"));
+        assert_ne!(spawned.id, main.id);
+
+        let overridden = with_thread(spawned.clone(), current_thread);
+        assert_eq!(overridden, spawned);
+        assert_eq!(current_thread(), main);
+    }
+}
@@ -0,0 +1,217 @@
+//! Per-call-site state of the rate-limited logging macros
+//!
+//! These types are used by [`log_once!`](crate::log_once), [`log_every_n!`](crate::log_every_n), and [`log_every!`](crate::log_every).
+
+use crate::extra_fields::with_scoped_fields;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Passes only the first occurrence.
+#[derive(Debug, Default)]
+pub struct Once(AtomicBool);
+
+/// Passes the first occurrence and every `n`th occurrence after it.
+#[derive(Debug, Default)]
+pub struct EveryN(AtomicU64);
+
+/// Passes at most one occurrence per period.
+#[derive(Debug, Default)]
+pub struct Every {
+    last: Mutex<Option<Instant>>,
+    skipped: AtomicU64,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Once(AtomicBool::new(false))
+    }
+
+    /// Returns `true` on the first call.
+    pub fn tick(&self) -> bool {
+        !self.0.swap(true, Ordering::Relaxed)
+    }
+}
+
+impl EveryN {
+    pub const fn new() -> Self {
+        EveryN(AtomicU64::new(0))
+    }
+
+    /// Returns the number of occurrences skipped since the last pass, or `None` if this occurrence is skipped.
+    pub fn tick(&self, n: u64) -> Option<u64> {
+        let n = n.max(1);
+        let count = self.0.fetch_add(1, Ordering::Relaxed);
+
+        match (count, count % n) {
+            (0, _) => Some(0),
+            (_, 0) => Some(n - 1),
+            _ => None,
+        }
+    }
+}
+
+impl Every {
+    pub const fn new() -> Self {
+        Every {
+            last: Mutex::new(None),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of occurrences skipped since the last pass, or `None` if this occurrence is skipped.
+    pub fn tick(&self, period: Duration) -> Option<u64> {
+        let now = Instant::now();
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match *last {
+            Some(last) if now.duration_since(last) < period => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            _ => {
+                *last = Some(now);
+                Some(self.skipped.swap(0, Ordering::Relaxed))
+            }
+        }
+    }
+}
+
+/// Runs `f` with the number of skipped occurrences added to the emitted records as `log.skipped`.
+pub fn with_skipped<R>(skipped: u64, f: impl FnOnce() -> R) -> R {
+    if skipped == 0 {
+        return f();
+    }
+
+    let mut fields = serde_json::Map::new();
+    fields.insert("log.skipped".to_string(), skipped.into());
+    with_scoped_fields(fields, f)
+}
+
+/// Logs a message only the first time the call site is reached.
+///
+/// The arguments are the same as [`log::log!`].
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// for _ in 0..1000 {
+///     ecs_logger::log_once!(Level::Warn, "deprecated config key `timeout` is used");
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_once {
+    (target: $target:expr, $lvl:expr, $($arg:tt)+) => {{
+        static ONCE: $crate::__private::Once = $crate::__private::Once::new();
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) && ONCE.tick() {
+            $crate::__private::log::log!(target: $target, lvl, $($arg)+);
+        }
+    }};
+    ($lvl:expr, $($arg:tt)+) => {
+        $crate::log_once!(target: ::core::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+/// Logs a message the first time and every `n`th time the call site is reached.
+///
+/// Each emitted event except the first carries the number of skipped occurrences as `log.skipped`.
+/// The other arguments are the same as [`log::log!`].
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// for i in 0..1000 {
+///     ecs_logger::log_every_n!(100, Level::Warn, "queue is full, dropping item {}", i);
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_every_n {
+    ($n:expr, target: $target:expr, $lvl:expr, $($arg:tt)+) => {{
+        static EVERY_N: $crate::__private::EveryN = $crate::__private::EveryN::new();
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            if let ::core::option::Option::Some(skipped) = EVERY_N.tick($n) {
+                $crate::__private::with_skipped(skipped, || {
+                    $crate::__private::log::log!(target: $target, lvl, $($arg)+);
+                });
+            }
+        }
+    }};
+    ($n:expr, $lvl:expr, $($arg:tt)+) => {
+        $crate::log_every_n!($n, target: ::core::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+/// Logs a message at most once per `period` (a [`std::time::Duration`]) at the call site.
+///
+/// Each emitted event carries the number of occurrences skipped since the previous one as `log.skipped`, if any.
+/// The other arguments are the same as [`log::log!`].
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+/// use std::time::Duration;
+///
+/// for _ in 0..1000 {
+///     ecs_logger::log_every!(Duration::from_secs(60), Level::Warn, "disk usage is above 90%");
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_every {
+    ($period:expr, target: $target:expr, $lvl:expr, $($arg:tt)+) => {{
+        static EVERY: $crate::__private::Every = $crate::__private::Every::new();
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            if let ::core::option::Option::Some(skipped) = EVERY.tick($period) {
+                $crate::__private::with_skipped(skipped, || {
+                    $crate::__private::log::log!(target: $target, lvl, $($arg)+);
+                });
+            }
+        }
+    }};
+    ($period:expr, $lvl:expr, $($arg:tt)+) => {
+        $crate::log_every!($period, target: ::core::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once() {
+        let once = Once::new();
+        assert!(once.tick());
+        assert!(!once.tick());
+        assert!(!once.tick());
+    }
+
+    #[test]
+    fn test_every_n() {
+        let every_n = EveryN::new();
+        let ticks = (0..7).map(|_| every_n.tick(3)).collect::<Vec<_>>();
+        assert_eq!(
+            ticks,
+            vec![Some(0), None, None, Some(2), None, None, Some(2)]
+        );
+
+        let every_1 = EveryN::new();
+        assert_eq!(every_1.tick(0), Some(0));
+        assert_eq!(every_1.tick(0), Some(0));
+    }
+
+    #[test]
+    fn test_every() {
+        let every = Every::new();
+        assert_eq!(every.tick(Duration::from_secs(3600)), Some(0));
+        assert_eq!(every.tick(Duration::from_secs(3600)), None);
+        assert_eq!(every.tick(Duration::from_secs(3600)), None);
+        assert_eq!(every.tick(Duration::ZERO), Some(2));
+    }
+}
@@ -0,0 +1,244 @@
+//! Reader of ECS log streams
+//!
+//! [`EventReader`] parses newline-delimited JSON written by this crate back into [`ReadEvent`]s,
+//! so tools which post-process the logs do not have to duplicate the schema.
+//! Any key style of [`FormatOptions`](crate::FormatOptions) can be read, and unknown fields are kept in [`ReadEvent::fields`].
+//!
+//! ## Example
+//!
+//! ```
+//! use ecs_logger::reader::EventReader;
+//!
+//! let logs = br#"{"@timestamp":"2021-11-26T15:25:22.321002600Z","log.level":"ERROR","message":"Oops","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"main.rs"},"rust":{"target":"my_app"}},"user.id":"42"}
+//! {"@timestamp":"2021-11-26T15:25:23Z","log.level":"INFO","message":"Retrying"}
+//! "#;
+//!
+//! for event in EventReader::new(&logs[..]) {
+//!     let event = event.unwrap();
+//!     println!("{:?} {:?}", event.level, event.message);
+//! }
+//! ```
+
+use crate::KeyStyle;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::io::{self, BufRead};
+use thiserror::Error;
+
+/// Event parsed from a line of an ECS log stream.
+///
+/// Every field is optional, because the logs may come from other ECS loggers or older versions of this crate.
+/// A known field whose value has an unexpected type is left in [`fields`](Self::fields).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadEvent {
+    /// `@timestamp` field.
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// `log.level` field.
+    pub level: Option<log::Level>,
+
+    /// `message` field.
+    pub message: Option<String>,
+
+    /// `ecs.version` field.
+    pub ecs_version: Option<String>,
+
+    /// `log.origin.file.line` field.
+    pub line: Option<u32>,
+
+    /// `log.origin.file.name` field.
+    pub file_name: Option<String>,
+
+    /// `log.origin.rust.target` field.
+    pub target: Option<String>,
+
+    /// `log.origin.rust.module_path` field.
+    pub module_path: Option<String>,
+
+    /// `log.origin.rust.file_path` field.
+    pub file_path: Option<String>,
+
+    /// `event.sequence` field.
+    pub sequence: Option<u64>,
+
+    /// The other fields, with nested objects collapsed into dotted keys (e.g. `"user.id"`).
+    pub fields: Map<String, Value>,
+}
+
+/// Error returned by [`EventReader`].
+#[derive(Error, Debug)]
+pub enum ReadError {
+    /// The stream cannot be read.
+    #[error("failed to read the log stream")]
+    Io(#[from] io::Error),
+
+    /// The line is not valid JSON.
+    #[error("line {line} is not valid JSON")]
+    InvalidJson {
+        /// The line number, starting from 1.
+        line: usize,
+
+        /// The underlying error.
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The line is valid JSON, but not an object.
+    #[error("line {line} is not a JSON object")]
+    NotObject {
+        /// The line number, starting from 1.
+        line: usize,
+    },
+}
+
+/// Iterator over the events of a newline-delimited JSON stream. Blank lines are skipped.
+///
+/// The iterator can be resumed after a [`ReadError::InvalidJson`] or [`ReadError::NotObject`], to skip malformed lines.
+#[derive(Debug)]
+pub struct EventReader<R> {
+    reader: R,
+    line: usize,
+    buf: String,
+}
+
+impl<R: BufRead> EventReader<R> {
+    /// Creates a reader of the stream.
+    pub fn new(reader: R) -> Self {
+        EventReader {
+            reader,
+            line: 0,
+            buf: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = Result<ReadEvent, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            if !self.buf.trim().is_empty() {
+                return Some(parse_line(&self.buf, self.line));
+            }
+        }
+    }
+}
+
+/// Parses a line of the stream
+fn parse_line(text: &str, line: usize) -> Result<ReadEvent, ReadError> {
+    let json_map = match serde_json::from_str(text) {
+        Ok(Value::Object(m)) => m,
+        Ok(_) => return Err(ReadError::NotObject { line }),
+        Err(source) => return Err(ReadError::InvalidJson { line, source }),
+    };
+
+    let mut fields = KeyStyle::Flat.apply(json_map);
+    let string = |v: &Value| v.as_str().map(str::to_string);
+
+    Ok(ReadEvent {
+        timestamp: take(&mut fields, "@timestamp", |v| v.as_str()?.parse().ok()),
+        level: take(&mut fields, "log.level", |v| v.as_str()?.parse().ok()),
+        message: take(&mut fields, "message", string),
+        ecs_version: take(&mut fields, "ecs.version", string),
+        line: take(&mut fields, "log.origin.file.line", |v| {
+            v.as_u64()?.try_into().ok()
+        }),
+        file_name: take(&mut fields, "log.origin.file.name", string),
+        target: take(&mut fields, "log.origin.rust.target", string),
+        module_path: take(&mut fields, "log.origin.rust.module_path", string),
+        file_path: take(&mut fields, "log.origin.rust.file_path", string),
+        sequence: take(&mut fields, "event.sequence", Value::as_u64),
+        fields,
+    })
+}
+
+/// Removes the field `key` from `fields` if `parse` accepts its value
+fn take<T>(
+    fields: &mut Map<String, Value>,
+    key: &str,
+    parse: impl FnOnce(&Value) -> Option<T>,
+) -> Option<T> {
+    let parsed = parse(fields.get(key)?)?;
+    fields.shift_remove(key);
+    Some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_events() {
+        let logs = concat!(
+            r#"{"@timestamp":"2021-11-26T15:25:22.321002600Z","log.level":"ERROR","message":"Oops","ecs.version":"1.12.1","log.origin":{"file":{"line":13,"name":"main.rs"},"rust":{"target":"my_app","module_path":"my_app","file_path":"src/main.rs"}},"user.id":"42","b":{"c":[1,2]}}"#,
+            "\n\n",
+            r#"{"log":{"level":"warn"},"message":"Nested","event":{"sequence":7}}"#,
+            "\n",
+            r#"{"log.level":"LOUD","message":1}"#,
+        );
+
+        let events = EventReader::new(logs.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                ReadEvent {
+                    timestamp: Some("2021-11-26T15:25:22.321002600Z".parse().unwrap()),
+                    level: Some(log::Level::Error),
+                    message: Some("Oops".to_string()),
+                    ecs_version: Some("1.12.1".to_string()),
+                    line: Some(13),
+                    file_name: Some("main.rs".to_string()),
+                    target: Some("my_app".to_string()),
+                    module_path: Some("my_app".to_string()),
+                    file_path: Some("src/main.rs".to_string()),
+                    sequence: None,
+                    fields: json!({ "user.id": "42", "b.c": [1, 2] })
+                        .as_object()
+                        .unwrap()
+                        .clone(),
+                },
+                ReadEvent {
+                    level: Some(log::Level::Warn),
+                    message: Some("Nested".to_string()),
+                    sequence: Some(7),
+                    ..Default::default()
+                },
+                ReadEvent {
+                    fields: json!({ "log.level": "LOUD", "message": 1 })
+                        .as_object()
+                        .unwrap()
+                        .clone(),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut reader = EventReader::new("{\"message\":\"a\"}\nnot json\n[1]\n{}\n".as_bytes());
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReadError::InvalidJson { line: 2, .. }))
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReadError::NotObject { line: 3 }))
+        ));
+        assert_eq!(reader.next().unwrap().unwrap(), ReadEvent::default());
+        assert!(reader.next().is_none());
+    }
+}
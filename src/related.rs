@@ -0,0 +1,137 @@
+//! `related.*` fields aggregated from the other fields of the events
+
+use crate::KeyStyle;
+use serde_json::{Map, Value};
+
+/// Kind of the values which are aggregated into a `related.*` field.
+#[derive(Clone, Copy)]
+enum Related {
+    Ip,
+    User,
+    Hash,
+}
+
+impl Related {
+    const ALL: [Related; 3] = [Related::Ip, Related::User, Related::Hash];
+
+    /// Returns the name of the field under `related`
+    fn name(self) -> &'static str {
+        match self {
+            Related::Ip => "ip",
+            Related::User => "user",
+            Related::Hash => "hash",
+        }
+    }
+
+    /// Returns whether the flattened `key` has values of the kind, e.g. `source.ip`, `user.name`, or `file.hash.sha256`
+    fn matches(self, key: &str) -> bool {
+        if key.starts_with("related.") {
+            return false;
+        }
+        let mut segments = key.rsplit('.');
+        let last = segments.next().unwrap_or_default();
+        match self {
+            Related::Ip => last == "ip",
+            Related::User => matches!(last, "name" | "id") && segments.any(|s| s == "user"),
+            Related::Hash => segments.next() == Some("hash"),
+        }
+    }
+}
+
+/// Adds the `related.ip`, `related.user`, and `related.hash` arrays of the IP addresses, the users, and the hashes found in `event`,
+/// as ECS recommends for pivoting between events. The values set in `related.*` already are kept.
+pub(crate) fn add_related(event: &mut Map<String, Value>) {
+    let flat = KeyStyle::Flat.apply(event.clone());
+
+    for related in Related::ALL {
+        let key = format!("related.{}", related.name());
+        let mut values: Vec<Value> = Vec::new();
+        let mut push = |value: &Value| match value {
+            Value::String(s) if !s.is_empty() && !values.contains(value) => {
+                values.push(s.as_str().into());
+            }
+            _ => {}
+        };
+
+        let existing = flat.get(&key);
+        let found = flat.iter().filter(|(k, _)| related.matches(k));
+        for value in existing.into_iter().chain(found.map(|(_, v)| v)) {
+            match value {
+                Value::Array(items) => items.iter().for_each(&mut push),
+                value => push(value),
+            }
+        }
+        if values.is_empty() {
+            continue;
+        }
+
+        // Replace the existing field, which may be nested, with the dotted one
+        if let Some(Value::Object(nested)) = event.get_mut("related") {
+            nested.remove(related.name());
+            if nested.is_empty() {
+                event.remove("related");
+            }
+        }
+        event.insert(key, Value::Array(values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn related(event: Value) -> Value {
+        let mut event = event.as_object().unwrap().clone();
+        add_related(&mut event);
+        Value::Object(event)
+    }
+
+    #[test]
+    fn test_add_related() {
+        assert_eq!(
+            related(json!({
+                "source.ip": "192.0.2.1",
+                "destination": { "ip": "198.51.100.7" },
+                "host.ip": ["192.0.2.1", "10.0.0.1"],
+                "user.name": "alice",
+                "user.target.name": "bob",
+                "username": "not a user",
+                "file.hash.sha256": "e3b0c442",
+                "process": { "hash": { "md5": "d41d8cd9" } },
+            })),
+            json!({
+                "source.ip": "192.0.2.1",
+                "destination": { "ip": "198.51.100.7" },
+                "host.ip": ["192.0.2.1", "10.0.0.1"],
+                "user.name": "alice",
+                "user.target.name": "bob",
+                "username": "not a user",
+                "file.hash.sha256": "e3b0c442",
+                "process": { "hash": { "md5": "d41d8cd9" } },
+                "related.ip": ["192.0.2.1", "198.51.100.7", "10.0.0.1"],
+                "related.user": ["alice", "bob"],
+                "related.hash": ["e3b0c442", "d41d8cd9"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_add_related_existing() {
+        assert_eq!(
+            related(json!({
+                "related": { "user": ["root"], "hosts": ["web-1"] },
+                "user.id": "42",
+            })),
+            json!({
+                "related": { "hosts": ["web-1"] },
+                "user.id": "42",
+                "related.user": ["root", "42"],
+            })
+        );
+        assert_eq!(
+            related(json!({ "message": "hi" })),
+            json!({ "message": "hi" })
+        );
+    }
+}
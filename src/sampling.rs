@@ -0,0 +1,375 @@
+//! Adaptive sampling of low-severity events under load
+
+use log::Level;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Length of the window which the event rate is measured over.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Configuration of adaptive sampling.
+///
+/// When the rate of events reaching the logger crosses [`high_rate`](Self::new) per second, or the queue of
+/// [deferred formatting](crate::Builder::deferred_formatting) grows longer than [`queue_depth`](Self::queue_depth),
+/// only one in [`keep_one_in`](Self::keep_one_in) events at [`level`](Self::level) or less severe is written.
+/// Every event is written again once the rate falls to [`low_rate`](Self::low_rate) and the queue shrinks to
+/// [`low_queue_depth`](Self::low_queue_depth).
+/// A `WARN` event with the target `ecs_logger` is written when the mode changes.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::AdaptiveSampling;
+///
+/// ecs_logger::Builder::from_env()
+///     .deferred_formatting(4096)
+///     .adaptive_sampling(AdaptiveSampling::new(10_000).queue_depth(1024).keep_one_in(100))
+///     .init();
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampling {
+    high_rate: u64,
+    low_rate: u64,
+    high_queue_depth: Option<usize>,
+    low_queue_depth: Option<usize>,
+    level: Level,
+    keep_one_in: u64,
+}
+
+impl AdaptiveSampling {
+    /// Creates a configuration which starts sampling when more than `high_rate` events per second reach the logger.
+    pub fn new(high_rate: u64) -> Self {
+        AdaptiveSampling {
+            high_rate,
+            low_rate: high_rate / 2,
+            high_queue_depth: None,
+            low_queue_depth: None,
+            level: Level::Info,
+            keep_one_in: 10,
+        }
+    }
+
+    /// Sets the rate of events per second at or below which sampling stops.
+    ///
+    /// Defaults to half of the high rate.
+    pub fn low_rate(mut self, low_rate: u64) -> Self {
+        self.low_rate = low_rate;
+        self
+    }
+
+    /// Also starts sampling when more than `high_queue_depth` events are waiting in the queue of
+    /// [deferred formatting](crate::Builder::deferred_formatting).
+    ///
+    /// Ignored when the formatting is not deferred.
+    pub fn queue_depth(mut self, high_queue_depth: usize) -> Self {
+        self.high_queue_depth = Some(high_queue_depth);
+        self
+    }
+
+    /// Sets the number of queued events at or below which sampling stops, once the rate has fallen as well.
+    ///
+    /// Defaults to half of the depth set with [`queue_depth`](Self::queue_depth).
+    pub fn low_queue_depth(mut self, low_queue_depth: usize) -> Self {
+        self.low_queue_depth = Some(low_queue_depth);
+        self
+    }
+
+    /// Sets the most severe level which is sampled.
+    ///
+    /// Defaults to [`Level::Info`], which means `INFO`, `DEBUG`, and `TRACE` events are sampled.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the sampling rate while under load.
+    ///
+    /// Defaults to `10`.
+    pub fn keep_one_in(mut self, n: u64) -> Self {
+        self.keep_one_in = n.max(1);
+        self
+    }
+}
+
+/// Change of the sampling mode, reported once by [`Sampler::sample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ModeChange {
+    Started {
+        rate: u64,
+        /// Number of the queued events, if the queue is watched
+        queue_depth: Option<usize>,
+        level: Level,
+        keep_one_in: u64,
+    },
+    Stopped {
+        rate: u64,
+        queue_depth: Option<usize>,
+    },
+}
+
+/// State of adaptive sampling, shared by the logging threads without a lock.
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    config: AdaptiveSampling,
+    /// Queue of deferred formatting, watched if a queue depth is configured
+    queue: Option<Arc<AtomicUsize>>,
+    /// Origin of `window_start`
+    epoch: Instant,
+    /// Start of the current window in nanoseconds since `epoch`
+    window_start: AtomicU64,
+    window_count: AtomicU64,
+    /// Rate of the last complete window
+    last_rate: AtomicU64,
+    sampling: AtomicBool,
+    /// Position of the next sampled event in the cycle of `keep_one_in` events
+    position: AtomicU64,
+}
+
+impl Sampler {
+    pub(crate) fn new(config: AdaptiveSampling, queue: Option<Arc<AtomicUsize>>) -> Self {
+        Sampler {
+            queue: queue.filter(|_| config.high_queue_depth.is_some()),
+            config,
+            epoch: Instant::now(),
+            window_start: AtomicU64::new(0),
+            window_count: AtomicU64::new(0),
+            last_rate: AtomicU64::new(0),
+            sampling: AtomicBool::new(false),
+            position: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns whether an event at `level` is written, and the mode change caused by the event if any.
+    pub(crate) fn sample(&self, level: Level) -> (bool, Option<ModeChange>) {
+        let queue_depth = self
+            .queue
+            .as_ref()
+            .map(|queue| queue.load(Ordering::Relaxed));
+        self.sample_at(level, Instant::now(), queue_depth)
+    }
+
+    fn sample_at(
+        &self,
+        level: Level,
+        now: Instant,
+        queue_depth: Option<usize>,
+    ) -> (bool, Option<ModeChange>) {
+        let now =
+            u64::try_from(now.saturating_duration_since(self.epoch).as_nanos()).unwrap_or(u64::MAX);
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        let elapsed = now.saturating_sub(window_start);
+
+        // Only the thread which moves the window measures the rate of the finished one
+        let change = if elapsed >= WINDOW.as_nanos() as u64
+            && self
+                .window_start
+                .compare_exchange(window_start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            let count = self.window_count.swap(0, Ordering::Relaxed);
+            let rate = (count as f64 / Duration::from_nanos(elapsed).as_secs_f64()) as u64;
+            self.last_rate.store(rate, Ordering::Relaxed);
+            self.update(rate, queue_depth)
+        } else if self.over_queue_depth(queue_depth) {
+            // The queue is checked for every event, since it fills up faster than a window when the writer falls behind
+            self.update(self.last_rate.load(Ordering::Relaxed), queue_depth)
+        } else {
+            None
+        };
+        self.window_count.fetch_add(1, Ordering::Relaxed);
+
+        if !self.sampling.load(Ordering::Relaxed) || level < self.config.level {
+            return (true, change);
+        }
+
+        let keep_one_in = self.config.keep_one_in;
+        let position = self
+            .position
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |position| {
+                Some((position + 1) % keep_one_in)
+            })
+            .expect("the position should always be updated");
+        (position == 0, change)
+    }
+
+    /// Starts or stops sampling for the measured load, and returns the change if this call made it.
+    fn update(&self, rate: u64, queue_depth: Option<usize>) -> Option<ModeChange> {
+        let under_load = rate > self.config.high_rate || self.over_queue_depth(queue_depth);
+        let low_queue_depth = self
+            .config
+            .low_queue_depth
+            .or(self.config.high_queue_depth.map(|high| high / 2));
+        let calm = rate <= self.config.low_rate
+            && queue_depth
+                .zip(low_queue_depth)
+                .is_none_or(|(depth, low)| depth <= low);
+
+        if under_load && self.set_sampling(true) {
+            self.position.store(0, Ordering::Relaxed);
+            Some(ModeChange::Started {
+                rate,
+                queue_depth,
+                level: self.config.level,
+                keep_one_in: self.config.keep_one_in,
+            })
+        } else if !under_load && calm && self.set_sampling(false) {
+            Some(ModeChange::Stopped { rate, queue_depth })
+        } else {
+            None
+        }
+    }
+
+    fn over_queue_depth(&self, queue_depth: Option<usize>) -> bool {
+        queue_depth
+            .zip(self.config.high_queue_depth)
+            .is_some_and(|(depth, high)| depth > high)
+    }
+
+    /// Returns `true` if the mode was changed to `sampling` by this call.
+    fn set_sampling(&self, sampling: bool) -> bool {
+        self.sampling
+            .compare_exchange(!sampling, sampling, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl ModeChange {
+    /// Returns the message of the notice event.
+    pub(crate) fn message(&self) -> String {
+        match self {
+            ModeChange::Started {
+                rate,
+                queue_depth,
+                level,
+                keep_one_in,
+            } => format!(
+                "{}; sampling {} and less severe events at 1 in {}",
+                load(*rate, *queue_depth),
+                level,
+                keep_one_in
+            ),
+            ModeChange::Stopped { rate, queue_depth } => {
+                format!("{}; sampling stopped", load(*rate, *queue_depth))
+            }
+        }
+    }
+}
+
+/// Describes the load in the notice events
+fn load(rate: u64, queue_depth: Option<usize>) -> String {
+    match queue_depth {
+        Some(depth) => format!("Log rate is {} events/s with {} events queued", rate, depth),
+        None => format!("Log rate is {} events/s", rate),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampler() {
+        let sampler = Sampler::new(AdaptiveSampling::new(9).low_rate(5).keep_one_in(3), None);
+        let at = |ms| sampler.epoch + Duration::from_millis(ms);
+        let sample = |level, ms| sampler.sample_at(level, at(ms), None);
+
+        // 10 events in the first window
+        for i in 0..10 {
+            assert_eq!(sample(Level::Debug, i * 10), (true, None));
+        }
+
+        // Rate exceeds the high rate
+        assert_eq!(
+            sample(Level::Debug, 1000),
+            (
+                true,
+                Some(ModeChange::Started {
+                    rate: 10,
+                    queue_depth: None,
+                    level: Level::Info,
+                    keep_one_in: 3
+                })
+            )
+        );
+        assert_eq!(sample(Level::Info, 1001), (false, None));
+        assert_eq!(sample(Level::Warn, 1002), (true, None));
+        assert_eq!(sample(Level::Trace, 1003), (false, None));
+        assert_eq!(sample(Level::Trace, 1004), (true, None));
+
+        // Rate falls to the low rate
+        assert_eq!(
+            sample(Level::Debug, 2000),
+            (
+                true,
+                Some(ModeChange::Stopped {
+                    rate: 5,
+                    queue_depth: None
+                })
+            )
+        );
+        assert_eq!(sample(Level::Debug, 2001), (true, None));
+    }
+
+    #[test]
+    fn test_sampler_queue_depth() {
+        let queue = Arc::new(AtomicUsize::new(0));
+        let sampler = Sampler::new(
+            AdaptiveSampling::new(1000).queue_depth(8).keep_one_in(2),
+            Some(Arc::clone(&queue)),
+        );
+        let at = |ms| sampler.epoch + Duration::from_millis(ms);
+
+        assert_eq!(sampler.sample_at(Level::Info, at(0), Some(8)), (true, None));
+
+        // The queue grows in the middle of the window
+        assert_eq!(
+            sampler.sample_at(Level::Info, at(10), Some(9)),
+            (
+                true,
+                Some(ModeChange::Started {
+                    rate: 0,
+                    queue_depth: Some(9),
+                    level: Level::Info,
+                    keep_one_in: 2
+                })
+            )
+        );
+        assert_eq!(
+            sampler.sample_at(Level::Info, at(20), Some(9)),
+            (false, None)
+        );
+
+        // The rate is low, but the queue is still longer than the low depth
+        assert_eq!(
+            sampler.sample_at(Level::Info, at(1010), Some(5)),
+            (true, None)
+        );
+
+        // The queue shrinks to the low depth
+        assert_eq!(
+            sampler.sample_at(Level::Info, at(2010), Some(4)),
+            (
+                true,
+                Some(ModeChange::Stopped {
+                    rate: 1,
+                    queue_depth: Some(4)
+                })
+            )
+        );
+
+        // The depth is read from the queue
+        queue.store(9, Ordering::Relaxed);
+        assert!(matches!(
+            sampler.sample(Level::Info),
+            (true, Some(ModeChange::Started { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_queue_depth_without_queue() {
+        let sampler = Sampler::new(AdaptiveSampling::new(1000).queue_depth(8), None);
+        assert!(sampler.queue.is_none());
+        assert_eq!(sampler.sample(Level::Info), (true, None));
+    }
+}
@@ -0,0 +1,504 @@
+//! Security audit events and detections
+
+use crate::extra_fields::with_scoped_fields;
+use crate::Outcome;
+use log::Level;
+use serde_json::{Map, Value};
+use std::net::IpAddr;
+use std::panic::Location;
+
+/// Security audit event with the categorization fields expected by Elastic SIEM detection rules.
+///
+/// | Constructor | `event.action` | `event.category` | `event.type` | `event.outcome` | Level |
+/// |---|---|---|---|---|---|
+/// | [`authentication_success`](Self::authentication_success) | `user-login` | `authentication` | `start` | `success` | `INFO` |
+/// | [`authentication_failure`](Self::authentication_failure) | `user-login` | `authentication` | `start` | `failure` | `WARN` |
+/// | [`authorization_denied`](Self::authorization_denied) | `authorization-denied` | `iam` | `info` | `failure` | `WARN` |
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::SecurityEvent;
+///
+/// ecs_logger::init();
+///
+/// SecurityEvent::authentication_failure("alice")
+///     .reason("invalid password")
+///     .client_ip("192.0.2.1".parse().unwrap())
+///     .log();
+/// // {"log.level":"WARN","message":"Authentication failed for user alice","event.category":["authentication"],...}
+/// ```
+#[derive(Debug, Clone)]
+pub struct SecurityEvent {
+    action: &'static str,
+    category: &'static str,
+    event_type: &'static str,
+    outcome: Outcome,
+    level: Level,
+    message: String,
+    user_name: String,
+    user_id: Option<String>,
+    reason: Option<String>,
+    client_ip: Option<IpAddr>,
+}
+
+impl SecurityEvent {
+    /// Creates an event of a successful login by `user_name`.
+    pub fn authentication_success(user_name: impl Into<String>) -> Self {
+        let user_name = user_name.into();
+        SecurityEvent::new(
+            "user-login",
+            "authentication",
+            "start",
+            Outcome::Success,
+            Level::Info,
+            format!("Authentication succeeded for user {}", user_name),
+            user_name,
+        )
+    }
+
+    /// Creates an event of a failed login attempt by `user_name`.
+    pub fn authentication_failure(user_name: impl Into<String>) -> Self {
+        let user_name = user_name.into();
+        SecurityEvent::new(
+            "user-login",
+            "authentication",
+            "start",
+            Outcome::Failure,
+            Level::Warn,
+            format!("Authentication failed for user {}", user_name),
+            user_name,
+        )
+    }
+
+    /// Creates an event of `user_name` being denied access to `resource`.
+    pub fn authorization_denied(user_name: impl Into<String>, resource: &str) -> Self {
+        let user_name = user_name.into();
+        SecurityEvent::new(
+            "authorization-denied",
+            "iam",
+            "info",
+            Outcome::Failure,
+            Level::Warn,
+            format!("User {} was denied access to {}", user_name, resource),
+            user_name,
+        )
+    }
+
+    fn new(
+        action: &'static str,
+        category: &'static str,
+        event_type: &'static str,
+        outcome: Outcome,
+        level: Level,
+        message: String,
+        user_name: String,
+    ) -> Self {
+        SecurityEvent {
+            action,
+            category,
+            event_type,
+            outcome,
+            level,
+            message,
+            user_name,
+            user_id: None,
+            reason: None,
+            client_ip: None,
+        }
+    }
+
+    /// Sets `user.id`.
+    pub fn user_id(mut self, id: impl Into<String>) -> Self {
+        self.user_id = Some(id.into());
+        self
+    }
+
+    /// Sets `event.reason`, e.g. why the authentication failed.
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// Sets `source.ip` and `client.ip`.
+    pub fn client_ip(mut self, ip: IpAddr) -> Self {
+        self.client_ip = Some(ip);
+        self
+    }
+
+    /// Returns the ECS fields of the event with dotted keys.
+    pub fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let mut insert = |key: &str, value: Value| {
+            fields.insert(key.to_string(), value);
+        };
+
+        insert("event.kind", "event".into());
+        insert("event.action", self.action.into());
+        insert("event.category", vec![self.category].into());
+        insert("event.type", vec![self.event_type].into());
+        insert("event.outcome", self.outcome.as_str().into());
+        insert("user.name", self.user_name.clone().into());
+        if let Some(id) = &self.user_id {
+            insert("user.id", id.clone().into());
+        }
+        if let Some(reason) = &self.reason {
+            insert("event.reason", reason.clone().into());
+        }
+        if let Some(ip) = self.client_ip {
+            insert("source.ip", ip.to_string().into());
+            insert("client.ip", ip.to_string().into());
+        }
+
+        fields
+    }
+
+    /// Logs the event.
+    ///
+    /// Like [`ResultExt`](crate::ResultExt), the event has the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn log(self) {
+        let location = Location::caller();
+
+        with_scoped_fields(self.fields(), || {
+            crate::log_at(self.level, format_args!("{}", self.message), location)
+        });
+    }
+}
+
+/// Security detection, i.e. an alert raised by a rule, with the `rule.*`, `event.severity`, and `threat.*` fields read by Elastic SIEM.
+///
+/// | Field | Source |
+/// |---|---|
+/// | `event.kind` | `alert` |
+/// | `event.category` | [`category`](Self::category) |
+/// | `event.severity` | [`severity`](Self::severity) |
+/// | `event.risk_score` | [`risk_score`](Self::risk_score) |
+/// | `rule.id`, `rule.name` | [`new`](Self::new) |
+/// | `rule.description`, `rule.ruleset`, `rule.reference` | their setters |
+/// | `threat.framework` | `MITRE ATT&CK` if a tactic or a technique is set, or [`framework`](Self::framework) |
+/// | `threat.tactic.id`, `threat.tactic.name` | [`tactic`](Self::tactic) |
+/// | `threat.technique.id`, `threat.technique.name` | [`technique`](Self::technique) |
+///
+/// The message is `Detection: <rule.name>` unless set with [`message`](Self::message), and the level is `WARN` unless set with [`level`](Self::level).
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::Detection;
+///
+/// ecs_logger::init();
+///
+/// Detection::new("brute-force-1", "Brute force login")
+///     .severity(73)
+///     .category("authentication")
+///     .tactic("TA0006", "Credential Access")
+///     .technique("T1110", "Brute Force")
+///     .log();
+/// // {"log.level":"WARN","message":"Detection: Brute force login","event.kind":"alert","event.severity":73,
+/// //  "rule.id":"brute-force-1","rule.name":"Brute force login","threat.framework":"MITRE ATT&CK",...}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Detection {
+    rule_id: String,
+    rule_name: String,
+    description: Option<String>,
+    ruleset: Option<String>,
+    reference: Option<String>,
+    categories: Vec<String>,
+    severity: Option<u32>,
+    risk_score: Option<f64>,
+    framework: Option<String>,
+    tactics: Vec<(String, String)>,
+    techniques: Vec<(String, String)>,
+    level: Level,
+    message: Option<String>,
+}
+
+impl Detection {
+    /// Creates a detection by the rule of `rule_id` and `rule_name`.
+    pub fn new(rule_id: impl Into<String>, rule_name: impl Into<String>) -> Self {
+        Detection {
+            rule_id: rule_id.into(),
+            rule_name: rule_name.into(),
+            description: None,
+            ruleset: None,
+            reference: None,
+            categories: Vec::new(),
+            severity: None,
+            risk_score: None,
+            framework: None,
+            tactics: Vec::new(),
+            techniques: Vec::new(),
+            level: Level::Warn,
+            message: None,
+        }
+    }
+
+    /// Sets `rule.description`.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets `rule.ruleset`, the name of the set which the rule belongs to.
+    pub fn ruleset(mut self, ruleset: impl Into<String>) -> Self {
+        self.ruleset = Some(ruleset.into());
+        self
+    }
+
+    /// Sets `rule.reference`, the URL of the documentation of the rule.
+    pub fn reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    /// Adds a value of `event.category`, e.g. `authentication` or `intrusion_detection`.
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    /// Sets `event.severity`, the severity of the detection as defined by the rule.
+    pub fn severity(mut self, severity: u32) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Sets `event.risk_score`.
+    pub fn risk_score(mut self, risk_score: f64) -> Self {
+        self.risk_score = Some(risk_score);
+        self
+    }
+
+    /// Sets `threat.framework`, which is `MITRE ATT&CK` by default.
+    pub fn framework(mut self, framework: impl Into<String>) -> Self {
+        self.framework = Some(framework.into());
+        self
+    }
+
+    /// Adds a tactic of the threat, e.g. `TA0006` and `Credential Access`.
+    pub fn tactic(mut self, id: impl Into<String>, name: impl Into<String>) -> Self {
+        self.tactics.push((id.into(), name.into()));
+        self
+    }
+
+    /// Adds a technique of the threat, e.g. `T1110` and `Brute Force`.
+    pub fn technique(mut self, id: impl Into<String>, name: impl Into<String>) -> Self {
+        self.techniques.push((id.into(), name.into()));
+        self
+    }
+
+    /// Sets the level of the log record.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the message of the log record.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Returns the ECS fields of the detection with dotted keys.
+    pub fn fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let mut insert = |key: &str, value: Value| {
+            fields.insert(key.to_string(), value);
+        };
+
+        insert("event.kind", "alert".into());
+        if !self.categories.is_empty() {
+            insert("event.category", self.categories.clone().into());
+        }
+        if let Some(severity) = self.severity {
+            insert("event.severity", severity.into());
+        }
+        if let Some(risk_score) = self.risk_score {
+            insert("event.risk_score", risk_score.into());
+        }
+        insert("rule.id", self.rule_id.clone().into());
+        insert("rule.name", self.rule_name.clone().into());
+        let rule = [
+            ("rule.description", &self.description),
+            ("rule.ruleset", &self.ruleset),
+            ("rule.reference", &self.reference),
+        ];
+        for (key, value) in rule {
+            if let Some(value) = value {
+                insert(key, value.clone().into());
+            }
+        }
+
+        let framework = match &self.framework {
+            Some(framework) => Some(framework.as_str()),
+            None if !self.tactics.is_empty() || !self.techniques.is_empty() => Some("MITRE ATT&CK"),
+            None => None,
+        };
+        if let Some(framework) = framework {
+            insert("threat.framework", framework.into());
+        }
+        for (key, entries) in [
+            ("threat.tactic", &self.tactics),
+            ("threat.technique", &self.techniques),
+        ] {
+            if entries.is_empty() {
+                continue;
+            }
+            let (ids, names): (Vec<_>, Vec<_>) = entries.iter().cloned().unzip();
+            insert(&format!("{}.id", key), ids.into());
+            insert(&format!("{}.name", key), names.into());
+        }
+
+        fields
+    }
+
+    /// Logs the detection.
+    ///
+    /// Like [`SecurityEvent::log`], the record has the target `ecs_logger` and the origin of the caller.
+    #[track_caller]
+    pub fn log(self) {
+        let location = Location::caller();
+        let message = match &self.message {
+            Some(message) => message.clone(),
+            None => format!("Detection: {}", self.rule_name),
+        };
+
+        with_scoped_fields(self.fields(), || {
+            crate::log_at(self.level, format_args!("{}", message), location)
+        });
+    }
+}
+
+/// Logs an audit event at the `INFO` level, which must name the actor, the action, and the target in this order.
+///
+/// The event has `event.kind` set to `event`, `event.category` set to `["iam"]`, `event.action`, `user.name` of the actor,
+/// and `user.target.name` of the target, e.g. the user whose account is changed.
+/// Inline fields may follow like in [`ecs_log!`](crate::ecs_log!), overriding those, e.g. `event.type = ["deletion"]`.
+/// The message and its template are given after a semicolon.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+///
+/// let (admin, user) = ("alice", "bob");
+/// ecs_logger::audit!(actor: admin, action: "user-delete", target: user, event.type = ["deletion"]; "{} deleted {}", admin, user);
+/// // {"log.level":"INFO","message":"alice deleted bob","event.kind":"event","event.category":["iam"],"event.action":"user-delete",
+/// //  "user.name":"alice","user.target.name":"bob","event.type":["deletion"],...}
+/// ```
+///
+/// The event does not compile without any of the mandatory fields:
+///
+/// ```compile_fail
+/// ecs_logger::audit!(actor: "alice", action: "user-delete"; "deleted a user");
+/// ```
+#[macro_export]
+macro_rules! audit {
+    (
+        actor: $actor:expr,
+        action: $action:expr,
+        target: $target:expr
+        $(, $($key:ident).+ = $value:expr)* ;
+        $($arg:tt)+
+    ) => {
+        $crate::ecs_log!(
+            $crate::__private::log::Level::Info,
+            event.kind = "event",
+            event.category = ["iam"],
+            event.action = $action,
+            user.name = $actor,
+            user.target.name = $target
+            $(, $($key).+ = $value)* ;
+            $($arg)+
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_authentication_success() {
+        let event = SecurityEvent::authentication_success("alice").user_id("42");
+
+        assert_eq!(event.level, Level::Info);
+        assert_eq!(event.message, "Authentication succeeded for user alice");
+        assert_eq!(
+            Value::Object(event.fields()),
+            json!({
+                "event.kind": "event",
+                "event.action": "user-login",
+                "event.category": ["authentication"],
+                "event.type": ["start"],
+                "event.outcome": "success",
+                "user.name": "alice",
+                "user.id": "42",
+            })
+        );
+    }
+
+    #[test]
+    fn test_authorization_denied() {
+        let event = SecurityEvent::authorization_denied("bob", "/admin")
+            .reason("missing role")
+            .client_ip("192.0.2.1".parse().unwrap());
+
+        assert_eq!(event.level, Level::Warn);
+        assert_eq!(event.message, "User bob was denied access to /admin");
+        assert_eq!(
+            Value::Object(event.fields()),
+            json!({
+                "event.kind": "event",
+                "event.action": "authorization-denied",
+                "event.category": ["iam"],
+                "event.type": ["info"],
+                "event.outcome": "failure",
+                "user.name": "bob",
+                "event.reason": "missing role",
+                "source.ip": "192.0.2.1",
+                "client.ip": "192.0.2.1",
+            })
+        );
+    }
+
+    #[test]
+    fn test_detection() {
+        let detection = Detection::new("brute-force-1", "Brute force login")
+            .description("Many failed logins from one source")
+            .reference("https://example.com/rules/brute-force-1")
+            .category("authentication")
+            .severity(73)
+            .risk_score(47.5)
+            .tactic("TA0006", "Credential Access")
+            .technique("T1110", "Brute Force")
+            .technique("T1110.001", "Password Guessing");
+
+        assert_eq!(detection.level, Level::Warn);
+        assert_eq!(
+            Value::Object(detection.fields()),
+            json!({
+                "event.kind": "alert",
+                "event.category": ["authentication"],
+                "event.severity": 73,
+                "event.risk_score": 47.5,
+                "rule.id": "brute-force-1",
+                "rule.name": "Brute force login",
+                "rule.description": "Many failed logins from one source",
+                "rule.reference": "https://example.com/rules/brute-force-1",
+                "threat.framework": "MITRE ATT&CK",
+                "threat.tactic.id": ["TA0006"],
+                "threat.tactic.name": ["Credential Access"],
+                "threat.technique.id": ["T1110", "T1110.001"],
+                "threat.technique.name": ["Brute Force", "Password Guessing"],
+            })
+        );
+        assert_eq!(
+            Value::Object(Detection::new("r1", "Rule").fields()),
+            json!({ "event.kind": "alert", "rule.id": "r1", "rule.name": "Rule" })
+        );
+    }
+}
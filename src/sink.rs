@@ -0,0 +1,43 @@
+//! Destination of the rendered log lines
+
+use std::io::{self, Write};
+
+/// Callback which receives each rendered event.
+pub(crate) type Subscriber = Box<dyn Fn(&str) + Send + 'static>;
+
+/// Writer and subscribers which receive the rendered log lines.
+pub(crate) struct Sink {
+    writer: Box<dyn Write + Send + 'static>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl Sink {
+    pub(crate) fn new(
+        writer: Box<dyn Write + Send + 'static>,
+        subscribers: Vec<Subscriber>,
+    ) -> Self {
+        Sink {
+            writer,
+            subscribers,
+        }
+    }
+
+    /// Writes `line`, which is terminated by a newline, with a single call and passes it to the subscribers without the newline.
+    pub(crate) fn write_line(&mut self, line: &[u8]) {
+        let _ = self.writer.write_all(line);
+
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        if let Ok(line) = std::str::from_utf8(line) {
+            for subscriber in &self.subscribers {
+                subscriber(line);
+            }
+        }
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,74 @@
+//! SipHash-2-4, the keyed hash of the values hidden from the logs
+
+/// Returns SipHash-2-4 of `data` keyed with `key`.
+///
+/// Unlike an unkeyed hash with a salt, the output cannot be inverted or forged without the key,
+/// and it is stable across builds and platforms, unlike [`std::hash::DefaultHasher`].
+pub(crate) fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    let (k0, k1) = key.split_at(8);
+    let k0 = u64::from_le_bytes(k0.try_into().expect("half of the key should be 8 bytes"));
+    let k1 = u64::from_le_bytes(k1.try_into().expect("half of the key should be 8 bytes"));
+    let mut v = [
+        k0 ^ 0x736f6d6570736575,
+        k1 ^ 0x646f72616e646f6d,
+        k0 ^ 0x6c7967656e657261,
+        k1 ^ 0x7465646279746573,
+    ];
+
+    let mut compress = |m: u64| {
+        v[3] ^= m;
+        sip_round(&mut v);
+        sip_round(&mut v);
+        v[0] ^= m;
+    };
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(u64::from_le_bytes(
+            chunk.try_into().expect("chunk should be 8 bytes"),
+        ));
+    }
+    // The last block holds the remaining bytes and the length of the data modulo 256
+    let remainder = chunks.remainder();
+    let mut last = [0; 8];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[7] = data.len() as u8;
+    compress(u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn sip_round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13) ^ v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16) ^ v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21) ^ v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17) ^ v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_siphash24() {
+        // Test vectors of the reference implementation, with the key 00 01 .. 0f and the data 00 01 .. (n - 1)
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let data: Vec<u8> = (0..64).collect();
+
+        assert_eq!(siphash24(&key, &data[..0]), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(&key, &data[..1]), 0x74f839c593dc67fd);
+        assert_eq!(siphash24(&key, &data[..8]), 0x93f5f5799a932462);
+        assert_eq!(siphash24(&key, &data[..15]), 0xa129ca6149be45e5);
+        assert_eq!(siphash24(&key, &data[..63]), 0x958a324ceb064572);
+    }
+}
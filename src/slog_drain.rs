@@ -0,0 +1,221 @@
+//! `slog` drain formatting `slog` records as ECS documents
+
+use crate::extra_fields::with_scoped_fields;
+use crate::FormatOptions;
+use serde_json::{Map, Number, Value};
+use slog::{Drain, Key, Level, OwnedKVList, Record, Serializer, KV};
+use std::fmt;
+use std::io::{self, Write};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Mutex, PoisonError};
+
+/// Drain of [`slog`](https://docs.rs/slog) which writes the records as ECS log lines, formatted like the records of the [`log`] crate,
+/// for the codebases which migrate from `slog`.
+///
+/// The key-values of the record and of its loggers are added as top-level fields, e.g. `user.name` of
+/// `info!(log, "logged in"; "user.name" => "alice")`. The key-values of the record take precedence over those of the loggers,
+/// and those of the child loggers over those of their parents. They all take precedence over the extra fields and the context fields.
+/// The tag of the record is the target if it is not empty, and the module is otherwise.
+/// `CRITICAL` records are logged at the `ERROR` level.
+///
+/// Available with the `slog` feature.
+///
+/// # Example
+///
+/// ```
+/// use slog::{info, o, Drain};
+///
+/// let drain = ecs_logger::EcsDrain::new(std::io::stdout()).fuse();
+/// let log = slog::Logger::root(drain, o!("service.name" => "my-app"));
+///
+/// info!(log, "Hello {}!", "world"; "user.name" => "alice");
+/// // {"log.level":"INFO","message":"Hello world!","service.name":"my-app","user.name":"alice",...}
+/// ```
+pub struct EcsDrain<W> {
+    // `slog` requires the drains to be unwind safe. The options are never mutated after the drain is built.
+    options: AssertUnwindSafe<FormatOptions>,
+    writer: Mutex<W>,
+}
+
+/// Serializer which collects the key-values into JSON
+struct FieldSerializer {
+    fields: Map<String, Value>,
+    /// Whether a key-value overrides the one of the same key already collected
+    overwrite: bool,
+}
+
+impl<W: Write> EcsDrain<W> {
+    /// Creates a drain which writes to `writer` with the default [`FormatOptions`].
+    pub fn new(writer: W) -> Self {
+        EcsDrain {
+            options: AssertUnwindSafe(FormatOptions::default()),
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Sets the options of the format.
+    pub fn format_options(mut self, options: FormatOptions) -> Self {
+        self.options = AssertUnwindSafe(options);
+        self
+    }
+}
+
+impl<W: Write> Drain for EcsDrain<W> {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let mut serializer = FieldSerializer {
+            fields: Map::new(),
+            overwrite: true,
+        };
+        record.kv().serialize(record, &mut serializer)?;
+        // The values of the child loggers come first
+        serializer.overwrite = false;
+        values.serialize(record, &mut serializer)?;
+
+        let target = match record.tag() {
+            "" => record.module(),
+            tag => tag,
+        };
+        let mut buf = Vec::new();
+        with_scoped_fields(serializer.fields, || {
+            crate::write_event(
+                &mut buf,
+                &log::Record::builder()
+                    .args(*record.msg())
+                    .level(to_log_level(record.level()))
+                    .target(target)
+                    .module_path(Some(record.module()))
+                    .file(Some(record.file()))
+                    .line(Some(record.line()))
+                    .build(),
+                &self.options,
+            )
+        })?;
+
+        // Writes the whole line at once so that the lines of concurrent records are not interleaved
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.write_all(&buf)?;
+        writer.flush()
+    }
+}
+
+impl FieldSerializer {
+    fn insert(&mut self, key: Key, value: Value) -> slog::Result {
+        let key = key.to_string();
+        if self.overwrite {
+            self.fields.insert(key, value);
+        } else {
+            self.fields.entry(key).or_insert(value);
+        }
+        Ok(())
+    }
+}
+
+/// Implements the methods of [`Serializer`] which emit integers as JSON numbers
+macro_rules! emit_numbers {
+    ($($method:ident: $ty:ty),+) => {$(
+        fn $method(&mut self, key: Key, val: $ty) -> slog::Result {
+            self.insert(key, val.into())
+        }
+    )+};
+}
+
+impl Serializer for FieldSerializer {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.insert(key, val.to_string().into())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        self.insert(key, Value::Null)
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.insert(key, Value::Null)
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.insert(key, val.into())
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        self.insert(key, val.into())
+    }
+
+    fn emit_char(&mut self, key: Key, val: char) -> slog::Result {
+        self.insert(key, val.to_string().into())
+    }
+
+    // The other methods render the values with `emit_arguments` by default
+    emit_numbers!(
+        emit_u8: u8,
+        emit_i8: i8,
+        emit_u16: u16,
+        emit_i16: i16,
+        emit_u32: u32,
+        emit_i32: i32,
+        emit_u64: u64,
+        emit_i64: i64,
+        emit_usize: usize,
+        emit_isize: isize
+    );
+
+    fn emit_f32(&mut self, key: Key, val: f32) -> slog::Result {
+        self.emit_f64(key, val.into())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        // NaN and infinities are not valid in JSON
+        let value = Number::from_f64(val).map_or_else(|| val.to_string().into(), Value::Number);
+        self.insert(key, value)
+    }
+}
+
+/// Converts a level of `slog` into the closest level of `log`
+fn to_log_level(level: Level) -> log::Level {
+    match level {
+        Level::Critical | Level::Error => log::Level::Error,
+        Level::Warning => log::Level::Warn,
+        Level::Info => log::Level::Info,
+        Level::Debug => log::Level::Debug,
+        Level::Trace => log::Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SharedBuf;
+    use slog::{crit, debug, o};
+
+    #[test]
+    fn test_drain() {
+        let buf = SharedBuf::default();
+        let root = slog::Logger::root(
+            EcsDrain::new(buf.clone()).fuse(),
+            o!("service.name" => "my-app", "user.id" => 1),
+        );
+        let child = root.new(o!("user.id" => 2, "ratio" => 0.5));
+
+        crit!(child, "Disk {} full", "/var"; "user.id" => 3, "ok" => false);
+        debug!(child, #"audit", "Checked"; "path" => ?["a"], "none" => None::<u8>);
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0]["log.level"], "ERROR");
+        assert_eq!(lines[0]["message"], "Disk /var full");
+        assert_eq!(lines[0]["log.origin"]["rust"]["target"], module_path!());
+        assert_eq!(lines[0]["service.name"], "my-app");
+        assert_eq!(lines[0]["user.id"], 3);
+        assert_eq!(lines[0]["ratio"], 0.5);
+        assert_eq!(lines[0]["ok"], false);
+
+        assert_eq!(lines[1]["log.level"], "DEBUG");
+        assert_eq!(lines[1]["log.origin"]["rust"]["target"], "audit");
+        assert_eq!(lines[1]["user.id"], 2);
+        assert_eq!(lines[1]["path"], "[\"a\"]");
+        assert_eq!(lines[1]["none"], Value::Null);
+    }
+}
@@ -0,0 +1,211 @@
+//! `log.syslog.*` fields of the records shipped through syslog
+
+use log::Level;
+use serde_json::{Map, Value};
+
+/// Syslog facility of [RFC 5424](https://www.rfc-editor.org/rfc/rfc5424#section-6.2.1), e.g. [`Local0`](Self::Local0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyslogFacility {
+    /// Kernel messages.
+    Kern = 0,
+
+    /// User-level messages.
+    User = 1,
+
+    /// Mail system.
+    Mail = 2,
+
+    /// System daemons.
+    Daemon = 3,
+
+    /// Security and authorization messages.
+    Auth = 4,
+
+    /// Messages generated internally by syslogd.
+    Syslog = 5,
+
+    /// Line printer subsystem.
+    Lpr = 6,
+
+    /// Network news subsystem.
+    News = 7,
+
+    /// UUCP subsystem.
+    Uucp = 8,
+
+    /// Clock daemon.
+    Cron = 9,
+
+    /// Private security and authorization messages.
+    Authpriv = 10,
+
+    /// FTP daemon.
+    Ftp = 11,
+
+    /// NTP subsystem.
+    Ntp = 12,
+
+    /// Log audit.
+    Security = 13,
+
+    /// Log alert.
+    Console = 14,
+
+    /// Scheduling daemon.
+    SolarisCron = 15,
+
+    /// Local use 0.
+    Local0 = 16,
+
+    /// Local use 1.
+    Local1 = 17,
+
+    /// Local use 2.
+    Local2 = 18,
+
+    /// Local use 3.
+    Local3 = 19,
+
+    /// Local use 4.
+    Local4 = 20,
+
+    /// Local use 5.
+    Local5 = 21,
+
+    /// Local use 6.
+    Local6 = 22,
+
+    /// Local use 7.
+    Local7 = 23,
+}
+
+impl SyslogFacility {
+    /// All facilities, indexed by their codes
+    const ALL: [SyslogFacility; 24] = [
+        SyslogFacility::Kern,
+        SyslogFacility::User,
+        SyslogFacility::Mail,
+        SyslogFacility::Daemon,
+        SyslogFacility::Auth,
+        SyslogFacility::Syslog,
+        SyslogFacility::Lpr,
+        SyslogFacility::News,
+        SyslogFacility::Uucp,
+        SyslogFacility::Cron,
+        SyslogFacility::Authpriv,
+        SyslogFacility::Ftp,
+        SyslogFacility::Ntp,
+        SyslogFacility::Security,
+        SyslogFacility::Console,
+        SyslogFacility::SolarisCron,
+        SyslogFacility::Local0,
+        SyslogFacility::Local1,
+        SyslogFacility::Local2,
+        SyslogFacility::Local3,
+        SyslogFacility::Local4,
+        SyslogFacility::Local5,
+        SyslogFacility::Local6,
+        SyslogFacility::Local7,
+    ];
+
+    /// Returns the facility of `code`, or `None` if it is not a valid code, i.e. greater than 23.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(code)).copied()
+    }
+
+    /// Returns the code of the facility, e.g. `16` for `local0`.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the name of the facility, e.g. `local0`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SyslogFacility::Kern => "kern",
+            SyslogFacility::User => "user",
+            SyslogFacility::Mail => "mail",
+            SyslogFacility::Daemon => "daemon",
+            SyslogFacility::Auth => "auth",
+            SyslogFacility::Syslog => "syslog",
+            SyslogFacility::Lpr => "lpr",
+            SyslogFacility::News => "news",
+            SyslogFacility::Uucp => "uucp",
+            SyslogFacility::Cron => "cron",
+            SyslogFacility::Authpriv => "authpriv",
+            SyslogFacility::Ftp => "ftp",
+            SyslogFacility::Ntp => "ntp",
+            SyslogFacility::Security => "security",
+            SyslogFacility::Console => "console",
+            SyslogFacility::SolarisCron => "solaris-cron",
+            SyslogFacility::Local0 => "local0",
+            SyslogFacility::Local1 => "local1",
+            SyslogFacility::Local2 => "local2",
+            SyslogFacility::Local3 => "local3",
+            SyslogFacility::Local4 => "local4",
+            SyslogFacility::Local5 => "local5",
+            SyslogFacility::Local6 => "local6",
+            SyslogFacility::Local7 => "local7",
+        }
+    }
+}
+
+/// Returns the [RFC 5424](https://www.rfc-editor.org/rfc/rfc5424#section-6.2.1) severity code and name of `level`
+fn severity(level: Level) -> (u8, &'static str) {
+    match level {
+        Level::Error => (3, "Error"),
+        Level::Warn => (4, "Warning"),
+        Level::Info => (6, "Informational"),
+        Level::Debug | Level::Trace => (7, "Debug"),
+    }
+}
+
+/// Returns the `log.syslog.*` fields of a record at `level` sent with `facility`, with dotted keys.
+pub(crate) fn syslog_fields(facility: SyslogFacility, level: Level) -> Map<String, Value> {
+    let (severity_code, severity_name) = severity(level);
+    let mut fields = Map::new();
+    fields.insert(
+        "log.syslog.facility.code".to_string(),
+        facility.code().into(),
+    );
+    fields.insert(
+        "log.syslog.facility.name".to_string(),
+        facility.name().into(),
+    );
+    fields.insert(
+        "log.syslog.priority".to_string(),
+        (u16::from(facility.code()) * 8 + u16::from(severity_code)).into(),
+    );
+    fields.insert("log.syslog.severity.code".to_string(), severity_code.into());
+    fields.insert("log.syslog.severity.name".to_string(), severity_name.into());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_syslog_fields() {
+        assert_eq!(
+            Value::Object(syslog_fields(SyslogFacility::Local0, Level::Warn)),
+            json!({
+                "log.syslog.facility.code": 16,
+                "log.syslog.facility.name": "local0",
+                "log.syslog.priority": 132,
+                "log.syslog.severity.code": 4,
+                "log.syslog.severity.name": "Warning",
+            })
+        );
+        assert_eq!(
+            syslog_fields(SyslogFacility::User, Level::Trace)["log.syslog.priority"],
+            json!(15)
+        );
+        assert_eq!(SyslogFacility::from_code(23), Some(SyslogFacility::Local7));
+        assert_eq!(SyslogFacility::from_code(24), None);
+        for (code, facility) in SyslogFacility::ALL.into_iter().enumerate() {
+            assert_eq!(usize::from(facility.code()), code);
+        }
+        assert_eq!(SyslogFacility::SolarisCron.name(), "solaris-cron");
+    }
+}
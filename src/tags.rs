@@ -0,0 +1,129 @@
+//! `tags` field
+
+use serde_json::{Map, Value};
+use std::sync::{PoisonError, RwLock};
+
+static TAGS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Adds `tag` to the [`tags`](https://www.elastic.co/guide/en/ecs/current/ecs-base.html#field-tags) of every log record,
+/// unless it is added already.
+///
+/// The tags are merged with the `tags` set by other means, e.g. with extra fields or [`context::push`](crate::context::push),
+/// instead of being overridden by them.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+///
+/// ecs_logger::add_tag("production");
+/// ecs_logger::add_tag("eu-west-1");
+///
+/// log::info!("Hello {}!", "world"); // {"tags":["production","eu-west-1"],...}
+///
+/// ecs_logger::clear_tags();
+/// ```
+pub fn add_tag(tag: impl Into<String>) {
+    let tag = tag.into();
+    let mut tags = TAGS.write().unwrap_or_else(PoisonError::into_inner);
+    if !tags.contains(&tag) {
+        tags.push(tag);
+    }
+}
+
+/// Replaces the tags added by [`add_tag`] with `tags`.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::set_tags(["production", "eu-west-1"]);
+/// # ecs_logger::clear_tags();
+/// ```
+pub fn set_tags<I>(tags: I)
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    let mut new_tags = Vec::new();
+    for tag in tags {
+        let tag = tag.into();
+        if !new_tags.contains(&tag) {
+            new_tags.push(tag);
+        }
+    }
+
+    *TAGS.write().unwrap_or_else(PoisonError::into_inner) = new_tags;
+}
+
+/// Clears the tags added by [`add_tag`] and [`set_tags`].
+pub fn clear_tags() {
+    TAGS.write().unwrap_or_else(PoisonError::into_inner).clear();
+}
+
+/// Merges the global tags into the `tags` field of `event`.
+pub(crate) fn merge_tags(event: &mut Map<String, Value>) {
+    let tags = TAGS.read().unwrap_or_else(PoisonError::into_inner);
+    merge_tags_from(event, &tags);
+}
+
+/// Sets `tags` of `event` to the union of `global` and the strings it has already, in this order
+fn merge_tags_from(event: &mut Map<String, Value>, global: &[String]) {
+    if global.is_empty() {
+        return;
+    }
+
+    let mut tags: Vec<Value> = global.iter().map(|tag| tag.as_str().into()).collect();
+    match event.remove("tags") {
+        Some(Value::Array(values)) => tags.extend(values),
+        Some(value @ Value::String(_)) => tags.push(value),
+        Some(value) => {
+            // Not a tag, so it is kept as is
+            event.insert("tags".to_string(), value);
+            return;
+        }
+        None => {}
+    }
+
+    let mut merged: Vec<Value> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        if !merged.contains(&tag) {
+            merged.push(tag);
+        }
+    }
+    event.insert("tags".to_string(), merged.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn merge(event: Value, global: &[&str]) -> Value {
+        let global = global.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        let mut event = event.as_object().unwrap().clone();
+        merge_tags_from(&mut event, &global);
+        Value::Object(event)
+    }
+
+    #[test]
+    fn test_merge_tags() {
+        assert_eq!(merge(json!({}), &[]), json!({}));
+        assert_eq!(merge(json!({}), &["a", "b"]), json!({ "tags": ["a", "b"] }));
+        assert_eq!(
+            merge(json!({ "tags": ["b", "c"] }), &["a", "b"]),
+            json!({ "tags": ["a", "b", "c"] })
+        );
+        assert_eq!(
+            merge(json!({ "tags": "c" }), &["a"]),
+            json!({ "tags": ["a", "c"] })
+        );
+        assert_eq!(
+            merge(json!({ "tags": { "c": 1 } }), &["a"]),
+            json!({ "tags": { "c": 1 } })
+        );
+        assert_eq!(
+            merge(json!({ "tags": ["c"] }), &[]),
+            json!({ "tags": ["c"] })
+        );
+    }
+}
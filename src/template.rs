@@ -0,0 +1,78 @@
+//! Unformatted message template added as `message_template` and `event.original`
+//!
+//! The template is set by [`log_templated!`](crate::log_templated) for the duration of the log call.
+
+use std::cell::Cell;
+
+thread_local! {
+    /// Template of the record being logged by the current thread.
+    static TEMPLATE: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `template` reported as the template of the records emitted by the current thread.
+pub fn with_template<R>(template: &'static str, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous template even if `f` panics
+    struct RestoreGuard(Option<&'static str>);
+
+    impl Drop for RestoreGuard {
+        fn drop(&mut self) {
+            TEMPLATE.with(|t| t.set(self.0));
+        }
+    }
+
+    let _guard = RestoreGuard(TEMPLATE.with(|t| t.replace(Some(template))));
+
+    f()
+}
+
+/// Returns the template of the record being logged by the current thread, if it is known.
+pub(crate) fn current_template() -> Option<&'static str> {
+    TEMPLATE.try_with(Cell::get).ok().flatten()
+}
+
+/// Logs a message like [`log::log!`], adding its unformatted template as `message_template`,
+/// and as `event.original` when [`FormatOptions::event_original`](crate::FormatOptions::event_original) is enabled.
+///
+/// Records with the same template can then be grouped regardless of their arguments, e.g. in Kibana.
+/// The message must be a string literal.
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// ecs_logger::log_templated!(Level::Info, "user {} logged in", "alice");
+/// // {"log.level":"INFO","message":"user alice logged in","message_template":"user {} logged in",...}
+/// ```
+#[macro_export]
+macro_rules! log_templated {
+    (target: $target:expr, $lvl:expr, $fmt:literal $($arg:tt)*) => {{
+        let lvl = $lvl;
+        if $crate::__private::log::log_enabled!(target: $target, lvl) {
+            $crate::__private::with_template($fmt, || {
+                $crate::__private::log::log!(target: $target, lvl, $fmt $($arg)*);
+            });
+        }
+    }};
+    ($lvl:expr, $fmt:literal $($arg:tt)*) => {
+        $crate::log_templated!(target: ::core::module_path!(), $lvl, $fmt $($arg)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_template() {
+        assert_eq!(current_template(), None);
+
+        let (outer, inner) = with_template("outer {}", || {
+            let inner = with_template("inner {}", current_template);
+            (current_template(), inner)
+        });
+        assert_eq!(outer, Some("outer {}"));
+        assert_eq!(inner, Some("inner {}"));
+        assert_eq!(current_template(), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! Helpers for asserting on log output in tests
+//!
+//! ## Example
+//!
+//! ```
+//! ecs_logger::test::with_capture(|events| {
+//!     log::warn!("Disk usage is {}%", 91);
+//!
+//!     let events = events.to_vec();
+//!     assert_eq!(events.len(), 1);
+//!     assert_eq!(events[0]["log.level"], "WARN");
+//!     assert_eq!(events[0]["message"], "Disk usage is 91%");
+//! });
+//! ```
+
+use crate::FormatOptions;
+use log::{Log, Metadata, Record};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Once;
+
+thread_local! {
+    /// Events captured on the current thread by the innermost [`with_capture`].
+    static CAPTURE: RefCell<Option<Rc<RefCell<Vec<Value>>>>> = const { RefCell::new(None) };
+}
+
+/// Events captured by [`with_capture`].
+#[derive(Debug, Default)]
+pub struct CapturedEvents(Rc<RefCell<Vec<Value>>>);
+
+impl CapturedEvents {
+    /// Returns the events captured so far, oldest first.
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.0.borrow().clone()
+    }
+
+    /// Returns the messages of the events captured so far, oldest first.
+    pub fn messages(&self) -> Vec<String> {
+        self.0
+            .borrow()
+            .iter()
+            .filter_map(|event| event["message"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Returns the number of events captured so far.
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Returns `true` if no event has been captured so far.
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Removes and returns the events captured so far.
+    pub fn take(&self) -> Vec<Value> {
+        self.0.take()
+    }
+}
+
+/// Runs `f` capturing the ECS events logged on the current thread in memory instead of writing them.
+///
+/// All levels are captured regardless of the `RUST_LOG` environment variable. Events logged on other threads are not captured,
+/// so tests can run in parallel.
+///
+/// The first call installs a global logger which forwards events to the active capture, if any, and drops them otherwise.
+///
+/// # Panics
+///
+/// This function will panic if another global logger has already been initialized (e.g. with [`init`](crate::init)).
+pub fn with_capture<R>(f: impl FnOnce(&CapturedEvents) -> R) -> R {
+    /// Restores the previous capture even if `f` panics
+    struct Restore(Option<Rc<RefCell<Vec<Value>>>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CAPTURE.with(|c| *c.borrow_mut() = self.0.take());
+        }
+    }
+
+    install();
+
+    let events = CapturedEvents::default();
+    let previous = CAPTURE.with(|c| c.replace(Some(events.0.clone())));
+    let _restore = Restore(previous);
+
+    f(&events)
+}
+
+fn install() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        log::set_logger(&CaptureLogger)
+            .expect("with_capture should not be called after another logger initialized");
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+}
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        CAPTURE.with(|c| c.borrow().is_some())
+    }
+
+    fn log(&self, record: &Record) {
+        let Some(events) = CAPTURE.with(|c| c.borrow().clone()) else {
+            return;
+        };
+
+        let mut buf = Vec::new();
+        if crate::write_event(&mut buf, record, &FormatOptions::default()).is_ok() {
+            let event = serde_json::from_slice(&buf).expect("ECS event should be valid JSON");
+            events.borrow_mut().push(event);
+        }
+    }
+
+    fn flush(&self) {}
+}
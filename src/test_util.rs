@@ -0,0 +1,33 @@
+//! Helpers shared by the unit tests
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// [`Write`] implementation which can be inspected after being moved into a logger
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuf {
+    /// Returns everything written so far.
+    pub(crate) fn content(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+
+    /// Returns the written lines parsed as JSON.
+    pub(crate) fn lines(&self) -> Vec<serde_json::Value> {
+        self.content()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,159 @@
+//! Drop guard which logs the duration of a block
+
+use crate::extra_fields::with_scoped_fields;
+use crate::outcome::{with_outcome, Outcome};
+use log::Level;
+use serde_json::Map;
+use std::panic::Location;
+use std::time::Instant;
+
+/// Starts a timer which logs an event with `label` as the message when dropped.
+///
+/// The event has `event.duration` in nanoseconds and `event.outcome`, which is `success` unless the timer is marked failed.
+/// Like [`ResultExt`](crate::ResultExt), the event has the target `ecs_logger` and the origin of the caller.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+///
+/// fn rebuild_index() -> Result<(), std::io::Error> {
+///     let timer = ecs_logger::span_timer("rebuild index");
+///
+///     // Do the work...
+///     let result = Ok(());
+///
+///     if result.is_err() {
+///         timer.fail();
+///     }
+///     result
+/// } // {"log.level":"INFO","message":"rebuild index","event.duration":1234567,"event.outcome":"success",...}
+///
+/// rebuild_index().unwrap();
+/// ```
+#[track_caller]
+pub fn span_timer(label: impl Into<String>) -> SpanTimer {
+    SpanTimer {
+        label: label.into(),
+        level: Level::Info,
+        action: None,
+        outcome: Outcome::Success,
+        start: Instant::now(),
+        location: Location::caller(),
+    }
+}
+
+/// Guard returned by [`span_timer`].
+#[must_use = "the duration is logged when the timer is dropped"]
+#[derive(Debug)]
+pub struct SpanTimer {
+    label: String,
+    level: Level,
+    action: Option<String>,
+    outcome: Outcome,
+    start: Instant,
+    location: &'static Location<'static>,
+}
+
+impl SpanTimer {
+    /// Sets the level of the event. Defaults to `INFO`.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets `event.action` of the event, e.g. `rebuild-index`.
+    pub fn with_action(mut self, action: impl Into<String>) -> Self {
+        self.action = Some(action.into());
+        self
+    }
+
+    /// Sets `event.outcome` of the event.
+    pub fn set_outcome(&mut self, outcome: Outcome) {
+        self.outcome = outcome;
+    }
+
+    /// Marks the operation failed and logs the event.
+    pub fn fail(mut self) {
+        self.outcome = Outcome::Failure;
+    }
+}
+
+impl Drop for SpanTimer {
+    fn drop(&mut self) {
+        let duration = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+
+        let mut fields = Map::new();
+        fields.insert("event.duration".to_string(), duration.into());
+        if let Some(action) = &self.action {
+            fields.insert("event.action".to_string(), action.as_str().into());
+        }
+
+        with_scoped_fields(fields, || {
+            with_outcome(self.outcome, || {
+                crate::log_at(self.level, format_args!("{}", self.label), self.location)
+            })
+        });
+    }
+}
+
+/// Outcome of a block timed by [`ecs_timed!`](crate::ecs_timed!) which evaluates to a [`Result`].
+pub trait ResultOutcome {
+    /// Returns `failure` if the result is [`Err`], or `success` otherwise.
+    fn timed_outcome(&self) -> Outcome;
+}
+
+impl<T, E> ResultOutcome for Result<T, E> {
+    fn timed_outcome(&self) -> Outcome {
+        match self {
+            Ok(_) => Outcome::Success,
+            Err(_) => Outcome::Failure,
+        }
+    }
+}
+
+/// Outcome of a block timed by [`ecs_timed!`](crate::ecs_timed!) which evaluates to anything but a [`Result`].
+///
+/// It is implemented for the references, so a method call on `&value` picks [`ResultOutcome`] first for the results.
+pub trait ValueOutcome {
+    /// Returns `success`.
+    fn timed_outcome(&self) -> Outcome {
+        Outcome::Success
+    }
+}
+
+impl<T: ?Sized> ValueOutcome for &T {}
+
+/// Measures a block and logs an `INFO` event with the action as the message, `event.action`, and `event.duration` in nanoseconds.
+///
+/// `event.outcome` is `failure` if the block evaluates to an [`Err`], and `success` otherwise.
+/// If the block is left early, e.g. with `?` or `return`, or panics, it is `unknown`.
+/// The macro evaluates to the value of the block. See [`span_timer`] for the guard used underneath.
+///
+/// # Example
+///
+/// ```
+/// ecs_logger::init();
+///
+/// let rows = ecs_logger::ecs_timed!("load-users", {
+///     std::fs::read_to_string("/nonexistent/users.csv").map(|users| users.lines().count())
+/// });
+/// // {"log.level":"INFO","message":"load-users","event.action":"load-users","event.duration":1234567,"event.outcome":"failure",...}
+/// assert!(rows.is_err());
+/// ```
+#[macro_export]
+macro_rules! ecs_timed {
+    ($action:expr, $body:block) => {{
+        let action: ::std::string::String = ::core::convert::Into::into($action);
+        let mut timer =
+            $crate::span_timer(::core::clone::Clone::clone(&action)).with_action(action);
+        timer.set_outcome($crate::Outcome::Unknown);
+        let value = $body;
+        {
+            use $crate::__private::{ResultOutcome as _, ValueOutcome as _};
+            timer.set_outcome((&value).timed_outcome());
+        }
+        ::core::mem::drop(timer);
+        value
+    }};
+}
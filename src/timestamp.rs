@@ -1,5 +1,14 @@
+use chrono::{DateTime, Datelike, SecondsFormat, Utc};
+use std::cell::RefCell;
+use std::fmt::Write;
+
+thread_local! {
+    /// Unix time in seconds and its formatted date and time up to the seconds, e.g. `2000-01-23T01:23:45`
+    static PREFIX_CACHE: RefCell<(i64, String)> = const { RefCell::new((0, String::new())) };
+}
+
 #[cfg(not(test))]
-pub fn get_timestamp() -> chrono::DateTime<chrono::Utc> {
+pub fn get_timestamp() -> DateTime<Utc> {
     chrono::Utc::now()
 }
 
@@ -7,16 +16,105 @@ pub fn get_timestamp() -> chrono::DateTime<chrono::Utc> {
 pub const MOCK_TIMESTAMP: &str = "2000-01-23T01:23:45.678901200Z";
 
 #[cfg(test)]
-pub fn get_timestamp() -> chrono::DateTime<chrono::Utc> {
-    chrono::DateTime::parse_from_rfc3339(MOCK_TIMESTAMP)
+pub fn get_timestamp() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(MOCK_TIMESTAMP)
         .unwrap()
-        .with_timezone(&chrono::Utc)
+        .with_timezone(&Utc)
+}
+
+/// Formats `timestamp` in the same way as [`DateTime::to_rfc3339_opts`] with `use_z` set to `true`.
+///
+/// The date and time up to the seconds are cached per thread, and only the sub-second part is rendered
+/// unless the second has changed since the previous call.
+pub fn format_timestamp(timestamp: &DateTime<Utc>, format: SecondsFormat) -> String {
+    let nanos = timestamp.timestamp_subsec_nanos();
+    if nanos >= 1_000_000_000 || !(0..=9999).contains(&timestamp.year()) {
+        // Leap seconds and years which need a sign are rare enough not to be cached
+        return timestamp.to_rfc3339_opts(format, true);
+    }
+
+    let mut formatted = String::with_capacity(30);
+    PREFIX_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let (secs, prefix) = &mut *cache;
+        if *secs != timestamp.timestamp() || prefix.is_empty() {
+            *secs = timestamp.timestamp();
+            prefix.clear();
+            let _ = write!(prefix, "{}", timestamp.format("%Y-%m-%dT%H:%M:%S"));
+        }
+        formatted.push_str(prefix);
+    });
+
+    let _ = match format {
+        SecondsFormat::Secs => Ok(()),
+        SecondsFormat::Millis => write!(formatted, ".{:03}", nanos / 1_000_000),
+        SecondsFormat::Micros => write!(formatted, ".{:06}", nanos / 1_000),
+        SecondsFormat::Nanos => write!(formatted, ".{:09}", nanos),
+        SecondsFormat::AutoSi => match nanos {
+            0 => Ok(()),
+            nanos if nanos % 1_000_000 == 0 => write!(formatted, ".{:03}", nanos / 1_000_000),
+            nanos if nanos % 1_000 == 0 => write!(formatted, ".{:06}", nanos / 1_000),
+            nanos => write!(formatted, ".{:09}", nanos),
+        },
+        _ => return timestamp.to_rfc3339_opts(format, true),
+    };
+    formatted.push('Z');
+
+    formatted
+}
+
+/// Serializes `timestamp` in the same format as the `serde` implementation of [`DateTime`], using the cache of [`format_timestamp`].
+pub fn serialize<S: serde::Serializer>(
+    timestamp: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_timestamp(timestamp, SecondsFormat::AutoSi))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_format_timestamp() {
+        let formats = [
+            SecondsFormat::Secs,
+            SecondsFormat::Millis,
+            SecondsFormat::Micros,
+            SecondsFormat::Nanos,
+            SecondsFormat::AutoSi,
+        ];
+        let timestamps = [
+            "2000-01-23T01:23:45Z",
+            "2000-01-23T01:23:45.120Z",
+            "2000-01-23T01:23:45.000120Z",
+            "2000-01-23T01:23:45.000000120Z",
+            "2000-01-23T01:23:46.5Z",
+            "1969-12-31T23:59:59.999999999Z",
+            "0001-01-01T00:00:00Z",
+            "2016-12-31T23:59:60.5Z",
+        ];
+
+        for timestamp in timestamps {
+            let timestamp = DateTime::parse_from_rfc3339(timestamp)
+                .unwrap()
+                .with_timezone(&Utc);
+            for format in formats {
+                assert_eq!(
+                    format_timestamp(&timestamp, format),
+                    timestamp.to_rfc3339_opts(format, true)
+                );
+            }
+            assert_eq!(
+                serde_json::to_string(&timestamp).unwrap(),
+                format!(
+                    "\"{}\"",
+                    format_timestamp(&timestamp, SecondsFormat::AutoSi)
+                )
+            );
+        }
+    }
+
     #[test]
     fn test_get_timestamp() {
         assert_eq!(
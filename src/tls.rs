@@ -0,0 +1,205 @@
+//! `tls.*` fields of the TLS session being handled
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// TLS session which the log records belong to, e.g. the handshake details of a connection terminated by the service.
+///
+/// It adds the `tls.*` fields set with its methods to the records.
+/// The fields are attached like those of the other [`ToEcsFields`](crate::ToEcsFields) types.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::TlsContext;
+///
+/// ecs_logger::init();
+///
+/// TlsContext::new()
+///     .version("TLSv1.3")
+///     .cipher("TLS_AES_128_GCM_SHA256")
+///     .server_name("example.com")
+///     .established(true)
+///     .in_scope(|| log::info!("Handshake completed"));
+/// // {"log.level":"INFO","message":"Handshake completed","tls.version":"1.3","tls.version_protocol":"tls","tls.cipher":"TLS_AES_128_GCM_SHA256",...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsContext {
+    version: Option<String>,
+    version_protocol: Option<String>,
+    cipher: Option<String>,
+    established: Option<bool>,
+    resumed: Option<bool>,
+    next_protocol: Option<String>,
+    server_name: Option<String>,
+    client_subject: Option<String>,
+    client_issuer: Option<String>,
+    server_subject: Option<String>,
+    server_issuer: Option<String>,
+}
+
+impl TlsContext {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `tls.version` and `tls.version_protocol`.
+    ///
+    /// Both `1.3` and the OpenSSL notation `TLSv1.3` are accepted, and the protocol is `tls` unless it is prefixed with `SSLv`.
+    pub fn version(mut self, version: &str) -> Self {
+        let (protocol, version) = if let Some(version) = version.strip_prefix("TLSv") {
+            ("tls", version)
+        } else if let Some(version) = version.strip_prefix("SSLv") {
+            ("ssl", version)
+        } else {
+            ("tls", version)
+        };
+        self.version = Some(version.to_string());
+        self.version_protocol = Some(protocol.to_string());
+        self
+    }
+
+    /// Sets `tls.cipher`, the name of the negotiated cipher suite.
+    pub fn cipher(mut self, cipher: impl Into<String>) -> Self {
+        self.cipher = Some(cipher.into());
+        self
+    }
+
+    /// Sets `tls.established`, whether the handshake completed successfully.
+    pub fn established(mut self, established: bool) -> Self {
+        self.established = Some(established);
+        self
+    }
+
+    /// Sets `tls.resumed`, whether the session was resumed.
+    pub fn resumed(mut self, resumed: bool) -> Self {
+        self.resumed = Some(resumed);
+        self
+    }
+
+    /// Sets `tls.next_protocol`, the protocol negotiated with ALPN, e.g. `h2`.
+    pub fn next_protocol(mut self, next_protocol: impl Into<String>) -> Self {
+        self.next_protocol = Some(next_protocol.into());
+        self
+    }
+
+    /// Sets `tls.client.server_name`, the server name requested by the client with SNI.
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Sets `tls.client.subject`, the distinguished name of the client certificate.
+    pub fn client_subject(mut self, subject: impl Into<String>) -> Self {
+        self.client_subject = Some(subject.into());
+        self
+    }
+
+    /// Sets `tls.client.issuer`, the distinguished name of the issuer of the client certificate.
+    pub fn client_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.client_issuer = Some(issuer.into());
+        self
+    }
+
+    /// Sets `tls.server.subject`, the distinguished name of the server certificate.
+    pub fn server_subject(mut self, subject: impl Into<String>) -> Self {
+        self.server_subject = Some(subject.into());
+        self
+    }
+
+    /// Sets `tls.server.issuer`, the distinguished name of the issuer of the server certificate.
+    pub fn server_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.server_issuer = Some(issuer.into());
+        self
+    }
+}
+
+impl Serialize for TlsContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        let strings = [
+            ("tls.version", &self.version),
+            ("tls.version_protocol", &self.version_protocol),
+            ("tls.cipher", &self.cipher),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        if let Some(established) = self.established {
+            map.serialize_entry("tls.established", &established)?;
+        }
+        if let Some(resumed) = self.resumed {
+            map.serialize_entry("tls.resumed", &resumed)?;
+        }
+        let strings = [
+            ("tls.next_protocol", &self.next_protocol),
+            ("tls.client.server_name", &self.server_name),
+            ("tls.client.subject", &self.client_subject),
+            ("tls.client.issuer", &self.client_issuer),
+            ("tls.server.subject", &self.server_subject),
+            ("tls.server.issuer", &self.server_issuer),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(serde_json::to_value(TlsContext::new()).unwrap(), json!({}));
+        assert_eq!(
+            serde_json::to_value(
+                TlsContext::new()
+                    .version("1.2")
+                    .cipher("ECDHE-RSA-AES128-GCM-SHA256")
+                    .established(true)
+                    .resumed(false)
+                    .next_protocol("h2")
+                    .server_name("example.com")
+                    .client_subject("CN=alice")
+                    .client_issuer("CN=Example CA")
+                    .server_subject("CN=example.com")
+                    .server_issuer("CN=Example CA")
+            )
+            .unwrap(),
+            json!({
+                "tls.version": "1.2",
+                "tls.version_protocol": "tls",
+                "tls.cipher": "ECDHE-RSA-AES128-GCM-SHA256",
+                "tls.established": true,
+                "tls.resumed": false,
+                "tls.next_protocol": "h2",
+                "tls.client.server_name": "example.com",
+                "tls.client.subject": "CN=alice",
+                "tls.client.issuer": "CN=Example CA",
+                "tls.server.subject": "CN=example.com",
+                "tls.server.issuer": "CN=Example CA",
+            })
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        let version = |version| {
+            let value = serde_json::to_value(TlsContext::new().version(version)).unwrap();
+            (
+                value["tls.version_protocol"].clone(),
+                value["tls.version"].clone(),
+            )
+        };
+        assert_eq!(version("TLSv1.3"), (json!("tls"), json!("1.3")));
+        assert_eq!(version("SSLv3"), (json!("ssl"), json!("3")));
+        assert_eq!(version("1.1"), (json!("tls"), json!("1.1")));
+    }
+}
@@ -0,0 +1,331 @@
+//! `trace.id` and `span.id` fields for distributed tracing correlation
+
+use crate::error::random_bits;
+use crate::extra_fields::to_json_map;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Trace and span which the log records belong to.
+///
+/// It adds `trace.id`, `span.id`, and `transaction.id` to the records, so Kibana can link the logs to the distributed trace.
+/// Use it directly with [`in_scope`](Self::in_scope) or the other ways of [`ToEcsFields`](crate::ToEcsFields), or return it from a provider registered with
+/// [`FormatOptions::trace_context`](crate::FormatOptions::trace_context) to look up the current span of a tracing library.
+///
+/// The context of an incoming request can be parsed from its W3C [`traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header)
+/// header with [`from_traceparent`](Self::from_traceparent), and the header of a downstream call is rendered by
+/// [`traceparent`](Self::traceparent) of a [`child`](Self::child) context.
+/// With the `opentelemetry` feature enabled, it can also be converted from an `opentelemetry::trace::SpanContext`.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::TraceContext;
+///
+/// ecs_logger::init();
+///
+/// TraceContext::new("0af7651916cd43dd8448eb211c80319c")
+///     .span_id("b7ad6b7169203331")
+///     .in_scope(|| log::info!("Handling request"));
+/// // {"log.level":"INFO","message":"Handling request","trace.id":"0af7651916cd43dd8448eb211c80319c","span.id":"b7ad6b7169203331",...}
+/// ```
+///
+/// Propagating the trace of a request:
+///
+/// ```
+/// use ecs_logger::TraceContext;
+///
+/// ecs_logger::init();
+///
+/// let header = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+/// let trace = TraceContext::from_traceparent(header).unwrap_or_else(|_| TraceContext::generate());
+/// let _trace = trace.enter();
+/// log::info!("Handling request"); // Has `trace.id` and `span.id`
+///
+/// let downstream = trace.child().traceparent().unwrap();
+/// assert!(downstream.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: Option<String>,
+    transaction_id: Option<String>,
+    sampled: bool,
+}
+
+/// Error returned by [`TraceContext::from_traceparent`] when the header is malformed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid traceparent header: {0}")]
+pub struct TraceparentError(String);
+
+impl TraceContext {
+    /// Creates a trace context with `trace.id`.
+    pub fn new(trace_id: impl Into<String>) -> Self {
+        TraceContext {
+            trace_id: trace_id.into(),
+            span_id: None,
+            transaction_id: None,
+            sampled: true,
+        }
+    }
+
+    /// Creates a sampled trace context with a random trace id and a random span id, for the requests which have no `traceparent`.
+    pub fn generate() -> Self {
+        TraceContext::new(format!("{:032x}", random_bits().max(1))).span_id(random_span_id())
+    }
+
+    /// Parses a W3C `traceparent` header, e.g. `00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01`,
+    /// into the trace id, the id of the parent span as `span.id`, and the sampled flag.
+    ///
+    /// The headers of future versions are accepted as long as their first four parts are valid, as the specification requires.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TraceparentError`] if the header is malformed, or if an id is all zeros.
+    pub fn from_traceparent(header: &str) -> Result<Self, TraceparentError> {
+        let header = header.trim();
+        let invalid = || TraceparentError(header.to_string());
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+
+        let mut parts = header.split('-');
+        let (Some(version), Some(trace_id), Some(span_id), Some(flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let rest_is_valid = match version {
+            "00" => parts.next().is_none(),
+            "ff" => false,
+            _ => is_hex(version, 2),
+        };
+        if !rest_is_valid
+            || !is_hex(trace_id, 32)
+            || !is_hex(span_id, 16)
+            || !is_hex(flags, 2)
+            || trace_id.bytes().all(|b| b == b'0')
+            || span_id.bytes().all(|b| b == b'0')
+        {
+            return Err(invalid());
+        }
+
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| invalid())?;
+        Ok(TraceContext::new(trace_id)
+            .span_id(span_id)
+            .sampled(flags & 1 == 1))
+    }
+
+    /// Returns a context of the same trace with a new random span id, e.g. for a call to a downstream service.
+    pub fn child(&self) -> Self {
+        TraceContext {
+            span_id: Some(random_span_id()),
+            ..self.clone()
+        }
+    }
+
+    /// Renders the W3C `traceparent` header of the context, or `None` if `span.id` is not set.
+    pub fn traceparent(&self) -> Option<String> {
+        let span_id = self.span_id.as_ref()?;
+        Some(format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            span_id,
+            u8::from(self.sampled)
+        ))
+    }
+
+    /// Sets the sampled flag of the `traceparent` header. Contexts are sampled by default.
+    pub fn sampled(mut self, sampled: bool) -> Self {
+        self.sampled = sampled;
+        self
+    }
+
+    /// Returns whether the trace is sampled.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Sets `span.id`.
+    pub fn span_id(mut self, span_id: impl Into<String>) -> Self {
+        self.span_id = Some(span_id.into());
+        self
+    }
+
+    /// Sets `transaction.id`, the id of the Elastic APM transaction which the span belongs to.
+    pub fn transaction_id(mut self, transaction_id: impl Into<String>) -> Self {
+        self.transaction_id = Some(transaction_id.into());
+        self
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+impl From<&opentelemetry::trace::SpanContext> for TraceContext {
+    fn from(context: &opentelemetry::trace::SpanContext) -> Self {
+        TraceContext::new(context.trace_id().to_string())
+            .span_id(context.span_id().to_string())
+            .sampled(context.is_sampled())
+    }
+}
+
+/// Returns a random span id in 16 hexadecimal digits, which is never all zeros
+fn random_span_id() -> String {
+    format!("{:016x}", (random_bits() as u64).max(1))
+}
+
+impl Serialize for TraceContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("trace.id", &self.trace_id)?;
+        if let Some(span_id) = &self.span_id {
+            map.serialize_entry("span.id", span_id)?;
+        }
+        if let Some(transaction_id) = &self.transaction_id {
+            map.serialize_entry("transaction.id", transaction_id)?;
+        }
+        map.end()
+    }
+}
+
+/// Function which returns the trace context of the current thread.
+#[derive(Clone)]
+pub(crate) struct TraceContextProvider(Arc<dyn Fn() -> Option<TraceContext> + Send + Sync>);
+
+impl fmt::Debug for TraceContextProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceContextProvider")
+            .finish_non_exhaustive()
+    }
+}
+
+impl TraceContextProvider {
+    pub(crate) fn new(provider: impl Fn() -> Option<TraceContext> + Send + Sync + 'static) -> Self {
+        TraceContextProvider(Arc::new(provider))
+    }
+
+    /// Inserts the fields of the current trace context into `json_map`, keeping the fields which are already set.
+    pub(crate) fn insert_into(&self, json_map: &mut Map<String, Value>) {
+        let Some(context) = (self.0)() else {
+            return;
+        };
+
+        let fields =
+            to_json_map(context).expect("TraceContext should be converted into a JSON object");
+        for (k, v) in fields {
+            json_map.entry(k).or_insert(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(TraceContext::new("t-1")).unwrap(),
+            json!({ "trace.id": "t-1" })
+        );
+        assert_eq!(
+            serde_json::to_value(TraceContext::new("t-1").span_id("s-1")).unwrap(),
+            json!({ "trace.id": "t-1", "span.id": "s-1" })
+        );
+        assert_eq!(
+            serde_json::to_value(TraceContext::new("t-1").transaction_id("tx-1")).unwrap(),
+            json!({ "trace.id": "t-1", "transaction.id": "tx-1" })
+        );
+    }
+
+    #[test]
+    fn test_from_traceparent() {
+        let context = TraceContext::from_traceparent(
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        )
+        .unwrap();
+        assert_eq!(
+            context,
+            TraceContext::new("0af7651916cd43dd8448eb211c80319c").span_id("b7ad6b7169203331")
+        );
+        assert_eq!(
+            context.traceparent().unwrap(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+
+        let context = TraceContext::from_traceparent(
+            "01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-future",
+        )
+        .unwrap();
+        assert!(!context.is_sampled());
+
+        for header in [
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0af7651916cd43dd-b7ad6b7169203331-01",
+        ] {
+            assert!(
+                TraceContext::from_traceparent(header).is_err(),
+                "{}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn test_child() {
+        let context = TraceContext::generate().transaction_id("tx-1");
+        let child = context.child();
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_eq!(child.transaction_id, context.transaction_id);
+        assert_ne!(child.span_id, context.span_id);
+
+        let header = child.traceparent().unwrap();
+        assert_eq!(
+            TraceContext::from_traceparent(&header).unwrap().span_id,
+            child.span_id
+        );
+        assert_eq!(TraceContext::new("t-1").traceparent(), None);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn test_from_span_context() {
+        use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+        let context = SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        assert_eq!(
+            TraceContext::from(&context).traceparent().unwrap(),
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+    }
+
+    #[test]
+    fn test_provider() {
+        let provider = TraceContextProvider::new(|| Some(TraceContext::new("t-1").span_id("s-1")));
+
+        let mut json_map = json!({ "span.id": "s-0" }).as_object().unwrap().clone();
+        provider.insert_into(&mut json_map);
+        assert_eq!(
+            Value::Object(json_map),
+            json!({ "span.id": "s-0", "trace.id": "t-1" })
+        );
+
+        let mut json_map = Map::new();
+        TraceContextProvider::new(|| None).insert_into(&mut json_map);
+        assert!(json_map.is_empty());
+    }
+}
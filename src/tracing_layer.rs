@@ -0,0 +1,385 @@
+//! `tracing-subscriber` layer formatting `tracing` events as ECS documents
+
+use crate::extra_fields::{extend_json_map, merge_extra_fields, with_scoped_fields};
+use crate::FormatOptions;
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::io::{self, Write};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Layer of [`tracing-subscriber`](https://docs.rs/tracing-subscriber) which writes the `tracing` events as ECS log lines,
+/// formatted like the records of the [`log`] crate, so the codebases which use both have one consistent output.
+///
+/// The `message` field of the event is the `message` of the document, and the other fields of the event and of its spans
+/// are added as top-level fields, e.g. `user.name` of `info!(user.name = "alice", "logged in")`. The fields of the event
+/// take precedence over those of its spans, and the fields of the inner spans over those of the outer ones.
+/// They all take precedence over the extra fields and the context fields.
+///
+/// The events in a span have `span.id`, the ID of the innermost span in 16 hexadecimal digits.
+/// If the event has the W3C `span.id` of a [`TraceContext`](crate::TraceContext), it is kept, and the ID of the span is `tracing.span.id` instead.
+/// With [`span_parents`](Self::span_parents), they also have `tracing.parent.id` and `tracing.span.parent_ids`, so the chain of the spans
+/// can be reconstructed from the logs alone. The IDs are those assigned by the subscriber, which are unique only among the spans open
+/// at the same time and are reused afterwards.
+///
+/// Available with the `tracing` feature.
+///
+/// # Example
+///
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::registry().with(ecs_logger::EcsLayer::new());
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("request", http.request.method = "GET");
+///     let _enter = span.enter();
+///     tracing::info!(user.name = "alice", "Hello {}!", "world");
+///     // {"log.level":"INFO","message":"Hello world!","http.request.method":"GET","user.name":"alice",...}
+/// });
+/// ```
+pub struct EcsLayer<W = fn() -> io::Stdout> {
+    options: FormatOptions,
+    span_parents: bool,
+    make_writer: W,
+}
+
+/// Fields recorded on a span
+struct SpanFields(Map<String, Value>);
+
+/// Visitor which collects the fields into JSON, keeping `message` apart so that it does not override the message of the event
+#[derive(Default)]
+struct FieldVisitor {
+    fields: Map<String, Value>,
+    message: Option<String>,
+}
+
+impl EcsLayer {
+    /// Creates a layer which writes to stdout with the default [`FormatOptions`].
+    pub fn new() -> Self {
+        EcsLayer {
+            options: FormatOptions::default(),
+            span_parents: false,
+            make_writer: io::stdout,
+        }
+    }
+}
+
+impl Default for EcsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W> EcsLayer<W> {
+    /// Sets the options of the format.
+    pub fn format_options(mut self, options: FormatOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Adds `tracing.parent.id`, the ID of the parent of the innermost span, and `tracing.span.parent_ids`,
+    /// the IDs of all its ancestors from the root, to the events in a span. Disabled by default.
+    pub fn span_parents(mut self, enabled: bool) -> Self {
+        self.span_parents = enabled;
+        self
+    }
+
+    /// Sets the writer of the log lines, e.g. [`std::io::stderr`] or a non-blocking writer of `tracing-appender`.
+    pub fn with_writer<W2>(self, make_writer: W2) -> EcsLayer<W2>
+    where
+        W2: for<'a> MakeWriter<'a> + 'static,
+    {
+        EcsLayer {
+            options: self.options,
+            span_parents: self.span_parents,
+            make_writer,
+        }
+    }
+}
+
+impl<S, W> Layer<S> for EcsLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        let fields = visitor.fields;
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanFields>() {
+            Some(SpanFields(existing)) => existing.extend(fields),
+            None => extensions.insert(SpanFields(fields)),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut ids = Vec::new();
+            for span in scope.from_root() {
+                ids.push(span_id(&span.id()));
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    extend_json_map(&mut fields, span_fields);
+                }
+            }
+
+            let mut hierarchy = Map::new();
+            if let Some(id) = ids.pop() {
+                let key = if has_trace_span_id(&self.options) {
+                    "tracing.span.id"
+                } else {
+                    "span.id"
+                };
+                hierarchy.insert(key.to_string(), id.into());
+            }
+            if self.span_parents && !ids.is_empty() {
+                hierarchy.insert(
+                    "tracing.parent.id".to_string(),
+                    ids[ids.len() - 1].clone().into(),
+                );
+                hierarchy.insert("tracing.span.parent_ids".to_string(), ids.into());
+            }
+            // The fields of the spans take precedence
+            extend_json_map(&mut hierarchy, &fields);
+            fields = hierarchy;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let message = visitor.message.take().unwrap_or_default();
+        extend_json_map(&mut fields, &visitor.fields);
+
+        let metadata = event.metadata();
+        let mut buf = Vec::new();
+        let result = with_scoped_fields(fields, || {
+            crate::write_event(
+                &mut buf,
+                &log::Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(to_log_level(*metadata.level()))
+                    .target(metadata.target())
+                    .module_path(metadata.module_path())
+                    .file(metadata.file())
+                    .line(metadata.line())
+                    .build(),
+                &self.options,
+            )
+        });
+        if result.is_ok() {
+            // Writes the whole line at once so that the lines of concurrent events are not interleaved
+            let _ = self.make_writer.make_writer_for(metadata).write_all(&buf);
+        }
+    }
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.fields.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // NaN and infinities are not valid in JSON
+        let value = Number::from_f64(value).map_or_else(|| value.to_string().into(), Value::Number);
+        self.insert(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.insert(field, value.into());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.insert(field, format!("{:?}", value).into());
+        }
+    }
+}
+
+/// Formats the ID of a span in 16 hexadecimal digits
+fn span_id(id: &Id) -> String {
+    format!("{:016x}", id.into_u64())
+}
+
+/// Returns whether the event gets the W3C `span.id` of a trace context, from the context fields or from `options`
+fn has_trace_span_id(options: &FormatOptions) -> bool {
+    let mut fields = merge_extra_fields(Map::new());
+    if let Some(trace_context) = &options.trace_context {
+        trace_context.insert_into(&mut fields);
+    }
+    fields.contains_key("span.id")
+}
+
+/// Converts a level of `tracing` into the level of the same name of `log`
+fn to_log_level(level: Level) -> log::Level {
+    match level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        fn events(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_layer() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(EcsLayer::new().with_writer({
+            let capture = capture.clone();
+            move || capture.clone()
+        }));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("request", http.request.method = "GET", user.id = 1);
+            let _outer = outer.enter();
+            let inner = tracing::debug_span!("query", db.rows = tracing::field::Empty, user.id = 2);
+            let _inner = inner.enter();
+            inner.record("db.rows", 3);
+
+            tracing::warn!(user.id = 3, ratio = 0.5, ok = true, path = ?["a"], "Slow {}", "query");
+            tracing::error!("failed");
+        });
+
+        let events = capture.events();
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0]["log.level"], "WARN");
+        assert_eq!(events[0]["message"], "Slow query");
+        assert_eq!(events[0]["log.origin"]["rust"]["target"], module_path!());
+        assert_eq!(events[0]["http.request.method"], "GET");
+        assert_eq!(events[0]["db.rows"], 3);
+        assert_eq!(events[0]["user.id"], 3);
+        assert_eq!(events[0]["ratio"], 0.5);
+        assert_eq!(events[0]["ok"], true);
+        assert_eq!(events[0]["path"], "[\"a\"]");
+
+        assert_eq!(events[1]["log.level"], "ERROR");
+        assert_eq!(events[1]["message"], "failed");
+        assert_eq!(events[1]["user.id"], 2);
+    }
+
+    #[test]
+    fn test_span_hierarchy() {
+        let capture = Capture::default();
+        let layer = EcsLayer::new().span_parents(true).with_writer({
+            let capture = capture.clone();
+            move || capture.clone()
+        });
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        let ids = tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("outside");
+            let outer = tracing::info_span!("outer");
+            let _outer = outer.enter();
+            tracing::info!("in outer");
+            let inner = tracing::info_span!("inner");
+            let _inner = inner.enter();
+            tracing::info!("in inner");
+            [outer.id().unwrap(), inner.id().unwrap()].map(|id| span_id(&id))
+        });
+
+        let events = capture.events();
+        assert!(events[0].get("span.id").is_none());
+
+        assert_eq!(events[1]["span.id"], ids[0]);
+        assert!(events[1].get("tracing.parent.id").is_none());
+        assert!(events[1].get("tracing.span.parent_ids").is_none());
+
+        assert_eq!(events[2]["span.id"], ids[1]);
+        assert_eq!(events[2]["tracing.parent.id"], ids[0]);
+        assert_eq!(
+            events[2]["tracing.span.parent_ids"],
+            serde_json::json!([ids[0]])
+        );
+        assert_eq!(ids[0].len(), 16);
+        assert!(events[2].get("tracing.span.id").is_none());
+    }
+
+    #[test]
+    fn test_span_with_trace_context() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(EcsLayer::new().with_writer({
+            let capture = capture.clone();
+            move || capture.clone()
+        }));
+
+        let id = tracing::subscriber::with_default(subscriber, || {
+            let _trace = crate::TraceContext::new("0af7651916cd43dd8448eb211c80319c")
+                .span_id("b7ad6b7169203331")
+                .enter();
+            let span = tracing::info_span!("request");
+            let _span = span.enter();
+            tracing::info!("handled");
+            span_id(&span.id().unwrap())
+        });
+
+        let events = capture.events();
+        assert_eq!(events[0]["trace.id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(events[0]["span.id"], "b7ad6b7169203331");
+        assert_eq!(events[0]["tracing.span.id"], id);
+    }
+}
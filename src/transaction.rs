@@ -0,0 +1,106 @@
+//! `transaction.*` fields for Elastic APM correlation
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// Elastic APM transaction which the log records belong to.
+///
+/// It adds `transaction.id` and `transaction.sampled` to the records, so Kibana can link the logs to the transaction
+/// the same way as for the official Elastic APM agents. `transaction.sampled` should be the sampling decision of the
+/// APM agent: the logs of unsampled transactions are still indexed, but there is no trace to link them to.
+///
+/// It can be attached like the other [`ToEcsFields`](crate::ToEcsFields) types.
+/// A provider registered with [`FormatOptions::trace_context`](crate::FormatOptions::trace_context) can report the
+/// transaction of the current thread with [`TraceContext::transaction_id`](crate::TraceContext::transaction_id) instead.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::Transaction;
+///
+/// ecs_logger::init();
+///
+/// Transaction::new("945254c567a5417e")
+///     .sampled(true)
+///     .in_scope(|| log::info!("Handling request"));
+/// // {"log.level":"INFO","message":"Handling request","transaction.id":"945254c567a5417e","transaction.sampled":true,...}
+///
+/// // Or set it at the start of a request, and clear it at the end
+/// let guard = Transaction::new("3f9f4b4a8e2c1d07").enter();
+/// log::info!("Handling request");
+/// guard.pop();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    id: String,
+    sampled: Option<bool>,
+}
+
+impl Transaction {
+    /// Creates a transaction with `transaction.id`, whose sampling decision is unknown.
+    pub fn new(id: impl Into<String>) -> Self {
+        Transaction {
+            id: id.into(),
+            sampled: None,
+        }
+    }
+
+    /// Sets `transaction.sampled`.
+    pub fn sampled(mut self, sampled: bool) -> Self {
+        self.sampled = Some(sampled);
+        self
+    }
+}
+
+impl Serialize for Transaction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("transaction.id", &self.id)?;
+        if let Some(sampled) = self.sampled {
+            map.serialize_entry("transaction.sampled", &sampled)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(Transaction::new("abc")).unwrap(),
+            json!({ "transaction.id": "abc" })
+        );
+        assert_eq!(
+            serde_json::to_value(Transaction::new("abc").sampled(false)).unwrap(),
+            json!({ "transaction.id": "abc", "transaction.sampled": false })
+        );
+    }
+
+    #[test]
+    fn test_in_scope() {
+        let fields = Transaction::new("abc")
+            .sampled(true)
+            .in_scope(scoped_fields);
+        assert_eq!(
+            Value::Object(fields),
+            json!({ "transaction.id": "abc", "transaction.sampled": true })
+        );
+        assert!(scoped_fields().is_empty());
+    }
+
+    #[test]
+    fn test_enter() {
+        let guard = Transaction::new("abc").enter();
+        assert_eq!(
+            Value::Object(scoped_fields()),
+            json!({ "transaction.id": "abc" })
+        );
+
+        guard.pop();
+        assert!(scoped_fields().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! `user.*` fields of the acting user
+
+use crate::siphash::siphash24;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// User who performs the actions which the log records are about.
+///
+/// It adds `user.id`, `user.name`, and `user.email` to the records.
+/// Enter it after authenticating a request, so all logs of the request carry the acting user, or attach it in another way of [`ToEcsFields`](crate::ToEcsFields).
+///
+/// The name and the email address are personal data in many jurisdictions.
+/// [`hashed`](Self::hashed) replaces them with keyed hashes, which still correlate the records of the same user.
+///
+/// # Example
+///
+/// ```
+/// use ecs_logger::UserContext;
+///
+/// ecs_logger::init();
+///
+/// let _user = UserContext::new("42")
+///     .name("alice")
+///     .email("alice@example.com")
+///     .hashed(*b"my 128-bit key!!")
+///     .enter();
+/// log::info!("Updated profile");
+/// // {"log.level":"INFO","message":"Updated profile","user.id":"42","user.name":"<hash>","user.email":"<hash>",...}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserContext {
+    id: String,
+    name: Option<String>,
+    email: Option<String>,
+}
+
+impl UserContext {
+    /// Creates a context of the user with `user.id`.
+    pub fn new(id: impl Into<String>) -> Self {
+        UserContext {
+            id: id.into(),
+            name: None,
+            email: None,
+        }
+    }
+
+    /// Sets `user.name`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets `user.email`.
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Replaces `user.name` and `user.email` with their SipHash-2-4 keyed with the 128-bit secret `key`, in 16 hexadecimal digits.
+    ///
+    /// The hashes are stable across builds and platforms. Without the key, they cannot be computed for guessed values, nor inverted.
+    pub fn hashed(mut self, key: [u8; 16]) -> Self {
+        let hash = |value: String| format!("{:016x}", siphash24(&key, value.as_bytes()));
+        self.name = self.name.map(hash);
+        self.email = self.email.map(hash);
+        self
+    }
+}
+
+impl Serialize for UserContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("user.id", &self.id)?;
+        if let Some(name) = &self.name {
+            map.serialize_entry("user.name", name)?;
+        }
+        if let Some(email) = &self.email {
+            map.serialize_entry("user.email", email)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_fields::scoped_fields;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(UserContext::new("42")).unwrap(),
+            json!({ "user.id": "42" })
+        );
+        assert_eq!(
+            serde_json::to_value(
+                UserContext::new("42")
+                    .name("alice")
+                    .email("alice@example.com")
+            )
+            .unwrap(),
+            json!({ "user.id": "42", "user.name": "alice", "user.email": "alice@example.com" })
+        );
+    }
+
+    #[test]
+    fn test_hashed() {
+        let user = UserContext::new("42")
+            .name("alice")
+            .hashed(*b"0123456789abcdef");
+        let fields = user.in_scope(scoped_fields);
+        assert_eq!(fields["user.id"], "42");
+
+        let name = fields["user.name"].as_str().unwrap();
+        assert_eq!(name.len(), 16);
+        assert_ne!(name, "alice");
+        assert_eq!(
+            Value::Object(fields.clone()),
+            serde_json::to_value(
+                UserContext::new("42")
+                    .name("alice")
+                    .hashed(*b"0123456789abcdef")
+            )
+            .unwrap()
+        );
+        assert_ne!(
+            serde_json::to_value(
+                UserContext::new("42")
+                    .name("alice")
+                    .hashed(*b"fedcba9876543210")
+            )
+            .unwrap(),
+            Value::Object(fields)
+        );
+    }
+}
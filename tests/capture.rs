@@ -0,0 +1,253 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests {
+    use ecs_logger::test::with_capture;
+    use log::{debug, info};
+
+    #[test]
+    fn test_with_capture() {
+        with_capture(|events| {
+            info!("Hello {}!", "world");
+            debug!("Goodbye");
+
+            assert_eq!(events.messages(), vec!["Hello world!", "Goodbye"]);
+            assert_eq!(events.to_vec()[1]["log.level"], "DEBUG");
+        });
+
+        // Outside of a capture, events are dropped
+        info!("Not captured");
+    }
+
+    #[test]
+    fn test_with_capture_nested() {
+        with_capture(|outer| {
+            info!("outer");
+
+            with_capture(|inner| {
+                info!("inner");
+                assert_eq!(inner.messages(), vec!["inner"]);
+            });
+
+            info!("outer again");
+            assert_eq!(outer.messages(), vec!["outer", "outer again"]);
+        });
+    }
+
+    #[test]
+    fn test_with_capture_other_thread() {
+        with_capture(|events| {
+            std::thread::spawn(|| info!("other thread")).join().unwrap();
+            assert!(events.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_result_ext() {
+        use ecs_logger::ResultExt;
+
+        with_capture(|events| {
+            let result = "abc".parse::<i32>().log_warn("parsing port");
+            assert!(result.is_err());
+            assert_eq!(Ok::<_, std::fmt::Error>(1).log_err("unreachable"), Ok(1));
+
+            let events = events.to_vec();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0]["log.level"], "WARN");
+            assert_eq!(events[0]["message"], "parsing port");
+            assert_eq!(events[0]["error.message"], "invalid digit found in string");
+            assert_eq!(events[0]["log.origin"]["file"]["name"], "capture.rs");
+        });
+    }
+
+    #[test]
+    fn test_span_timer() {
+        with_capture(|events| {
+            {
+                let _timer = ecs_logger::span_timer("rebuild index");
+            }
+            ecs_logger::span_timer("compact")
+                .with_level(log::Level::Warn)
+                .fail();
+
+            let events = events.to_vec();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0]["message"], "rebuild index");
+            assert_eq!(events[0]["log.level"], "INFO");
+            assert_eq!(events[0]["event.outcome"], "success");
+            assert!(events[0]["event.duration"].is_u64());
+            assert_eq!(events[1]["log.level"], "WARN");
+            assert_eq!(events[1]["event.outcome"], "failure");
+        });
+    }
+
+    #[test]
+    fn test_ecs_log() {
+        with_capture(|events| {
+            let name = "alice";
+            ecs_logger::ecs_info!(user.name = name, event.action = "login", attempts = 2; "user {} logged in", name);
+            ecs_logger::ecs_warn!(target: "auth", labels.reason = "slow"; "slow login");
+            ecs_logger::ecs_error!("failed");
+
+            let events = events.to_vec();
+            assert_eq!(events.len(), 3);
+
+            assert_eq!(events[0]["log.level"], "INFO");
+            assert_eq!(events[0]["message"], "user alice logged in");
+            assert_eq!(events[0]["message_template"], "user {} logged in");
+            assert_eq!(events[0]["log.origin"]["function"], "test_ecs_log");
+            assert_eq!(events[0]["user.name"], "alice");
+            assert_eq!(events[0]["event.action"], "login");
+            assert_eq!(events[0]["attempts"], 2);
+
+            assert_eq!(events[1]["log.level"], "WARN");
+            assert_eq!(events[1]["labels.reason"], "slow");
+
+            assert_eq!(events[2]["message"], "failed");
+            assert!(events[2].get("user.name").is_none());
+        });
+    }
+
+    #[test]
+    fn test_ecs_log_fields() {
+        with_capture(|events| {
+            let user = ecs_logger::UserContext::new("42").name("alice");
+            ecs_logger::ecs_info!(fields: user, user.name = "bob"; "logged in");
+            ecs_logger::ecs_info!(fields: &user; "logged out");
+
+            let events = events.to_vec();
+            assert_eq!(events[0]["user.id"], "42");
+            assert_eq!(events[0]["user.name"], "bob");
+            assert_eq!(events[1]["user.name"], "alice");
+        });
+    }
+
+    #[test]
+    fn test_ecs_error_chain() {
+        #[derive(Debug)]
+        struct ConfigError(std::io::Error);
+
+        impl std::fmt::Display for ConfigError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("failed to load config")
+            }
+        }
+
+        impl std::error::Error for ConfigError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        with_capture(|events| {
+            let err = ConfigError(std::io::Error::other("config.toml not found"));
+            ecs_logger::ecs_error!(error: err, file.name = "config.toml"; "Startup failed in {} ms", 12);
+
+            let boxed: Box<dyn std::error::Error + Send + Sync> = "invalid port".into();
+            ecs_logger::ecs_warn!(error: boxed; "Using the default port");
+
+            let events = events.to_vec();
+            assert_eq!(events[0]["message"], "Startup failed in 12 ms");
+            assert_eq!(events[0]["error.message"], "failed to load config");
+            assert!(events[0]["error.type"]
+                .as_str()
+                .unwrap()
+                .ends_with("ConfigError"));
+            assert!(events[0]["error.stack_trace"]
+                .as_str()
+                .unwrap()
+                .starts_with("failed to load config\nCaused by: config.toml not found"));
+            assert_eq!(events[0]["file.name"], "config.toml");
+
+            assert_eq!(events[1]["log.level"], "WARN");
+            assert_eq!(events[1]["error.message"], "invalid port");
+            assert!(events[1].get("error.type").is_none());
+        });
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_ecs_error_anyhow() {
+        use anyhow::Context;
+
+        with_capture(|events| {
+            let err = "abc".parse::<u16>().context("invalid port").unwrap_err();
+            ecs_logger::ecs_error!(error: err; "Startup failed");
+
+            let events = events.to_vec();
+            assert_eq!(events[0]["error.message"], "invalid port");
+            assert!(events[0]["error.stack_trace"]
+                .as_str()
+                .unwrap()
+                .starts_with("invalid port\nCaused by: invalid digit found in string"));
+        });
+    }
+
+    #[test]
+    fn test_ecs_timed() {
+        fn parse(s: &str) -> Result<u16, std::num::ParseIntError> {
+            ecs_logger::ecs_timed!("parse-port", { s.parse() })
+        }
+
+        fn parse_early(s: &str) -> Result<u16, std::num::ParseIntError> {
+            ecs_logger::ecs_timed!("parse-port-early", {
+                let port = s.parse::<u16>()?;
+                Ok(port)
+            })
+        }
+
+        with_capture(|events| {
+            assert_eq!(parse("8080"), Ok(8080));
+            assert!(parse("abc").is_err());
+            assert!(parse_early("abc").is_err());
+            let sum = ecs_logger::ecs_timed!(String::from("sum"), { 1 + 2 });
+            assert_eq!(sum, 3);
+
+            let events = events.to_vec();
+            assert_eq!(events.len(), 4);
+            assert_eq!(events[0]["message"], "parse-port");
+            assert_eq!(events[0]["event.action"], "parse-port");
+            assert!(events[0]["event.duration"].is_u64());
+            assert_eq!(events[0]["event.outcome"], "success");
+            assert_eq!(events[1]["event.outcome"], "failure");
+            assert_eq!(events[2]["event.outcome"], "unknown");
+            assert_eq!(events[3]["event.action"], "sum");
+            assert_eq!(events[3]["event.outcome"], "success");
+            assert_eq!(events[3]["log.origin"]["file"]["name"], "capture.rs");
+        });
+    }
+
+    #[test]
+    fn test_audit() {
+        with_capture(|events| {
+            let admin = "alice";
+            ecs_logger::audit!(actor: admin, action: "role-grant", target: "bob", group.name = "admins"; "{} granted a role", admin);
+            ecs_logger::audit!(
+                actor: 42,
+                action: "user-login",
+                target: "bob",
+                event.category = ["authentication"],
+                event.outcome = "failure";
+                "Impersonation denied"
+            );
+
+            let events = events.to_vec();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0]["log.level"], "INFO");
+            assert_eq!(events[0]["message"], "alice granted a role");
+            assert_eq!(events[0]["event.kind"], "event");
+            assert_eq!(events[0]["event.category"], serde_json::json!(["iam"]));
+            assert_eq!(events[0]["event.action"], "role-grant");
+            assert_eq!(events[0]["user.name"], "alice");
+            assert_eq!(events[0]["user.target.name"], "bob");
+            assert_eq!(events[0]["group.name"], "admins");
+
+            assert_eq!(events[1]["user.name"], 42);
+            assert_eq!(
+                events[1]["event.category"],
+                serde_json::json!(["authentication"])
+            );
+            assert_eq!(events[1]["event.outcome"], "failure");
+        });
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    const CHILD_ENV: &str = "ECS_LOGGER_FLUSH_ON_EXIT_CHILD";
+
+    /// Runs `test_name` in a child process with [`CHILD_ENV`] set, and returns its stdout
+    fn run_child(test_name: &str) -> String {
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", test_name, "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn init_child() {
+        ecs_logger::Builder::new()
+            .filter_level(log::LevelFilter::Info)
+            .deferred_formatting(1024)
+            .flush_on_exit(true)
+            .target(ecs_logger::Target::Stdout)
+            .init();
+
+        for i in 0..100 {
+            log::info!("event {}", i);
+        }
+    }
+
+    #[test]
+    fn test_flush_on_exit() {
+        if std::env::var_os(CHILD_ENV).is_some() {
+            init_child();
+            std::process::exit(0);
+        }
+
+        let stdout = run_child("tests::test_flush_on_exit");
+        assert!(stdout.contains(r#""message":"event 99""#), "{}", stdout);
+    }
+
+    #[test]
+    fn test_flush_on_panic() {
+        if std::env::var_os(CHILD_ENV).is_some() {
+            init_child();
+            panic!("crash");
+        }
+
+        let stdout = run_child("tests::test_flush_on_panic");
+        assert!(stdout.contains(r#""message":"event 99""#), "{}", stdout);
+    }
+}
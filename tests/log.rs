@@ -1,3 +1,5 @@
+#![cfg(feature = "env_logger")]
+
 #[cfg(test)]
 mod tests {
     use ecs_logger::extra_fields;
@@ -53,11 +55,17 @@ mod tests {
     static SINK: Lazy<Mutex<sink::Sink>> = Lazy::new(|| {
         let (sink, writer) = sink::create();
 
-        env_logger::builder()
+        let mut builder = env_logger::builder();
+        builder
             .parse_filters("trace")
             .format(ecs_logger::format)
-            .target(env_logger::Target::Pipe(Box::new(writer)))
-            .init();
+            .target(env_logger::Target::Pipe(Box::new(writer)));
+        // The `host.*` fields differ between machines
+        #[cfg(feature = "host")]
+        builder.format(ecs_logger::format_with(
+            ecs_logger::FormatOptions::new().host_metadata(false),
+        ));
+        builder.init();
 
         Mutex::new(sink)
     });
@@ -74,11 +82,11 @@ mod tests {
         trace!("baz {}", false);
 
         let output = sink.read();
-        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"ERROR","message":"error 123!","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
-\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"WARN","message":"foo","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
-\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"456","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
-\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"DEBUG","message":"bar abc","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
-\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"TRACE","message":"baz false","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
+        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"ERROR","message":"error 123!","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
+\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"WARN","message":"foo","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
+\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"456","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
+\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"DEBUG","message":"bar abc","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
+\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"TRACE","message":"baz false","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
 $"#).unwrap();
         assert!(re.is_match(&output));
     }
@@ -91,7 +99,7 @@ $"#).unwrap();
         log!(target: "example_target", log::Level::Info, "log with {:?}!", "custom target".to_string());
 
         let output = sink.read();
-        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"log with \\"custom target\\"!","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"example_target","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}}}
+        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"log with \\"custom target\\"!","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"example_target","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}}}
 $"#).unwrap();
         assert!(re.is_match(&output));
     }
@@ -108,8 +116,25 @@ $"#).unwrap();
         info!("hello world");
 
         let output = sink.read();
-        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"hello world","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"foo":"bar","baz":123}
+        let re = Regex::new(r#"^\{"@timestamp":"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d+Z","log\.level":"INFO","message":"hello world","ecs\.version":"1\.12\.1","log\.origin":\{"file":\{"line":\d+,"name":"log\.rs"},"rust":\{"target":"log::tests","module_path":"log::tests","file_path":"tests(?:/|\\\\)log\.rs"}},"process":\{"thread":\{"id":\d+,"name":"[^"]+"}},"foo":"bar","baz":123}
 $"#).unwrap();
         assert!(re.is_match(&output));
     }
+
+    #[test]
+    fn test_tags() {
+        let sink = SINK.lock().unwrap();
+        extra_fields::set_extra_fields(json!({ "tags": ["api", "v2"] })).unwrap();
+        ecs_logger::set_tags(["production", "api"]);
+        ecs_logger::add_tag("eu-west-1");
+
+        info!("hello world");
+
+        ecs_logger::clear_tags();
+        extra_fields::clear_extra_fields();
+
+        let output = sink.read();
+        let re = Regex::new(r#""tags":\["production","api","eu-west-1","v2"]}\n$"#).unwrap();
+        assert!(re.is_match(&output));
+    }
 }
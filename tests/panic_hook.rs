@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    const CHILD_ENV: &str = "ECS_LOGGER_PANIC_HOOK_CHILD";
+
+    #[test]
+    fn test_install_panic_hook() {
+        if std::env::var_os(CHILD_ENV).is_some() {
+            ecs_logger::Builder::new()
+                .filter_level(log::LevelFilter::Info)
+                .deferred_formatting(1024)
+                .target(ecs_logger::Target::Stdout)
+                .init();
+            ecs_logger::install_panic_hook();
+
+            log::info!("starting");
+            std::thread::Builder::new()
+                .name("worker".to_string())
+                .spawn(|| panic!("invalid state {}", 42))
+                .unwrap()
+                .join()
+                .unwrap_err();
+            std::process::exit(0);
+        }
+
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "tests::test_install_panic_hook",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        let event = stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .find(|event| event["log.level"] == "FATAL")
+            .unwrap_or_else(|| panic!("no FATAL event in {}", stdout));
+        assert_eq!(event["message"], "thread 'worker' panicked");
+        assert_eq!(event["error.message"], "invalid state 42");
+        assert_eq!(event["error.type"], "panic");
+        assert!(event["error.stack_trace"].is_string());
+        assert_eq!(event["log.origin"]["file"]["name"], "panic_hook.rs");
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("invalid state"), "{}", stderr);
+    }
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests {
+    use ecs_logger::{log_every_n, log_once};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// This struct is used as an adaptor, it implements io::Write and appends the buffer to a shared [`Vec`]
+    #[derive(Clone, Default)]
+    struct Writer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Writer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rate_limited_macros() {
+        let writer = Writer::default();
+        ecs_logger::Builder::new()
+            .parse_filters("trace")
+            .target(ecs_logger::Target::Pipe(Box::new(writer.clone())))
+            .init();
+
+        for i in 0..10 {
+            log_once!(log::Level::Warn, "once {}", i);
+            log_every_n!(4, log::Level::Info, "every 4th {}", i);
+        }
+
+        let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let events = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|v| (v["message"].clone(), v["log.skipped"].clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            events,
+            vec![
+                ("once 0".into(), serde_json::Value::Null),
+                ("every 4th 0".into(), serde_json::Value::Null),
+                ("every 4th 4".into(), 3.into()),
+                ("every 4th 8".into(), 3.into()),
+            ]
+        );
+    }
+}